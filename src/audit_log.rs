@@ -0,0 +1,270 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{models::LightRequest, storage::STORAGE_ENV_KEY};
+
+const AUDIT_MAX_BYTES_ENV_KEY: &str = "RIZ_AUDIT_MAX_BYTES";
+const DEFAULT_AUDIT_MAX_BYTES: u64 = 1024 * 1024;
+
+/// A single dispatched lighting command, as recorded in the audit log
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct AuditEntry {
+    /// When the command was dispatched, in seconds since the unix epoch
+    timestamp: u64,
+
+    /// Room the light belongs to, if known
+    room_id: Option<Uuid>,
+
+    /// Light the command was sent to, if known
+    light_id: Option<Uuid>,
+
+    /// IPv4 address the command was sent to
+    #[schema(value_type = String, example = "192.168.1.50")]
+    ip: Ipv4Addr,
+
+    /// The requested lighting settings
+    request: LightRequest,
+
+    /// Where the command came from (`api` or `presence`)
+    source: String,
+}
+
+impl AuditEntry {
+    /// Create a new entry for a command dispatched now
+    pub fn new(
+        room_id: Option<Uuid>,
+        light_id: Option<Uuid>,
+        ip: Ipv4Addr,
+        request: &LightRequest,
+        source: &str,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        AuditEntry {
+            timestamp,
+            room_id,
+            light_id,
+            ip,
+            request: request.clone(),
+            source: source.to_string(),
+        }
+    }
+
+    /// Accessor for when the command was dispatched (unix seconds)
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Accessor for the room ID, if known
+    pub fn room_id(&self) -> Option<&Uuid> {
+        self.room_id.as_ref()
+    }
+
+    /// Accessor for the light ID, if known
+    pub fn light_id(&self) -> Option<&Uuid> {
+        self.light_id.as_ref()
+    }
+
+    /// Accessor for the IP the command was sent to
+    pub fn ip(&self) -> Ipv4Addr {
+        self.ip
+    }
+
+    /// Accessor for the requested lighting settings
+    pub fn request(&self) -> &LightRequest {
+        &self.request
+    }
+
+    /// Accessor for where the command came from
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+/// Append-only log of dispatched commands, `audit.jsonl` in `RIZ_STORAGE_PATH`
+///
+/// Each line is one JSON [AuditEntry]. Once the log grows past
+/// `RIZ_AUDIT_MAX_BYTES` (env var, default 1MiB) it is rotated to
+/// `audit.jsonl.1`, replacing any previous rotation.
+///
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    file_path: PathBuf,
+    max_bytes: u64,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuditLog {
+    /// Create a new audit log in the configured storage path
+    pub fn new() -> Self {
+        let path = env::var(STORAGE_ENV_KEY).unwrap_or(".".to_string());
+        let max_bytes = env::var(AUDIT_MAX_BYTES_ENV_KEY)
+            .ok()
+            .and_then(|max| max.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_AUDIT_MAX_BYTES);
+
+        Self::with_path(Path::new(&path).join("audit.jsonl"), max_bytes)
+    }
+
+    /// Create a new audit log at the given file path and rotation size
+    pub fn with_path(file_path: PathBuf, max_bytes: u64) -> Self {
+        AuditLog {
+            file_path,
+            max_bytes,
+        }
+    }
+
+    /// Append the entry to the log, rotating first if needed
+    ///
+    /// Failures are logged, auditing should never block a command
+    ///
+    pub fn record(&self, entry: &AuditEntry) {
+        let line = match serde_json::to_string(entry) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Failed to dump audit entry: {:?}", e);
+                return;
+            }
+        };
+
+        self.rotate();
+
+        let res = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file_path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+
+        if let Err(e) = res {
+            error!("Failed to write audit entry: {:?}", e);
+        }
+    }
+
+    /// Read entries from the log (including the last rotation), oldest first
+    ///
+    /// Optionally filtered to a light ID, and/or entries at or after
+    /// `since` (seconds since the unix epoch)
+    ///
+    pub fn query(&self, light_id: Option<&Uuid>, since: Option<u64>) -> Vec<AuditEntry> {
+        let mut entries = Vec::new();
+        for path in [self.rotated_path(), self.file_path.clone()] {
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+
+            for line in content.lines() {
+                match serde_json::from_str::<AuditEntry>(line) {
+                    Ok(entry) => {
+                        if light_id.is_some() && entry.light_id.as_ref() != light_id {
+                            continue;
+                        }
+                        if since.map_or(false, |since| entry.timestamp < since) {
+                            continue;
+                        }
+                        entries.push(entry);
+                    }
+                    Err(e) => warn!("Skipping invalid audit entry: {:?}", e),
+                }
+            }
+        }
+        entries
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut path = self.file_path.clone().into_os_string();
+        path.push(".1");
+        PathBuf::from(path)
+    }
+
+    fn rotate(&self) {
+        let size = match fs::metadata(&self.file_path) {
+            Ok(meta) => meta.len(),
+            Err(_) => return,
+        };
+
+        if size >= self.max_bytes {
+            if let Err(e) = fs::rename(&self.file_path, self.rotated_path()) {
+                error!("Failed to rotate audit log: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{distributions::Alphanumeric, Rng};
+
+    use super::*;
+    use crate::models::PowerMode;
+
+    fn temp_path() -> PathBuf {
+        let s: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(12)
+            .map(char::from)
+            .collect();
+
+        let mut base = env::temp_dir();
+        base.push(s);
+        fs::create_dir_all(&base).unwrap();
+        base
+    }
+
+    #[test]
+    fn record_and_query() {
+        let base = temp_path();
+        let audit = AuditLog::with_path(base.join("audit.jsonl"), 1024 * 1024);
+
+        let light_id = Uuid::new_v4();
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let req = LightRequest::from_power(PowerMode::On);
+
+        audit.record(&AuditEntry::new(None, Some(light_id), ip, &req, "api"));
+        audit.record(&AuditEntry::new(None, None, ip, &req, "api"));
+
+        assert_eq!(audit.query(None, None).len(), 2);
+        assert_eq!(audit.query(Some(&light_id), None).len(), 1);
+        assert_eq!(audit.query(None, Some(u64::MAX)).len(), 0);
+
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    fn rotates_by_size() {
+        let base = temp_path();
+        let audit = AuditLog::with_path(base.join("audit.jsonl"), 1);
+
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let req = LightRequest::from_power(PowerMode::Off);
+        for _ in 0..3 {
+            audit.record(&AuditEntry::new(None, None, ip, &req, "api"));
+        }
+
+        // only the current file and the last rotation are kept
+        assert!(base.join("audit.jsonl.1").exists());
+        assert_eq!(audit.query(None, None).len(), 2);
+
+        fs::remove_dir_all(base).unwrap();
+    }
+}
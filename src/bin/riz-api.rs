@@ -1,12 +1,54 @@
-use std::{env, error::Error, net::Ipv4Addr, sync::Mutex};
+use std::{
+    env,
+    error::Error,
+    io,
+    net::Ipv4Addr,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
 
 use actix_cors::Cors;
-use actix_web::{http::header, middleware::Logger, web::Data, App, HttpServer, Result};
-use log::info;
+use actix_web::{
+    dev::Service,
+    http::header::{self, HeaderName, HeaderValue},
+    middleware::Logger,
+    web::{self, Data, JsonConfig, ServiceConfig},
+    App, HttpResponse, HttpServer, Result,
+};
+use log::{error, info, warn};
 use utoipa::OpenApi;
-use utoipa_swagger_ui::SwaggerUi;
+use utoipa_swagger_ui::{Config, SwaggerUi};
 
-use riz::{health, lights, models, rooms, Storage, Worker};
+use riz::{
+    audit,
+    config::{self, EffectiveConfig},
+    discover, health, lights, lock, maintenance,
+    models::{self, Light, LightingResponse, Room},
+    operations, presence, rooms, scenes, start_presence, ActiveHours, AuditEntry, Presence,
+    PresenceSchedule, RequestId, Storage, Worker, WriteHealth, REQUEST_ID_HEADER,
+};
+
+/// Default limit for JSON request bodies, in bytes
+const DEFAULT_MAX_BODY_BYTES: usize = 256 * 1024;
+
+/// Default time for clients to send their request headers, in seconds
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 5;
+
+/// Where the OpenAPI spec is served, with or without the swagger UI
+const OPENAPI_PATH: &str = "/v1/api-docs/openapi.json";
+
+/// Stop warming any further rooms once this long has passed since startup
+const WARM_STATUS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often to retry writing `rooms.json` after a write fails
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often bulbs are registered again to keep pushing their status
+const REGISTER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Access log format, the default with each request's ID appended
+const LOG_FORMAT: &str = r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T req=%{x-request-id}o"#;
 
 fn get_port() -> u16 {
     let port = env::var("RIZ_PORT").unwrap_or(String::from("8080"));
@@ -19,90 +61,476 @@ fn get_port() -> u16 {
     }
 }
 
+/// Limit for JSON request bodies, larger bodies are refused with a 413
+///
+/// Set in bytes with `RIZ_MAX_BODY_BYTES` (env var), defaults to 256KB
+///
+fn get_max_body_bytes() -> usize {
+    env::var("RIZ_MAX_BODY_BYTES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_BODY_BYTES)
+}
+
+/// Time allowed for clients to send their request headers, after which
+/// the request is refused with a 408
+///
+/// Set in seconds with `RIZ_REQUEST_TIMEOUT_SECS` (env var), defaults to 5
+///
+fn get_request_timeout() -> Duration {
+    let secs = env::var("RIZ_REQUEST_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Allowed CORS origin, set with `RIZ_CORS_ORIGIN` (env var)
+///
+/// Either `*` to allow any origin, or a single `scheme://host[:port]`
+///
+fn get_cors_origin() -> String {
+    env::var("RIZ_CORS_ORIGIN").unwrap_or(String::from("http://localhost:8000"))
+}
+
+/// Check the CORS origin is `*` or a `scheme://host[:port]`
+///
+/// Browsers compare the origin exactly, so anything else (a trailing
+/// slash, a path) quietly blocks every request from the UI
+///
+fn validate_cors_origin(origin: &str) -> std::result::Result<(), String> {
+    if origin == "*" {
+        return Ok(());
+    }
+
+    let (scheme, rest) = origin
+        .split_once("://")
+        .ok_or("expected scheme://host[:port]")?;
+
+    let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    if !valid_scheme {
+        return Err(format!("invalid scheme: {:?}", scheme));
+    }
+
+    if rest.contains(['/', '?', '#']) {
+        return Err("origins have no path, query or trailing slash".to_string());
+    }
+
+    let (host, port) = match rest.rsplit_once(':') {
+        Some((host, port)) if !port.ends_with(']') => (host, Some(port)),
+        _ => (rest, None),
+    };
+    if host.is_empty() || host.contains(char::is_whitespace) {
+        return Err(format!("invalid host: {:?}", host));
+    }
+    if let Some(port) = port {
+        if port.parse::<u16>().is_err() {
+            return Err(format!("invalid port: {:?}", port));
+        }
+    }
+    Ok(())
+}
+
+/// Warm status for all lights on startup, see [warm_status]
+fn warm_status_enabled() -> bool {
+    env::var("RIZ_WARM_STATUS").map_or(false, |v| v == "true")
+}
+
+/// Mount path for the swagger UI, or [None] if it's disabled
+///
+/// Disable with `RIZ_ENABLE_SWAGGER=false`, and move it from the default
+/// `/v1/swagger-ui` with `RIZ_SWAGGER_PATH` (env vars)
+///
+fn swagger_path() -> Option<String> {
+    if env::var("RIZ_ENABLE_SWAGGER").map_or(false, |v| v == "false") {
+        return None;
+    }
+    let path = env::var("RIZ_SWAGGER_PATH").unwrap_or(String::from("/v1/swagger-ui"));
+    Some(path.trim_end_matches('/').to_string())
+}
+
+/// Serve the OpenAPI spec, along with the swagger UI if it's enabled
+///
+/// The spec is built once and shared by all workers, the swagger UI
+/// loads it from [OPENAPI_PATH] rather than holding its own copy
+///
+fn api_docs(
+    cfg: &mut ServiceConfig,
+    swagger: Option<&str>,
+    openapi: &Data<utoipa::openapi::OpenApi>,
+) {
+    cfg.app_data(Data::clone(openapi))
+        .route(OPENAPI_PATH, web::get().to(openapi_spec));
+
+    if let Some(path) = swagger {
+        cfg.service(
+            SwaggerUi::new(format!("{}/{{_:.*}}", path)).config(Config::from(OPENAPI_PATH)),
+        );
+    }
+}
+
+/// Serve the shared OpenAPI spec
+async fn openapi_spec(openapi: Data<utoipa::openapi::OpenApi>) -> HttpResponse {
+    HttpResponse::Ok().json(openapi.get_ref())
+}
+
+/// Retry writing `rooms.json` in the background while it's failing
+///
+/// Bulb replies are kept in memory when a write fails, see [Storage::is_dirty],
+/// so they're written once storage recovers rather than lost on restart
+///
+fn retry_writes(storage: &Data<Mutex<Storage>>) {
+    let storage = Data::clone(storage);
+    thread::spawn(move || loop {
+        thread::sleep(FLUSH_INTERVAL);
+        match lock(&storage).flush() {
+            Ok(true) => info!("Wrote pending changes to rooms.json"),
+            Ok(false) => {}
+            Err(e) => warn!("Still unable to write rooms.json: {}", e),
+        }
+    });
+}
+
+/// Address and mac bulbs push their status to, see [push_status]
+///
+/// Set with `RIZ_PUSH_IP` and `RIZ_PUSH_MAC` (env vars), both are needed
+///
+fn push_listener() -> Option<(Ipv4Addr, String)> {
+    let ip = env::var("RIZ_PUSH_IP").ok()?;
+    let mac = env::var("RIZ_PUSH_MAC").ok()?;
+    match ip.parse::<Ipv4Addr>() {
+        Ok(ip) => Some((ip, mac)),
+        Err(e) => {
+            error!("Invalid RIZ_PUSH_IP {:?}: {}", ip, e);
+            None
+        }
+    }
+}
+
+/// Have bulbs push their status to the API, rather than polling them
+///
+/// Enabled with `RIZ_PUSH_IP` and `RIZ_PUSH_MAC`, the address of this
+/// host as the bulbs see it. The [Worker] listens for pushed status, and
+/// every known light is registered again each [REGISTER_INTERVAL].
+///
+fn push_status(storage: &Data<Mutex<Storage>>, worker: &Data<Mutex<Worker>>) {
+    let (ip, mac) = match push_listener() {
+        Some(listener) => listener,
+        None => return,
+    };
+
+    if let Err(e) = lock(worker).listen_for_pushes() {
+        error!("Failed to listen for pushed status: {}", e);
+        return;
+    }
+
+    let storage = Data::clone(storage);
+    thread::spawn(move || loop {
+        let lights: Vec<Light> = lock(&storage)
+            .iter_lights()
+            .filter(|(_, _, light)| light.enabled())
+            .map(|(_, _, light)| light.clone())
+            .collect();
+        for light in lights {
+            if let Err(e) = light.register(ip, &mac) {
+                warn!("Failed to register {} for pushed status: {}", light.ip(), e);
+            }
+        }
+        thread::sleep(REGISTER_INTERVAL);
+    });
+}
+
+/// Fetch live status for every known light in the background
+///
+/// Enabled with `RIZ_WARM_STATUS=true` (env var). Each room's stored
+/// lights are read with [Room::read_statuses], so the per-light timeouts,
+/// aliases and `RIZ_STATUS_CONCURRENCY` cap apply, and replies are queued
+/// as updates with the [Worker], so the first requests after startup
+/// don't show stale `rooms.json` data.
+///
+fn warm_status(storage: &Data<Mutex<Storage>>, worker: &Data<Mutex<Worker>>) {
+    if !warm_status_enabled() {
+        return;
+    }
+
+    let rooms: Vec<Room> = {
+        let data = lock(storage);
+        data.list()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| data.read(id))
+            .collect()
+    };
+    let worker = Data::clone(worker);
+
+    thread::spawn(move || {
+        let request_id = RequestId::new();
+        let total: usize = rooms.iter().map(|room| room.enabled_lights().count()).sum();
+        info!("[{}] Warming status for {} lights", request_id, total);
+
+        let deadline = Instant::now() + WARM_STATUS_TIMEOUT;
+        let mut warmed = 0;
+        for room in &rooms {
+            if Instant::now() >= deadline {
+                warn!("Timed out warming status");
+                break;
+            }
+            for (light_id, fetched) in room.read_statuses() {
+                let ip = match room.read(&light_id) {
+                    Some(light) => light.ip(),
+                    None => continue,
+                };
+                match fetched {
+                    Ok(status) => {
+                        let resp = LightingResponse::status(ip, status);
+                        match lock(&worker).queue_update(resp, &request_id) {
+                            Ok(_) => warmed += 1,
+                            Err(e) => warn!("Failed to queue status for {}: {}", ip, e),
+                        }
+                    }
+                    Err(e) => warn!("Failed to warm status for {}: {}", ip, e),
+                }
+            }
+        }
+
+        info!("Warmed status for {}/{} lights", warmed, total);
+    });
+}
+
 #[actix_web::main]
 async fn main() -> Result<(), impl Error> {
     env::set_var("RUST_LOG", "debug");
     env_logger::init();
 
+    let cors_origin = get_cors_origin();
+    if let Err(e) = validate_cors_origin(&cors_origin) {
+        error!("Invalid RIZ_CORS_ORIGIN {:?}: {}", cors_origin, e);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+    }
+
     #[derive(OpenApi)]
     #[openapi(
         paths(
             health::ping,
+            health::ready,
             rooms::create,
             rooms::list,
             rooms::read,
+            rooms::read_by_name,
             rooms::update,
             rooms::destroy,
             rooms::status,
+            rooms::batch_status,
+            rooms::reachability,
+            rooms::reboot,
+            rooms::reset,
+            rooms::export_preset,
+            rooms::import_preset,
             lights::create,
             lights::update,
             lights::destroy,
+            lights::destroy_by_ip,
             lights::update_room,
+            lights::apply,
             lights::update_light,
             lights::status,
+            lights::pilot,
+            lights::random,
+            lights::nudge_brightness,
+            lights::flash,
+            lights::sync,
+            lights::debug,
+            lights::ips,
+            discover::discover,
+            maintenance::prune,
+            operations::read,
+            presence::read,
+            presence::update,
+            presence::enable,
+            presence::disable,
+            scenes::list,
+            scenes::favorites,
+            audit::list,
+            config::read,
         ),
         components(schemas(
             models::Room,
+            rooms::CreatedRoom,
+            models::RoomPreset,
+            rooms::RoomsStatusRequest,
+            rooms::RoomStatus,
+            rooms::LightReachability,
+            rooms::RoomSort,
             models::Light,
             models::LightRequest,
+            models::LightFilter,
+            models::LightRef,
+            lights::ApplyRequest,
+            lights::FlashRequest,
             models::LightStatus,
+            models::UdpExchange,
+            models::BulbCapabilities,
+            models::DiscoveredLight,
+            models::WizProtocol,
             models::PowerMode,
             models::SceneMode,
+            models::RandomMode,
             models::Brightness,
             models::Color,
+            models::PartialColor,
             models::Kelvin,
             models::White,
             models::Speed,
+            models::FanSpeed,
+            models::Ratio,
             models::LastSet,
+            models::Operation,
+            models::LightOutcome,
+            models::OperationStatus,
+            models::PruneAction,
+            maintenance::PruneReport,
+            models::SceneName,
+            models::SceneCategory,
+            AuditEntry,
+            EffectiveConfig,
+            WriteHealth,
+            PresenceSchedule,
+            ActiveHours,
         ))
     )]
     struct ApiDoc;
 
-    let openapi = ApiDoc::openapi();
+    let openapi = Data::new(ApiDoc::openapi());
 
     let storage = Data::new(Mutex::new(Storage::new()));
     let worker = Data::new(Mutex::new(Worker::new(Data::clone(&storage))));
+    let scene_names = Data::new(models::SceneNames::from_env());
+
+    warm_status(&storage, &worker);
+    push_status(&storage, &worker);
+    retry_writes(&storage);
+
+    let presence = Data::new(Mutex::new(Presence::new()));
+    start_presence(
+        Data::clone(&presence),
+        Data::clone(&storage),
+        Data::clone(&worker),
+    );
 
     let port = get_port();
     info!("Listening on port: {port}");
 
+    let max_body_bytes = get_max_body_bytes();
+    let request_timeout = get_request_timeout();
+
+    let swagger = swagger_path();
+    match &swagger {
+        Some(path) => info!("Serving swagger UI at: {path}/"),
+        None => info!("Swagger UI disabled"),
+    }
+
+    let bind_address = Ipv4Addr::UNSPECIFIED;
+    let defaults = Light::new(Ipv4Addr::UNSPECIFIED, None);
+
+    let effective = Data::new(EffectiveConfig {
+        storage_path: lock(&storage).file_path().to_string(),
+        port,
+        bind_address,
+        cors_origin: cors_origin.clone(),
+        worker_threads: lock(&worker).threads(),
+        max_body_bytes,
+        request_timeout_secs: request_timeout.as_secs(),
+        swagger_path: swagger.clone(),
+        warm_status: warm_status_enabled(),
+        bulb_timeout_ms: defaults.timeout().as_millis() as u64,
+        bulb_retries: defaults.retries(),
+        status_concurrency: Room::status_concurrency(),
+        allow_public_ips: lock(&storage).allow_public_ips(),
+    });
+
     HttpServer::new(move || {
-        let origin = match env::var("RIZ_CORS_ORIGIN") {
-            Ok(val) => val,
-            Err(_) => String::from("http://localhost:8000"),
+        let cors = if cors_origin == "*" {
+            Cors::default().allow_any_origin()
+        } else {
+            Cors::default().allowed_origin(&cors_origin)
         };
-        let origin = origin.as_str();
-
-        let cors = Cors::default()
-            .allowed_origin(origin)
+        let cors = cors
             .allow_any_method()
             .allowed_header(header::CONTENT_TYPE)
+            .allowed_header(REQUEST_ID_HEADER)
+            .expose_headers([REQUEST_ID_HEADER])
             .max_age(600);
 
         App::new()
             .wrap(cors)
             .app_data(Data::clone(&storage))
             .app_data(Data::clone(&worker))
-            .wrap(Logger::default())
+            .app_data(Data::clone(&scene_names))
+            .app_data(Data::clone(&effective))
+            .app_data(Data::clone(&presence))
+            .app_data(JsonConfig::default().limit(max_body_bytes))
+            .wrap_fn(|req, srv| {
+                let id = RequestId::assign(&req);
+                let res = srv.call(req);
+                async move {
+                    res.await.map(|mut res| {
+                        if let Ok(value) = HeaderValue::from_str(id.as_str()) {
+                            let name = HeaderName::from_static(REQUEST_ID_HEADER);
+                            res.headers_mut().insert(name, value);
+                        }
+                        res
+                    })
+                }
+            })
+            .wrap(Logger::new(LOG_FORMAT))
             .service(rooms::create)
             .service(rooms::list)
             .service(rooms::read)
+            .service(rooms::read_by_name)
             .service(rooms::update)
             .service(rooms::destroy)
             .service(rooms::status)
+            .service(rooms::batch_status)
+            .service(rooms::reachability)
+            .service(rooms::reboot)
+            .service(rooms::reset)
+            .service(rooms::export_preset)
+            .service(rooms::import_preset)
             .service(lights::create)
             .service(lights::update)
             .service(lights::update_room)
+            .service(lights::apply)
             .service(lights::update_light)
             .service(lights::destroy)
+            .service(lights::destroy_by_ip)
             .service(lights::status)
+            .service(lights::pilot)
+            .service(lights::random)
+            .service(lights::nudge_brightness)
+            .service(lights::flash)
+            .service(lights::sync)
+            .service(lights::debug)
+            .service(lights::ips)
+            .service(discover::discover)
+            .service(maintenance::prune)
+            .service(operations::read)
+            .service(presence::read)
+            .service(presence::update)
+            .service(presence::enable)
+            .service(presence::disable)
+            .service(scenes::list)
+            .service(scenes::favorites)
+            .service(audit::list)
+            .service(config::read)
             .service(health::ping)
-            .service(
-                SwaggerUi::new("/v1/swagger-ui/{_:.*}")
-                    .url("/v1/api-docs/openapi.json", openapi.clone()),
-            )
+            .service(health::ready)
+            .configure(|cfg| api_docs(cfg, swagger.as_deref(), &openapi))
     })
-    .bind((Ipv4Addr::UNSPECIFIED, port))?
+    .client_request_timeout(request_timeout)
+    .bind((bind_address, port))?
     .run()
     .await
 }
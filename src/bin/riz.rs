@@ -1,54 +1,128 @@
-use std::{net::Ipv4Addr, str::FromStr};
+use std::{
+    env, fmt, fs,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
 
-use clap::Parser;
-use convert_case::{Case, Casing};
+use clap::{Parser, Subcommand};
 use riz::{
     models::{
-        Brightness, Color, Kelvin, Light, LightingResponse, Payload, PowerMode, SceneMode, Speed,
+        Brightness, Color, DynamicScene, Kelvin, Light, LightRequest, LightStatus,
+        LightingResponse, Payload, PowerMode, RandomMode, SceneMode, SceneName, SceneNames, Speed,
         White,
     },
-    Result,
+    Error, Result, Storage,
 };
-use strum::IntoEnumIterator;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+const DEFAULT_ROOM_ENV_KEY: &str = "RIZ_DEFAULT_ROOM";
+
+/// Disables colored output when set to any non-empty value, see https://no-color.org
+const NO_COLOR_ENV_KEY: &str = "NO_COLOR";
 
 #[derive(Debug, Parser)]
-#[command(author, version, about = "Riz light control CLI", long_about = None)]
+#[command(
+    author,
+    version,
+    about = "Riz light control CLI",
+    long_about = None,
+    args_conflicts_with_subcommands = true
+)]
 struct Args {
-    /// Bulb IPv4 address(es)
-    ip: Option<Vec<Ipv4Addr>>,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    #[arg(short, long)]
-    /// Set the bulb brightness (10-100)
+    /// Bulb IPv4 address(es) or nickname(s)
+    ip: Option<Vec<String>>,
+
+    #[arg(short = 'R', long, conflicts_with = "ip")]
+    /// Target all bulbs in the room (name or ID) from rooms.json
+    room: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        help = format!("Set the bulb brightness ({}-{})", Brightness::MIN, Brightness::MAX)
+    )]
     brightness: Option<u8>,
 
-    #[arg(short, long)]
-    /// Set the bulb color as r,g,b (0-255)
+    #[arg(short, long, conflicts_with_all = ["cool", "warm"])]
+    /// Set the bulb color as r,g,b (0-255) or #rrggbb
     color: Option<String>,
 
-    #[arg(short = 'C', long)]
-    /// Set the cool white value (1-100)
+    #[arg(
+        short = 'C',
+        long,
+        help = format!("Set the cool white value ({}-{})", White::MIN, White::MAX)
+    )]
     cool: Option<u8>,
 
-    #[arg(short = 'W', long)]
-    /// Set the warm white value (1-100)
+    #[arg(
+        short = 'W',
+        long,
+        help = format!("Set the warm white value ({}-{})", White::MIN, White::MAX)
+    )]
     warm: Option<u8>,
 
-    #[arg(short = 'p', long)]
-    /// Set the bulb speed (20-200)
+    #[arg(
+        short = 'p',
+        long,
+        help = format!("Set the bulb speed ({}-{})", Speed::MIN, Speed::MAX)
+    )]
     speed: Option<u8>,
 
-    #[arg(short, long)]
-    /// Set the bulb temperature in Kelvin (1000-8000)
+    #[arg(
+        short,
+        long,
+        help = format!("Set the bulb temperature in Kelvin ({}-{})", Kelvin::MIN, Kelvin::MAX)
+    )]
     temp: Option<u16>,
 
+    #[arg(
+        long,
+        value_name = "BRIGHTNESS",
+        conflicts_with_all = ["brightness", "color", "cool", "warm", "speed", "temp", "scene", "random", "reboot"],
+        help = format!(
+            "Dim to the brightness, warming as it dims ({}-{})",
+            Brightness::MIN,
+            Brightness::MAX
+        )
+    )]
+    warm_dim: Option<u8>,
+
     #[arg(short, long)]
     /// List the available scene IDs
     list: bool,
 
+    #[arg(short, long, requires = "list")]
+    /// Group the listed scenes by category
+    group: bool,
+
+    #[arg(short = 'F', long, requires = "list")]
+    /// Only list the favorite scenes (set with RIZ_FAVORITE_SCENES)
+    favorites: bool,
+
     #[arg(short, long)]
     /// Set the scene by ID
     scene: Option<u8>,
 
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "any",
+        conflicts_with_all = ["brightness", "color", "cool", "warm", "speed", "temp", "scene", "reboot"]
+    )]
+    /// Set a random color or scene (color, scene or any)
+    random: Option<RandomMode>,
+
     #[arg(short, long)]
     /// Turn the bulb on
     on: bool,
@@ -57,23 +131,234 @@ struct Args {
     /// Turn the bulb off
     off: bool,
 
-    #[arg(short, long)]
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["brightness", "color", "cool", "warm", "speed", "temp", "scene"]
+    )]
     /// Reboot the bulb
     reboot: bool,
 
+    #[arg(short = 'S', long, conflicts_with_all = ["list", "status", "ping"])]
+    /// Wait between each bulb when targeting several (milliseconds)
+    stagger: Option<u64>,
+
     #[arg(short = 'i', long)]
     /// Get the current bulb status
     status: bool,
+
+    #[arg(short, long, requires = "status")]
+    /// Print each bulb status as a line of JSON
+    json: bool,
+
+    #[arg(short = 'T', long, requires = "status", conflicts_with = "json")]
+    /// Print a readable one-line summary of each bulb status
+    text: bool,
+
+    #[arg(long)]
+    /// Don't use colors in the output (also set by NO_COLOR)
+    no_color: bool,
+
+    #[arg(short = 'P', long)]
+    /// Probe the bulb model before setting cool/warm white
+    probe: bool,
+
+    #[arg(
+        short = 'n',
+        long,
+        conflicts_with_all = ["status", "on", "off", "reboot"]
+    )]
+    /// Check the bulb is reachable and print the round trip time
+    ping: bool,
+
+    #[arg(long)]
+    /// Send the valid settings even if some are invalid
+    force: bool,
 }
 
-fn print_scenes() {
-    for scene in SceneMode::iter() {
-        let s = format!("{:?}", scene);
-        println!(
-            "{:>6} => {}",
-            scene as u8,
-            s.from_case(Case::Pascal).to_case(Case::Title)
-        );
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run a JSON-lines file of commands, in order
+    Run {
+        /// File of {"ip": .., "request": .., "delay_ms": ..} objects, one per line
+        file: PathBuf,
+
+        #[arg(long)]
+        /// Stop at the first malformed or failed line
+        strict: bool,
+    },
+
+    /// Send the options to all bulbs in the room (name or ID), see --room
+    Room {
+        /// Room name or ID from rooms.json
+        room: String,
+
+        /// Any options, as for a single bulb
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        options: Vec<String>,
+    },
+}
+
+/// A single line of a command file for `riz run`
+#[derive(Debug, Deserialize)]
+struct RunLine {
+    /// Bulb IPv4 address
+    ip: Ipv4Addr,
+
+    /// Lighting settings and/or power change to send
+    request: LightRequest,
+
+    /// Time to wait after sending, before the next line
+    #[serde(default)]
+    delay_ms: u64,
+}
+
+/// Run each line of the command file in order
+///
+/// Blank lines are skipped. Malformed or failed lines are reported and
+/// skipped, unless `strict`.
+///
+/// # Returns
+///   [bool] of if the file was run to the end
+///
+fn run_file(path: &Path, strict: bool) -> bool {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            return false;
+        }
+    };
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let res = serde_json::from_str::<RunLine>(line)
+            .map_err(Error::JsonLoad)
+            .and_then(|cmd| run_line(&cmd).map(|_| cmd.delay_ms));
+
+        match res {
+            Ok(delay_ms) => thread::sleep(Duration::from_millis(delay_ms)),
+            Err(e) if strict => {
+                eprintln!("Line {}: {}", i + 1, e);
+                return false;
+            }
+            Err(e) => eprintln!("Skipping line {}: {}", i + 1, e),
+        }
+    }
+    true
+}
+
+/// Send a command file line's request to its bulb
+fn run_line(cmd: &RunLine) -> Result<()> {
+    cmd.request.validate()?;
+
+    let light = Light::new(cmd.ip, None);
+    let payload = Payload::from(&cmd.request);
+    if payload.is_valid() {
+        light.set(&payload)?;
+    }
+
+    if let Some(power) = cmd.request.power() {
+        if payload.is_valid() {
+            light.settle();
+        }
+        light.set_power(power)?;
+    }
+    Ok(())
+}
+
+fn print_scenes(group: bool, favorites: bool) {
+    let names = SceneNames::from_env();
+    let scenes = if favorites {
+        names.list_favorites()
+    } else {
+        names.list()
+    };
+    if !group {
+        for scene in scenes {
+            println!("{:>6} => {}", scene.id(), scene.name());
+        }
+        return;
+    }
+
+    for (category, scenes) in SceneName::group(scenes) {
+        println!("{:?}:", category);
+        for scene in scenes {
+            println!("{:>6} => {}", scene.id(), scene.name());
+        }
+    }
+}
+
+/// Find the IPs of all lights in the room, by name or ID
+///
+/// Reads `rooms.json` from `RIZ_STORAGE_PATH`, which is never written
+/// to by the CLI.
+///
+fn room_ips(room: &str) -> std::result::Result<Vec<Ipv4Addr>, String> {
+    let storage = Storage::new();
+    let found = match Uuid::from_str(room) {
+        Ok(id) => storage
+            .read(&id)
+            .ok_or_else(|| format!("No such room: {}", id)),
+        Err(_) => storage
+            .read_by_name(room)
+            .map(|(_, found)| found)
+            .map_err(|e| e.to_string()),
+    }?;
+
+    let ips: Vec<_> = found
+        .enabled_lights()
+        .map(|(_, light)| light.ip())
+        .collect();
+    if ips.is_empty() {
+        return Err(format!("No enabled lights in room: {}", room));
+    }
+    Ok(ips)
+}
+
+/// Resolve each bulb argument to an IP, either directly or by looking
+/// up the light's nickname in `rooms.json`
+fn alias_ips(args: &[String]) -> std::result::Result<Vec<Ipv4Addr>, String> {
+    let mut storage = None;
+    let mut ips = Vec::with_capacity(args.len());
+
+    for arg in args {
+        if let Ok(ip) = Ipv4Addr::from_str(arg) {
+            ips.push(ip);
+            continue;
+        }
+
+        let storage = storage.get_or_insert_with(Storage::new);
+        match storage
+            .iter_lights()
+            .find(|(_, _, light)| light.nickname() == Some(arg.as_str()))
+        {
+            Some((_, _, light)) => ips.push(light.ip()),
+            None => return Err(format!("No such IP or light nickname: {}", arg)),
+        }
+    }
+
+    Ok(ips)
+}
+
+/// Resolve the bulb IPs to target, in order of precedence:
+/// explicit IPs (or nicknames), `--room`, then `RIZ_DEFAULT_ROOM` (env var)
+fn target_ips(args: &Args) -> std::result::Result<Vec<Ipv4Addr>, String> {
+    if let Some(ips) = &args.ip {
+        return alias_ips(ips);
+    }
+
+    if let Some(room) = &args.room {
+        return room_ips(room);
+    }
+
+    match env::var(DEFAULT_ROOM_ENV_KEY) {
+        Ok(room) => room_ips(&room).map_err(|e| format!("{} ({})", e, DEFAULT_ROOM_ENV_KEY)),
+        Err(_) => Err("IP address or room is required!".to_string()),
     }
 }
 
@@ -83,106 +368,397 @@ fn print_response(res: Result<LightingResponse>) {
     }
 }
 
-fn modify_light(args: &Args, light: Light) {
-    if args.status {
-        match light.get_status() {
-            Ok(status) => println!("{}", serde_json::to_string_pretty(&status).unwrap()),
-            Err(e) => eprintln!("Failed to get bulb status: {:?}", e),
+fn probe_white(light: &Light) {
+    match light.get_system_config() {
+        Ok(config) => {
+            if !config.tunable_white() {
+                eprintln!(
+                    "Warning: {} ({}) may not support cool/warm white",
+                    light.ip(),
+                    config.module_name().unwrap_or("unknown model")
+                );
+            }
         }
-        return;
+        Err(e) => eprintln!("Failed to probe bulb: {:?}", e),
+    }
+}
+
+/// Send a `getPilot` to the bulb and report the round trip time
+///
+/// # Returns
+///   [bool] of if the bulb replied
+///
+fn ping_light(light: &Light) -> bool {
+    match light.ping() {
+        Ok(elapsed) => {
+            let elapsed = elapsed.as_millis();
+            println!("{:<15}  ok  ({}ms)", light.ip().to_string(), elapsed);
+            true
+        }
+        Err(e) if e.is_timeout() => {
+            println!("{:<15}  timeout", light.ip().to_string());
+            false
+        }
+        Err(e) => {
+            println!("{:<15}  error  ({})", light.ip().to_string(), e);
+            false
+        }
+    }
+}
+
+/// Check if the output should use colors, from `--no-color` or `NO_COLOR`
+fn use_color(args: &Args) -> bool {
+    !args.no_color && env::var(NO_COLOR_ENV_KEY).map_or(true, |v| v.is_empty())
+}
+
+/// Print a one-line summary of the bulb's status
+///
+/// The color is always written as text (RGB & hex), with a swatch in
+/// front when colors are enabled. Scenes are shown by display name.
+///
+fn print_status(ip: Ipv4Addr, status: &LightStatus, color: bool) {
+    let swatch = match status.color() {
+        Some(c) if color => format!(
+            "\x1b[38;2;{};{};{}m\u{2588}\u{2588}\x1b[0m ",
+            c.red(),
+            c.green(),
+            c.blue()
+        ),
+        _ => String::new(),
+    };
+
+    let mut line = format!("{:<15}  {}{}", ip.to_string(), swatch, status);
+    if let Some(c) = status.color() {
+        line.push_str(&format!(" · hex={}", c.hex()));
+    }
+    if let Some(scene) = status.scene() {
+        line.push_str(&format!(" · {}", SceneNames::from_env().name(scene)));
+    }
+    println!("{}", line);
+}
+
+/// Fetch the status of all bulbs at once, printing each as it arrives
+///
+/// Each status is printed as pretty JSON of the bulb's `ip` and `status`,
+/// or with `--json` each line is a JSON object of the `ip` and either its
+/// `status` or an `error`. With `--text`, lines are a readable summary
+/// prefixed with the bulb IP.
+///
+fn print_statuses(args: &Args, ips: Vec<Ipv4Addr>) {
+    let (tx, rx) = mpsc::channel();
+    for ip in ips {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let _ = tx.send((ip, Light::new(ip, None).get_status()));
+        });
+    }
+    drop(tx);
+
+    let color = use_color(args);
+    for (ip, res) in rx {
+        match res {
+            Ok(status) if args.json => println!("{}", json!({"ip": ip, "status": status})),
+            Ok(status) if args.text => print_status(ip, &status, color),
+            Ok(status) => println!("{:#}", json!({"ip": ip, "status": status})),
+            Err(e) if args.json => println!("{}", json!({"ip": ip, "error": e.to_string()})),
+            Err(e) => eprintln!("{:<15}  Failed to get bulb status: {}", ip.to_string(), e),
+        }
+    }
+}
+
+/// Check an optional argument's value, recording it if it's invalid
+fn checked<A: fmt::Display, T>(
+    arg: Option<A>,
+    parse: impl FnOnce(&A) -> Option<T>,
+    what: &str,
+    errors: &mut Vec<String>,
+) -> Option<T> {
+    let arg = arg?;
+    let valid = parse(&arg);
+    if valid.is_none() {
+        errors.push(format!("Invalid {}: {}", what, arg));
+    }
+    valid
+}
+
+/// Build the lighting payload of only the valid settings from the arguments
+///
+/// All settings are combined into one remote command, how much sense
+/// that makes is context dependant... A scene carries its speed with
+/// it, otherwise the speed is sent alone.
+///
+/// # Returns
+///   [Payload] of the valid settings, and a description of each invalid value
+///
+fn valid_settings(args: &Args) -> (Payload, Vec<String>) {
+    let mut errors = Vec::new();
+    checked(
+        args.warm_dim,
+        |v| Brightness::create(*v),
+        "warm dim brightness",
+        &mut errors,
+    );
+    let speed = checked(
+        args.speed,
+        |v| Speed::create(*v),
+        "speed value",
+        &mut errors,
+    );
+    let scene = checked(
+        args.scene,
+        |v| SceneMode::create(*v),
+        "scene ID",
+        &mut errors,
+    );
+    let brightness = checked(
+        args.brightness,
+        |v| Brightness::create(*v),
+        "brightness value",
+        &mut errors,
+    );
+    let color = checked(
+        args.color.as_deref(),
+        |v| Color::from_str(v).ok(),
+        "color",
+        &mut errors,
+    );
+    let temp = checked(args.temp, |v| Kelvin::create(*v), "temp value", &mut errors);
+    let cool = checked(
+        args.cool,
+        |v| White::create(*v),
+        "cool white value",
+        &mut errors,
+    );
+    let warm = checked(
+        args.warm,
+        |v| White::create(*v),
+        "warm white value",
+        &mut errors,
+    );
+
+    let mut payload = match scene {
+        Some(scene) => Payload::from(&DynamicScene::new(scene, speed)),
+        None => speed.as_ref().map(Payload::from).unwrap_or_default(),
+    };
+    if let Some(brightness) = &brightness {
+        payload.brightness(brightness);
+    }
+    if let Some(color) = &color {
+        payload.color(color);
+    }
+    if let Some(temp) = &temp {
+        payload.temp(temp);
+    }
+    if let Some(cool) = &cool {
+        payload.cool(cool);
+    }
+    if let Some(warm) = &warm {
+        payload.warm(warm);
     }
 
+    (payload, errors)
+}
+
+/// Build the lighting payload from the arguments, checking every value
+///
+/// # Returns
+///   [Payload] of all settings, or a description of every invalid value
+///
+fn payload_from_args(args: &Args) -> std::result::Result<Payload, Vec<String>> {
+    match valid_settings(args) {
+        (payload, errors) if errors.is_empty() => Ok(payload),
+        (_, errors) => Err(errors),
+    }
+}
+
+/// Decide what to send for the arguments, see [payload_from_args]
+///
+/// # Returns
+///   [Payload] to send, or [None] to abort when any value is invalid,
+///   unless `--force` sends only the valid settings; and a description
+///   of each invalid value
+///
+fn payload_to_send(args: &Args) -> (Option<Payload>, Vec<String>) {
+    match payload_from_args(args) {
+        Ok(payload) => (Some(payload), Vec::new()),
+        Err(errors) if args.force => (Some(valid_settings(args).0), errors),
+        Err(errors) => (None, errors),
+    }
+}
+
+fn modify_light(args: &Args, light: Light, payload: &Payload) {
     // only make at most one power action...
+    let mut powered = true;
     if args.on {
         print_response(light.set_power(&PowerMode::On));
     } else if args.off {
         print_response(light.set_power(&PowerMode::Off));
     } else if args.reboot {
         print_response(light.set_power(&PowerMode::Reboot));
+    } else {
+        powered = false;
     }
 
-    // we can combine all other actions into one remote command
-    // how much sense that makes is context dependant...
-    let mut payload = Payload::new();
-
-    if let Some(scene) = args.scene {
-        if let Some(scene) = SceneMode::create(scene) {
-            payload.scene(&scene);
-        } else {
-            eprintln!("Invalid scene ID: {}", scene);
+    if let Some(mode) = args.random {
+        if powered {
+            light.settle();
         }
+        print_response(light.random(mode));
+        return;
     }
 
-    if let Some(brightness) = args.brightness {
-        if let Some(brightness) = Brightness::create(brightness) {
-            payload.brightness(&brightness);
-        } else {
-            eprintln!("Invalid brightness value: {}", brightness);
+    if let Some(brightness) = args.warm_dim.and_then(Brightness::create) {
+        if powered {
+            light.settle();
         }
+        print_response(light.warm_dim(&brightness));
+        return;
     }
 
-    if let Some(color) = &args.color {
-        if let Ok(color) = Color::from_str(color) {
-            payload.color(&color);
-        } else {
-            eprintln!("Invalid color: {}", color);
-        }
+    if args.probe && (args.cool.is_some() || args.warm.is_some()) {
+        probe_white(&light);
     }
 
-    if let Some(speed) = args.speed {
-        if let Some(speed) = Speed::create(speed) {
-            payload.speed(&speed);
-        } else {
-            eprintln!("Invalid speed value: {}", speed);
+    if payload.is_valid() {
+        if powered {
+            light.settle();
         }
+        print_response(light.set(payload));
     }
+}
+
+/// Parse `riz room <ROOM> [OPTIONS]` as `riz --room <ROOM> [OPTIONS]`
+fn room_args(room: &str, options: &[String]) -> Args {
+    let mut argv = vec![
+        env!("CARGO_BIN_NAME").to_string(),
+        "--room".to_string(),
+        room.to_string(),
+    ];
+    argv.extend(options.iter().cloned());
+    Args::parse_from(argv)
+}
 
-    if let Some(temp) = args.temp {
-        if let Some(temp) = Kelvin::create(temp) {
-            payload.temp(&temp);
-        } else {
-            eprintln!("Invalid temp value: {}", temp);
+fn main() {
+    let args = match Args::parse() {
+        Args {
+            command: Some(Command::Room { room, options }),
+            ..
+        } => room_args(&room, &options),
+        args => args,
+    };
+
+    if let Some(Command::Run { file, strict }) = &args.command {
+        if !run_file(file, *strict) {
+            process::exit(1);
         }
+        return;
     }
 
-    if let Some(cool) = args.cool {
-        if let Some(cool) = White::create(cool) {
-            payload.cool(&cool);
-        } else {
-            eprintln!("Invalid cool white value: {}", cool);
+    if args.list {
+        print_scenes(args.group, args.favorites);
+        return;
+    }
+
+    let ips = match target_ips(&args) {
+        Ok(ips) => ips,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
         }
+    };
+
+    if args.status {
+        print_statuses(&args, ips);
+        return;
     }
 
-    if let Some(warm) = args.warm {
-        if let Some(warm) = White::create(warm) {
-            payload.warm(&warm);
-        } else {
-            eprintln!("Invalid warm white value: {}", warm);
+    if args.ping {
+        let mut failed = false;
+        for ip in &ips {
+            failed |= !ping_light(&Light::new(*ip, None));
         }
+        if failed {
+            process::exit(1);
+        }
+        return;
     }
 
-    if payload.is_valid() {
-        print_response(light.set(&payload));
+    let (payload, errors) = payload_to_send(&args);
+    for e in &errors {
+        eprintln!("{}", e);
+    }
+    let Some(payload) = payload else {
+        eprintln!("Nothing was sent, use --force to send the valid settings");
+        process::exit(1);
+    };
+
+    for (i, ip) in ips.into_iter().enumerate() {
+        if let Some(stagger) = args.stagger.filter(|_| i > 0) {
+            thread::sleep(Duration::from_millis(stagger));
+        }
+        modify_light(&args, Light::new(ip, None), &payload);
     }
 }
 
-fn main() {
-    let args = Args::parse();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if args.list {
-        print_scenes();
-        return;
+    fn args(argv: &[&str]) -> Args {
+        Args::parse_from([&["riz", "10.1.2.3"], argv].concat())
     }
 
-    let ips = match &args.ip {
-        Some(ips) => ips,
-        None => {
-            eprintln!("IP address is required!");
-            return;
-        }
-    };
+    #[test]
+    fn valid_args_payload() {
+        let payload = payload_from_args(&args(&["-b", "50", "-c", "255,0,0"])).unwrap();
+        let value = serde_json::to_value(payload).unwrap();
+        assert_eq!(value["dimming"], 50);
+        assert_eq!(value["r"], 255);
+    }
 
-    for ip in ips {
-        modify_light(&args, Light::new(*ip, None));
+    #[test]
+    fn invalid_args_all_collected() {
+        let errors = payload_from_args(&args(&[
+            "-b", "5", "-p", "0", "-t", "9000", "-s", "250", "-c", "red",
+        ]))
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "Invalid speed value: 0",
+                "Invalid scene ID: 250",
+                "Invalid brightness value: 5",
+                "Invalid color: red",
+                "Invalid temp value: 9000",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_white_values_collected() {
+        let errors = payload_from_args(&args(&["-C", "101", "-W", "0"])).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "Invalid cool white value: 101",
+                "Invalid warm white value: 0"
+            ]
+        );
+
+        let errors = payload_from_args(&args(&["--warm-dim", "101"])).unwrap_err();
+        assert_eq!(errors, vec!["Invalid warm dim brightness: 101"]);
+    }
+
+    #[test]
+    fn invalid_args_abort_unless_forced() {
+        let (payload, errors) = payload_to_send(&args(&["-b", "5", "-c", "255,0,0"]));
+        assert!(payload.is_none());
+        assert_eq!(errors, vec!["Invalid brightness value: 5"]);
+
+        let (payload, errors) = payload_to_send(&args(&["-b", "5", "-c", "255,0,0", "--force"]));
+        assert_eq!(errors, vec!["Invalid brightness value: 5"]);
+        let value = serde_json::to_value(payload.unwrap()).unwrap();
+        assert_eq!(value["r"], 255);
+        assert!(value.get("dimming").is_none());
     }
 }
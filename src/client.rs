@@ -0,0 +1,603 @@
+//! Blocking HTTP client for another Riz API instance
+//!
+//! Only available with the `client` feature enabled
+
+use std::{collections::HashMap, net::Ipv4Addr, time::Duration};
+
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    config::EffectiveConfig,
+    maintenance::PruneReport,
+    models::{
+        Color, DiscoveredLight, Light, LightFilter, LightRef, LightRequest, LightStatus, Operation,
+        PruneAction, RandomMode, Room, RoomPreset, SceneName, UdpExchange,
+    },
+    rooms::{CreatedRoom, LightReachability, RoomStatus},
+    AuditEntry, Error, PresenceSchedule, Result, WriteHealth,
+};
+
+/// Timeout for each request to the remote Riz API
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Typed wrapper around the Riz API routes of a remote instance
+///
+/// # Examples
+///
+/// ```no_run
+/// use riz::RizClient;
+///
+/// let client = RizClient::new("http://10.1.2.3:8080");
+/// for id in client.list_rooms().unwrap() {
+///     let room = client.read_room(&id).unwrap();
+///     println!("{}: {}", id, room.name());
+/// }
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct RizClient {
+    base_url: String,
+    agent: ureq::Agent,
+}
+
+impl RizClient {
+    /// Create a new client for the Riz API at the base URL
+    pub fn new(base_url: &str) -> Self {
+        RizClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new().timeout(CLIENT_TIMEOUT).build(),
+        }
+    }
+
+    /// Check the remote API is up, `GET /v1/ping`
+    pub fn ping(&self) -> Result<()> {
+        self.call("ping", self.agent.get(&self.url("/v1/ping")))
+            .map(|_| ())
+    }
+
+    /// Read the remote API's storage health, `GET /v1/health/ready`
+    ///
+    /// An unhealthy API replies with a `503`, which is returned as an error
+    ///
+    pub fn ready(&self) -> Result<WriteHealth> {
+        self.json("ready", self.agent.get(&self.url("/v1/health/ready")))
+    }
+
+    /// List all room IDs, `GET /v1/rooms`
+    pub fn list_rooms(&self) -> Result<Vec<Uuid>> {
+        self.json("list_rooms", self.agent.get(&self.url("/v1/rooms")))
+    }
+
+    /// List all room IDs ordered by room name, `GET /v1/rooms?sort=name`
+    pub fn list_rooms_sorted(&self) -> Result<Vec<Uuid>> {
+        let req = self.agent.get(&self.url("/v1/rooms")).query("sort", "name");
+        self.json("list_rooms_sorted", req)
+    }
+
+    /// Create a room, with any initial lights, `POST /v1/rooms`
+    ///
+    /// # Returns
+    ///   the created [Room], linked to its new ID, with newly assigned light IDs
+    ///
+    pub fn create_room(&self, room: &Room) -> Result<Room> {
+        let req = self.agent.post(&self.url("/v1/rooms"));
+        let created: CreatedRoom =
+            Self::decode("create_room", self.send_json("create_room", req, room)?)?;
+        Ok(Self::linked(created))
+    }
+
+    /// Read room details, `GET /v1/room/{id}`
+    pub fn read_room(&self, id: &Uuid) -> Result<Room> {
+        let url = self.url(&format!("/v1/room/{}", id));
+        self.json("read_room", self.agent.get(&url))
+    }
+
+    /// Read room details by name, `GET /v1/rooms/by-name/{name}`
+    pub fn read_room_by_name(&self, name: &str) -> Result<Room> {
+        let url = self.url(&format!("/v1/rooms/by-name/{}", Self::encode_segment(name)));
+        self.json("read_room_by_name", self.agent.get(&url))
+    }
+
+    /// Update room details, `PATCH /v1/room/{id}`
+    pub fn update_room(&self, id: &Uuid, room: &Room) -> Result<()> {
+        let url = self.url(&format!("/v1/room/{}", id));
+        self.send_json("update_room", self.agent.patch(&url), room)
+            .map(|_| ())
+    }
+
+    /// Remove a room, `DELETE /v1/room/{id}`
+    pub fn delete_room(&self, id: &Uuid) -> Result<()> {
+        let url = self.url(&format!("/v1/room/{}", id));
+        self.call("delete_room", self.agent.delete(&url))
+            .map(|_| ())
+    }
+
+    /// Refresh the status of all lights in a room, `GET /v1/room/{id}/status`
+    pub fn room_status(&self, id: &Uuid) -> Result<Room> {
+        let url = self.url(&format!("/v1/room/{}/status", id));
+        self.json("room_status", self.agent.get(&url))
+    }
+
+    /// Refresh the status of all lights in many rooms, `POST /v1/rooms/status`
+    ///
+    /// # Returns
+    ///   [HashMap] of each room ID to its [RoomStatus]
+    ///
+    pub fn rooms_status(&self, ids: &[Uuid]) -> Result<HashMap<Uuid, RoomStatus>> {
+        let body = serde_json::json!({ "ids": ids });
+        let url = self.url("/v1/rooms/status");
+        Self::decode(
+            "rooms_status",
+            self.send_json("rooms_status", self.agent.post(&url), &body)?,
+        )
+    }
+
+    /// Check which lights in a room answer, `GET /v1/room/{id}/reachability`
+    pub fn room_reachability(&self, id: &Uuid) -> Result<Vec<LightReachability>> {
+        let url = self.url(&format!("/v1/room/{}/reachability", id));
+        self.json("room_reachability", self.agent.get(&url))
+    }
+
+    /// Reboot all lights in a room one at a time, `POST /v1/room/{id}/reboot`
+    ///
+    /// # Returns
+    ///   the [Operation] of the lights commanded, with all pending
+    ///
+    pub fn reboot_room(&self, id: &Uuid, delay_ms: Option<u64>) -> Result<Operation> {
+        let url = self.url(&format!("/v1/room/{}/reboot", id));
+        let mut req = self.agent.post(&url);
+        if let Some(delay_ms) = delay_ms {
+            req = req.query("delay_ms", &delay_ms.to_string());
+        }
+        self.json("reboot_room", req)
+    }
+
+    /// Reset all lights in a room to a safe default, or the given request,
+    /// `POST /v1/room/{id}/reset`
+    ///
+    /// # Returns
+    ///   the [Uuid] of the operation
+    ///
+    pub fn reset_room(&self, id: &Uuid, req: Option<&LightRequest>) -> Result<Uuid> {
+        let url = self.url(&format!("/v1/room/{}/reset", id));
+        match req {
+            Some(body) => Self::decode(
+                "reset_room",
+                self.send_json("reset_room", self.agent.post(&url), body)?,
+            ),
+            None => self.json("reset_room", self.agent.post(&url)),
+        }
+    }
+
+    /// Export a room's lighting, `GET /v1/room/{id}/export-preset`
+    pub fn export_preset(&self, id: &Uuid) -> Result<RoomPreset> {
+        let url = self.url(&format!("/v1/room/{}/export-preset", id));
+        self.json("export_preset", self.agent.get(&url))
+    }
+
+    /// Apply a preset's lighting to a room's lights by position,
+    /// `POST /v1/room/{id}/import-preset`
+    ///
+    /// # Returns
+    ///   [Vec] of [LightRef] of the updated lights
+    ///
+    pub fn import_preset(&self, id: &Uuid, preset: &RoomPreset) -> Result<Vec<LightRef>> {
+        let url = self.url(&format!("/v1/room/{}/import-preset", id));
+        Self::decode(
+            "import_preset",
+            self.send_json("import_preset", self.agent.post(&url), preset)?,
+        )
+    }
+
+    /// Create a light in a room, `POST /v1/room/{id}/lights`
+    ///
+    /// # Returns
+    ///   the new light's [Uuid]
+    ///
+    pub fn create_light(&self, room: &Uuid, light: &Light) -> Result<Uuid> {
+        let url = self.url(&format!("/v1/room/{}/lights", room));
+        Self::decode(
+            "create_light",
+            self.send_json("create_light", self.agent.post(&url), light)?,
+        )
+    }
+
+    /// Update lighting settings for all lights in a room,
+    /// `PUT /v1/room/{id}/lights`
+    ///
+    /// # Returns
+    ///   the [Uuid] of the operation, see [Self::read_operation]
+    ///
+    pub fn update_room_lights(
+        &self,
+        room: &Uuid,
+        req: &LightRequest,
+        sync_scene: bool,
+    ) -> Result<Uuid> {
+        let url = self.url(&format!("/v1/room/{}/lights", room));
+        let put = self
+            .agent
+            .put(&url)
+            .query("sync_scene", &sync_scene.to_string());
+        Self::decode(
+            "update_room_lights",
+            self.send_json("update_room_lights", put, req)?,
+        )
+    }
+
+    /// Update lighting settings for a single light,
+    /// `PUT /v1/room/{id}/light/{light_id}`
+    pub fn update_light_settings(
+        &self,
+        room: &Uuid,
+        light: &Uuid,
+        req: &LightRequest,
+    ) -> Result<()> {
+        let url = self.url(&format!("/v1/room/{}/light/{}", room, light));
+        self.send_json("update_light_settings", self.agent.put(&url), req)
+            .map(|_| ())
+    }
+
+    /// Update lighting settings for a single light, confirming the bulb
+    /// applied them, `PUT /v1/room/{id}/light/{light_id}?ensure=true`
+    ///
+    /// # Returns
+    ///   the light's confirmed [LightStatus]
+    ///
+    pub fn ensure_light_settings(
+        &self,
+        room: &Uuid,
+        light: &Uuid,
+        req: &LightRequest,
+    ) -> Result<LightStatus> {
+        let url = self.url(&format!("/v1/room/{}/light/{}", room, light));
+        let put = self.agent.put(&url).query("ensure", "true");
+        Self::decode(
+            "ensure_light_settings",
+            self.send_json("ensure_light_settings", put, req)?,
+        )
+    }
+
+    /// Update lighting settings for a single light, storing the state the
+    /// bulb actually applied, `PUT /v1/room/{id}/light/{light_id}?verify_state=true`
+    pub fn verify_light_settings(
+        &self,
+        room: &Uuid,
+        light: &Uuid,
+        req: &LightRequest,
+    ) -> Result<()> {
+        let url = self.url(&format!("/v1/room/{}/light/{}", room, light));
+        let put = self.agent.put(&url).query("verify_state", "true");
+        self.send_json("verify_light_settings", put, req)
+            .map(|_| ())
+    }
+
+    /// Update light details (name, ip, timeouts), `PATCH /v1/room/{id}/light/{light_id}`
+    pub fn update_light(&self, room: &Uuid, id: &Uuid, light: &Light) -> Result<()> {
+        let url = self.url(&format!("/v1/room/{}/light/{}", room, id));
+        self.send_json("update_light", self.agent.patch(&url), light)
+            .map(|_| ())
+    }
+
+    /// Remove a light, `DELETE /v1/room/{id}/light/{light_id}`
+    pub fn delete_light(&self, room: &Uuid, id: &Uuid) -> Result<()> {
+        let url = self.url(&format!("/v1/room/{}/light/{}", room, id));
+        self.call("delete_light", self.agent.delete(&url))
+            .map(|_| ())
+    }
+
+    /// Remove a light by its IP, `DELETE /v1/lights/by-ip/{ip}`
+    pub fn delete_light_by_ip(&self, ip: &Ipv4Addr) -> Result<()> {
+        let url = self.url(&format!("/v1/lights/by-ip/{}", ip));
+        self.call("delete_light_by_ip", self.agent.delete(&url))
+            .map(|_| ())
+    }
+
+    /// Refresh the status of a single light,
+    /// `GET /v1/room/{id}/light/{light_id}/status`
+    pub fn light_status(&self, room: &Uuid, id: &Uuid) -> Result<LightStatus> {
+        let url = self.url(&format!("/v1/room/{}/light/{}/status", room, id));
+        self.json("light_status", self.agent.get(&url))
+    }
+
+    /// Read a light's raw `getPilot` reply,
+    /// `GET /v1/room/{id}/light/{light_id}/pilot`
+    pub fn light_pilot(&self, room: &Uuid, id: &Uuid) -> Result<serde_json::Value> {
+        let url = self.url(&format!("/v1/room/{}/light/{}/pilot", room, id));
+        self.json("light_pilot", self.agent.get(&url))
+    }
+
+    /// Read the live status of a single light without storing it,
+    /// `GET /v1/room/{id}/light/{light_id}/status?persist=false`
+    pub fn peek_light_status(&self, room: &Uuid, id: &Uuid) -> Result<LightStatus> {
+        let url = self.url(&format!("/v1/room/{}/light/{}/status", room, id));
+        let req = self.agent.get(&url).query("persist", "false");
+        self.json("peek_light_status", req)
+    }
+
+    /// Set a random color or scene on a single light,
+    /// `PUT /v1/room/{id}/light/{light_id}/random`
+    ///
+    /// # Returns
+    ///   the [LightRequest] which was sent
+    ///
+    pub fn random_light(&self, room: &Uuid, id: &Uuid, mode: RandomMode) -> Result<LightRequest> {
+        let url = self.url(&format!("/v1/room/{}/light/{}/random", room, id));
+        let req = self.agent.put(&url).query("mode", &format!("{:?}", mode));
+        self.json("random_light", req)
+    }
+
+    /// Change a light's brightness by a signed percent,
+    /// `PUT /v1/room/{id}/light/{light_id}/brightness/nudge`
+    pub fn nudge_brightness(&self, room: &Uuid, id: &Uuid, delta: i8) -> Result<()> {
+        let url = self.url(&format!("/v1/room/{}/light/{}/brightness/nudge", room, id));
+        let req = self.agent.put(&url).query("delta", &delta.to_string());
+        self.call("nudge_brightness", req).map(|_| ())
+    }
+
+    /// Flash a color on a light then restore it, waiting until it's done,
+    /// `POST /v1/room/{id}/light/{light_id}/flash`
+    pub fn flash_light(
+        &self,
+        room: &Uuid,
+        id: &Uuid,
+        color: &Color,
+        count: u8,
+        on_ms: u64,
+        off_ms: u64,
+    ) -> Result<()> {
+        let body = serde_json::json!({
+            "color": color,
+            "count": count,
+            "on_ms": on_ms,
+            "off_ms": off_ms,
+        });
+        let url = self.url(&format!("/v1/room/{}/light/{}/flash", room, id));
+        // the API replies once the flashes are done, allow for them
+        let flashing =
+            Duration::from_millis(u64::from(count).saturating_mul(on_ms.saturating_add(off_ms)));
+        let req = self.agent.post(&url).timeout(CLIENT_TIMEOUT + flashing);
+        self.send_json("flash_light", req, &body).map(|_| ())
+    }
+
+    /// Read the last message sent to a light and its reply,
+    /// `GET /v1/room/{id}/light/{light_id}/debug`
+    pub fn light_debug(&self, room: &Uuid, id: &Uuid) -> Result<UdpExchange> {
+        let url = self.url(&format!("/v1/room/{}/light/{}/debug", room, id));
+        self.json("light_debug", self.agent.get(&url))
+    }
+
+    /// Set a light to match another light in the same room,
+    /// `POST /v1/room/{id}/light/{light_id}/sync/{source_id}`
+    ///
+    /// # Returns
+    ///   the [LightRequest] which was sent
+    ///
+    pub fn sync_light(&self, room: &Uuid, id: &Uuid, source: &Uuid) -> Result<LightRequest> {
+        let url = self.url(&format!("/v1/room/{}/light/{}/sync/{}", room, id, source));
+        self.json("sync_light", self.agent.post(&url))
+    }
+
+    /// Update lighting settings for all lights matching the filter,
+    /// `POST /v1/lights/apply`
+    ///
+    /// # Returns
+    ///   [Vec] of [LightRef] of the matched lights
+    ///
+    pub fn apply(&self, filter: &LightFilter, req: &LightRequest) -> Result<Vec<LightRef>> {
+        let body = serde_json::json!({"filter": filter, "request": req});
+        let url = self.url("/v1/lights/apply");
+        Self::decode(
+            "apply",
+            self.send_json("apply", self.agent.post(&url), &body)?,
+        )
+    }
+
+    /// List the IPs of all known lights, sorted, `GET /v1/ips`
+    pub fn list_ips(&self) -> Result<Vec<Ipv4Addr>> {
+        self.json("list_ips", self.agent.get(&self.url("/v1/ips")))
+    }
+
+    /// Discover bulbs on the API's local network, `GET /v1/discover`
+    pub fn discover(
+        &self,
+        timeout_ms: Option<u64>,
+        expected: Option<usize>,
+    ) -> Result<Vec<DiscoveredLight>> {
+        // allow for the time the API spends waiting for replies
+        let wait = Duration::from_millis(timeout_ms.unwrap_or_default());
+        let mut req = self
+            .agent
+            .get(&self.url("/v1/discover"))
+            .timeout(CLIENT_TIMEOUT + wait);
+        if let Some(timeout_ms) = timeout_ms {
+            req = req.query("timeout", &timeout_ms.to_string());
+        }
+        if let Some(expected) = expected {
+            req = req.query("expected", &expected.to_string());
+        }
+        self.json("discover", req)
+    }
+
+    /// Probe every light, applying the action to unreachable ones unless
+    /// it's a dry run, `POST /v1/maintenance/prune`
+    pub fn prune(&self, action: Option<PruneAction>, dry_run: bool) -> Result<PruneReport> {
+        // probing every light can take a while in large homes
+        let mut req = self
+            .agent
+            .post(&self.url("/v1/maintenance/prune"))
+            .timeout(CLIENT_TIMEOUT * 6)
+            .query("dry_run", &dry_run.to_string());
+        if let Some(action) = action {
+            let action = match action {
+                PruneAction::Disable => "disable",
+                PruneAction::ClearStatus => "clear_status",
+            };
+            req = req.query("action", action);
+        }
+        self.json("prune", req)
+    }
+
+    /// Read the per-light outcomes of an operation, `GET /v1/operations/{id}`
+    pub fn read_operation(&self, id: &Uuid) -> Result<Operation> {
+        let url = self.url(&format!("/v1/operations/{}", id));
+        self.json("read_operation", self.agent.get(&url))
+    }
+
+    /// Read the presence schedule, `GET /v1/presence`
+    pub fn presence(&self) -> Result<PresenceSchedule> {
+        self.json("presence", self.agent.get(&self.url("/v1/presence")))
+    }
+
+    /// Replace the presence schedule, `PUT /v1/presence`
+    pub fn set_presence(&self, schedule: &PresenceSchedule) -> Result<PresenceSchedule> {
+        let put = self.agent.put(&self.url("/v1/presence"));
+        Self::decode(
+            "set_presence",
+            self.send_json("set_presence", put, schedule)?,
+        )
+    }
+
+    /// Enable presence for a room, `PUT /v1/room/{id}/presence`
+    pub fn enable_presence(&self, room: &Uuid) -> Result<PresenceSchedule> {
+        let url = self.url(&format!("/v1/room/{}/presence", room));
+        self.json("enable_presence", self.agent.put(&url))
+    }
+
+    /// Disable presence for a room, `DELETE /v1/room/{id}/presence`
+    pub fn disable_presence(&self, room: &Uuid) -> Result<PresenceSchedule> {
+        let url = self.url(&format!("/v1/room/{}/presence", room));
+        self.json("disable_presence", self.agent.delete(&url))
+    }
+
+    /// List scenes, optionally only those a light supports, `GET /v1/scenes`
+    pub fn list_scenes(&self, light: Option<&Uuid>) -> Result<Vec<SceneName>> {
+        let mut req = self.agent.get(&self.url("/v1/scenes"));
+        if let Some(light) = light {
+            req = req.query("light", &light.to_string());
+        }
+        self.json("list_scenes", req)
+    }
+
+    /// List the favorite scenes, `GET /v1/scenes/favorites`
+    pub fn list_favorite_scenes(&self) -> Result<Vec<SceneName>> {
+        let url = self.url("/v1/scenes/favorites");
+        self.json("list_favorite_scenes", self.agent.get(&url))
+    }
+
+    /// Read the configuration the API resolved from env, `GET /v1/config`
+    pub fn config(&self) -> Result<EffectiveConfig> {
+        self.json("config", self.agent.get(&self.url("/v1/config")))
+    }
+
+    /// Read the audit log, `GET /v1/audit`
+    pub fn list_audit(&self, light: Option<&Uuid>, since: Option<u64>) -> Result<Vec<AuditEntry>> {
+        let mut req = self.agent.get(&self.url("/v1/audit"));
+        if let Some(light) = light {
+            req = req.query("light", &light.to_string());
+        }
+        if let Some(since) = since {
+            req = req.query("since", &since.to_string());
+        }
+        self.json("list_audit", req)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Send the request and decode the JSON response body
+    fn json<T: DeserializeOwned>(&self, action: &str, req: ureq::Request) -> Result<T> {
+        Self::decode(action, self.call(action, req)?)
+    }
+
+    /// Decode the JSON response body
+    fn decode<T: DeserializeOwned>(action: &str, resp: ureq::Response) -> Result<T> {
+        resp.into_json()
+            .map_err(|e| Error::client(action, &e.to_string()))
+    }
+
+    /// Percent-encode the value for use as a single URL path segment
+    ///
+    /// Only unreserved characters (RFC 3986) are left as is, so spaces,
+    /// `/`, `?` and `#` in room names can't change the requested path
+    ///
+    fn encode_segment(value: &str) -> String {
+        let mut encoded = String::with_capacity(value.len());
+        for byte in value.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    encoded.push(char::from(byte))
+                }
+                _ => encoded.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        encoded
+    }
+
+    /// Link the created room to the ID the API assigned it
+    fn linked(created: CreatedRoom) -> Room {
+        let mut room = created.room;
+        room.link(&created.id);
+        room
+    }
+
+    /// Send the request with no body
+    fn call(&self, action: &str, req: ureq::Request) -> Result<ureq::Response> {
+        req.call().map_err(|e| Self::error(action, e))
+    }
+
+    /// Send the request with a JSON body
+    fn send_json<T: Serialize>(
+        &self,
+        action: &str,
+        req: ureq::Request,
+        body: &T,
+    ) -> Result<ureq::Response> {
+        req.send_json(body).map_err(|e| Self::error(action, e))
+    }
+
+    /// Include the API's error message when it returned an error status
+    fn error(action: &str, err: ureq::Error) -> Error {
+        match err {
+            ureq::Error::Status(code, resp) => {
+                let body = resp.into_string().unwrap_or_default();
+                Error::client(action, &format!("{}: {}", code, body))
+            }
+            e => Error::client(action, &e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn created_room_linked() {
+        let id = Uuid::new_v4();
+        let body = format!(r#"{{"id": "{}", "room": {{"name": "test"}}}}"#, id);
+        let created: CreatedRoom = serde_json::from_str(&body).unwrap();
+
+        let room = RizClient::linked(created);
+        assert_eq!(room.id(), id);
+        assert_eq!(room.name(), "test");
+    }
+
+    #[test]
+    fn path_segment_encoded() {
+        assert_eq!(RizClient::encode_segment("kitchen"), "kitchen");
+        assert_eq!(
+            RizClient::encode_segment("living room/2?#"),
+            "living%20room%2F2%3F%23"
+        );
+        assert_eq!(RizClient::encode_segment("café"), "caf%C3%A9");
+    }
+
+    #[test]
+    fn base_url_trailing_slash() {
+        let client = RizClient::new("http://10.1.2.3:8080/");
+        assert_eq!(client.url("/v1/ping"), "http://10.1.2.3:8080/v1/ping");
+    }
+}
@@ -15,10 +15,18 @@ pub enum Error {
     #[error("failed to load json: {0:?}")]
     JsonLoad(serde_json::Error),
 
+    /// Failed to write `rooms.json` to the storage path
+    #[error("failed to persist {path}: {err:?}")]
+    Persist { path: String, err: std::io::Error },
+
     /// Some socket error when communicating with a bulb
     #[error("socket {action} error: {err:?}")]
     Socket { action: String, err: std::io::Error },
 
+    /// The reply to a bulb command didn't look like it came from a Wiz bulb
+    #[error("{0} replied, but does not look like a Wiz bulb")]
+    NotAWizBulb(Ipv4Addr),
+
     /// Failed to decode UDP response bytes as UTF-8
     #[error("utf8 decoding error: {0:?}")]
     Utf8Decode(FromUtf8Error),
@@ -27,14 +35,66 @@ pub enum Error {
     #[error("invalid payload; no attributes set")]
     NoAttribute,
 
+    /// Used when a reboot is requested along with lighting settings
+    #[error("invalid request; reboot can not be combined with lighting settings")]
+    RebootWithPayload,
+
+    /// Used when an RGB color is sent along with cool or warm white
+    #[error("invalid payload; rgb color can not be combined with cool or warm white")]
+    ColorWithWhite,
+
+    /// Used when a raw scene ID of 0 is requested, valid IDs are 1 to 255
+    #[error("invalid request; scene_id must be from 1 to 255")]
+    InvalidSceneId,
+
+    /// Used when a partial color can't be merged, no color is known
+    #[error("invalid request; no known color to merge the partial color onto")]
+    NoKnownColor,
+
+    /// Used when asked to confirm a reboot, the bulb can't be read back
+    #[error("invalid request; a reboot can not be confirmed")]
+    UnconfirmableReboot,
+
+    /// The bulb never reported the requested state, see [crate::models::Light::ensure]
+    #[error("{ip} did not confirm the requested state after {attempts} attempts")]
+    Unconfirmed { ip: Ipv4Addr, attempts: u8 },
+
+    /// Used when a flash's count or timing is out of range
+    #[error("invalid flash; {0}")]
+    InvalidFlash(String),
+
+    /// Used when a presence schedule setting is out of range
+    #[error("invalid presence schedule; {0}")]
+    InvalidSchedule(String),
+
+    /// The bulb's firmware is too old (or unknown) for a feature
+    #[error("{feature} is not supported on firmware {version}")]
+    UnsupportedFirmware { feature: String, version: String },
+
     /// Attempting to look up or modify a room which doesn't exist
     #[error("room not found {0}")]
     RoomNotFound(Uuid),
 
+    /// Attempting to look up a room by a name which doesn't exist
+    #[error("no room named {0}")]
+    RoomNameNotFound(String),
+
+    /// Attempting to look up a room by a name shared by multiple rooms
+    #[error("multiple rooms named {0}")]
+    AmbiguousRoomName(String),
+
     /// Attempting to look up or modify a light which doesn't exist
     #[error("light {light_id:?} not found in room {room_id:?}")]
     LightNotFound { room_id: Uuid, light_id: Uuid },
 
+    /// Attempting to look up or remove a light by an IP which isn't known
+    #[error("no light with ip {0}")]
+    LightIpNotFound(Ipv4Addr),
+
+    /// Attempting to add a light to a room which is already full
+    #[error("room {room_id:?} is limited to {max} lights")]
+    TooManyLights { room_id: Uuid, max: usize },
+
     /// Attempting to add a light with an invalid IP
     #[error("light with ip {ip} is invalid because the IP is {reason}")]
     InvalidIP { ip: Ipv4Addr, reason: String },
@@ -55,13 +115,26 @@ pub enum Error {
     #[error("invalid color string: {0}")]
     InvalidColorString(String),
 
+    /// Unable to parse a mac address, see [crate::models::Light::register]
+    #[error("invalid mac address: {0}")]
+    InvalidMac(String),
+
+    /// Request to a remote Riz API failed
+    #[cfg(feature = "client")]
+    #[error("riz client {action} error: {reason}")]
+    Client { action: String, reason: String },
+
     /// Unable to queue work, broken channel maybe
     #[error("unable to queue work: {0:?}")]
-    Dispatch(SendError<DispatchMessage>),
+    Dispatch(Box<SendError<DispatchMessage>>),
 
     /// Unable to process return path from worker
     #[error("unable to process work: {0:?}")]
     Reply(SendError<ReplyMessage>),
+
+    /// The worker stopped before answering a queued read
+    #[error("worker stopped before replying")]
+    NoReply,
 }
 
 impl Error {
@@ -73,6 +146,23 @@ impl Error {
         }
     }
 
+    /// Create a new persist error
+    pub fn persist(path: &str, err: std::io::Error) -> Self {
+        Error::Persist {
+            path: path.to_string(),
+            err,
+        }
+    }
+
+    /// Create a new remote Riz API client error
+    #[cfg(feature = "client")]
+    pub fn client(action: &str, reason: &str) -> Self {
+        Error::Client {
+            action: action.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
     /// Create a new light not found error
     pub fn light_not_found(room_id: &Uuid, light_id: &Uuid) -> Self {
         Error::LightNotFound {
@@ -81,6 +171,14 @@ impl Error {
         }
     }
 
+    /// Create a new too many lights error
+    pub fn too_many_lights(room_id: &Uuid, max: usize) -> Self {
+        Error::TooManyLights {
+            room_id: *room_id,
+            max,
+        }
+    }
+
     /// Create a new invalid IP error
     pub fn invalid_ip(ip: &Ipv4Addr, reason: &str) -> Self {
         Error::InvalidIP {
@@ -89,6 +187,38 @@ impl Error {
         }
     }
 
+    /// Check if this is a socket error from a bulb not replying in time
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            self,
+            Error::Socket { err, .. }
+                if matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut)
+        )
+    }
+
+    /// Create a new unconfirmed state error
+    pub fn unconfirmed(ip: &Ipv4Addr, attempts: u8) -> Self {
+        Error::Unconfirmed { ip: *ip, attempts }
+    }
+
+    /// Create a new invalid flash error
+    pub fn invalid_flash(reason: &str) -> Self {
+        Error::InvalidFlash(reason.to_string())
+    }
+
+    /// Create a new invalid presence schedule error
+    pub fn invalid_schedule(reason: &str) -> Self {
+        Error::InvalidSchedule(reason.to_string())
+    }
+
+    /// Create a new unsupported firmware error
+    pub fn unsupported_firmware(feature: &str, version: &str) -> Self {
+        Error::UnsupportedFirmware {
+            feature: feature.to_string(),
+            version: version.to_string(),
+        }
+    }
+
     /// Create a new no change light error
     pub fn no_change_light(room_id: &Uuid, light_id: &Uuid) -> Self {
         Error::NoChangeLight {
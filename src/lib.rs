@@ -20,23 +20,39 @@
 //! Riz light control CLI
 //!
 //! Usage: riz [OPTIONS] [IP]...
+//!        riz <COMMAND>
+//!
+//! Commands:
+//!   run   Run a JSON-lines file of commands, in order
+//!   room  Send the options to all bulbs in the room (name or ID), see --room
+//!   help  Print this message or the help of the given subcommand(s)
 //!
 //! Arguments:
-//!   [IP]...  Bulb IPv4 address(es)
+//!   [IP]...  Bulb IPv4 address(es) or nickname(s)
 //!
 //! Options:
+//!   -R, --room <ROOM>              Target all bulbs in the room (name or ID) from rooms.json
 //!   -b, --brightness <BRIGHTNESS>  Set the bulb brightness (10-100)
-//!   -c, --color <COLOR>            Set the bulb color as r,g,b (0-255)
+//!   -c, --color <COLOR>            Set the bulb color as r,g,b (0-255) or #rrggbb
 //!   -C, --cool <COOL>              Set the cool white value (1-100)
 //!   -W, --warm <WARM>              Set the warm white value (1-100)
 //!   -p, --speed <SPEED>            Set the bulb speed (20-200)
 //!   -t, --temp <TEMP>              Set the bulb temperature in Kelvin (1000-8000)
+//!       --warm-dim <BRIGHTNESS>    Dim to the brightness, warming as it dims (10-100)
 //!   -l, --list                     List the available scene IDs
+//!   -g, --group                    Group the listed scenes by category
+//!   -F, --favorites                Only list the favorite scenes (set with RIZ_FAVORITE_SCENES)
 //!   -s, --scene <SCENE>            Set the scene by ID
+//!       --random [<MODE>]          Set a random color or scene (color, scene or any)
 //!   -o, --on                       Turn the bulb on
 //!   -f, --off                      Turn the bulb off
 //!   -r, --reboot                   Reboot the bulb
+//!   -S, --stagger <STAGGER>        Wait between each bulb when targeting several (milliseconds)
 //!   -i, --status                   Get the current bulb status
+//!   -j, --json                     Print each bulb status as a line of JSON
+//!       --no-color                 Don't use colors in the output (also set by NO_COLOR)
+//!   -P, --probe                    Probe the bulb model before setting cool/warm white
+//!   -n, --ping                     Check the bulb is reachable and print the round trip time
 //!   -h, --help                     Print help
 //!   -V, --version                  Print version
 //! ```
@@ -44,14 +60,28 @@
 
 pub mod models;
 
+mod audit_log;
+#[cfg(feature = "client")]
+mod client;
 mod errors;
+mod presence_schedule;
+mod request_id;
 mod routes;
 mod storage;
+mod sync;
 mod worker;
 
+pub use audit_log::{AuditEntry, AuditLog};
+#[cfg(feature = "client")]
+pub use client::RizClient;
 pub use errors::Error;
-pub use routes::{health, lights, rooms};
-pub use storage::Storage;
-pub use worker::Worker;
+pub use presence_schedule::{start_presence, ActiveHours, Presence, PresenceSchedule};
+pub use request_id::{RequestId, REQUEST_ID_HEADER};
+pub use routes::{
+    audit, config, discover, health, lights, maintenance, operations, presence, rooms, scenes,
+};
+pub use storage::{Storage, WriteHealth};
+pub use sync::lock;
+pub use worker::{Origin, Worker};
 
 pub type Result<T> = std::result::Result<T, Error>;
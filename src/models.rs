@@ -1,26 +1,111 @@
 //! Riz models
 
-use std::collections::HashMap;
-use std::net::{Ipv4Addr, UdpSocket};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::panic;
 use std::result::Result as StdResult;
 use std::str::FromStr;
-use std::time::Duration;
-
-use log::debug;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{env, fs};
+
+use convert_case::{Case, Casing};
+use ipnet::Ipv4Net;
+use log::{debug, info, warn};
+use rand::{seq::IteratorRandom, Rng};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::{json, Map, Value};
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::{Error, Result};
+use crate::{sync::lock, Error, Result};
+
+const SCENE_NAMES_ENV_KEY: &str = "RIZ_SCENE_NAMES";
+const FAVORITE_SCENES_ENV_KEY: &str = "RIZ_FAVORITE_SCENES";
+const COMMAND_DELAY_ENV_KEY: &str = "RIZ_COMMAND_DELAY_MS";
+const SPLIT_SCENE_DIMMING_ENV_KEY: &str = "RIZ_SPLIT_SCENE_DIMMING";
+const STRICT_UTF8_ENV_KEY: &str = "RIZ_STRICT_UTF8";
+const MAX_LIGHTS_ENV_KEY: &str = "RIZ_MAX_ROOM_LIGHTS";
+const DEFAULT_MAX_LIGHTS: usize = 100;
+const STATUS_CONCURRENCY_ENV_KEY: &str = "RIZ_STATUS_CONCURRENCY";
+const DEFAULT_STATUS_CONCURRENCY: usize = 8;
+const BULB_TIMEOUT_ENV_KEY: &str = "RIZ_BULB_TIMEOUT_MS";
+const DEFAULT_BULB_TIMEOUT_MS: u64 = 1000;
+const BULB_RETRIES_ENV_KEY: &str = "RIZ_BULB_RETRIES";
+const DEFAULT_BULB_RETRIES: u8 = 0;
+const WARM_DIM_ENV_KEY: &str = "RIZ_WARM_DIM_KELVIN";
+const DEBUG_EXCHANGES_ENV_KEY: &str = "RIZ_DEBUG_EXCHANGES";
+const RESET_REQUEST_ENV_KEY: &str = "RIZ_RESET_REQUEST";
+
+/// Most flashes allowed in one [Light::flash]
+pub const MAX_FLASH_COUNT: u8 = 10;
+
+/// Longest on or off time allowed for each [Light::flash], in milliseconds
+pub const MAX_FLASH_MS: u64 = 2000;
+
+/// Standard port Wiz bulbs listen for commands on
+const BULB_PORT: u16 = 38899;
+
+/// Source of the `id` sent with each bulb command, see [WizProtocol]
+static REQUEST_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Last exchange with each bulb by IP, see [Light::last_exchange]
+static EXCHANGES: Mutex<BTreeMap<Ipv4Addr, UdpExchange>> = Mutex::new(BTreeMap::new());
+
+/// Scene IDs playable by tunable white bulbs
+const TW_SCENES: [u8; 14] = [6, 9, 10, 11, 12, 13, 14, 15, 16, 18, 29, 30, 31, 32];
+
+/// Scene IDs playable by dimmable white bulbs
+const DW_SCENES: [u8; 8] = [9, 10, 13, 14, 29, 30, 31, 32];
+
+/// Call `f` for each light on its own thread, at most `max` at once
+///
+/// # Returns
+///   [Vec] of the results, in the same order as the lights
+///
+fn concurrently<T, F>(lights: &[&Light], max: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&Light) -> T + Sync,
+{
+    let f = &f;
+    let mut results = Vec::with_capacity(lights.len());
+    for batch in lights.chunks(max) {
+        thread::scope(|s| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|light| s.spawn(move || f(light)))
+                .collect();
+            for handle in handles {
+                results.push(handle.join().unwrap_or_else(|e| panic::resume_unwind(e)));
+            }
+        });
+    }
+    results
+}
+
+/// The bulb type part of a `moduleName`, eg: `SHTW1C` of `ESP01_SHTW1C_31`
+///
+/// Wiz module names are the chip, bulb type and revision joined by `_`.
+/// Names which don't follow this have no bulb type.
+///
+fn bulb_type(module_name: &str) -> &str {
+    module_name.split('_').nth(1).unwrap_or_default()
+}
 
 /// Rooms group lights logically to allow for batched actions
 ///
 /// NB: They don't have to be the same as configured by the Wiz app
 ///
 #[serde_with::skip_serializing_none]
+#[serde_with::serde_as]
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct Room {
     #[schema(min_length = 1, max_length = 100)]
@@ -28,6 +113,11 @@ pub struct Room {
     #[schema(max_items = 100)]
     lights: Option<HashMap<Uuid, Light>>,
 
+    /// Subnet of the room's lights in CIDR notation, when not classful
+    #[serde_as(as = "Option<serde_with::DisplayFromStr>")]
+    #[schema(value_type = Option<String>, example = "192.168.0.0/22")]
+    subnet: Option<Ipv4Net>,
+
     #[serde(skip)]
     id: Uuid,
     #[serde(skip)]
@@ -40,6 +130,7 @@ impl Room {
         Room {
             name: String::from(name),
             lights: None,
+            subnet: None,
             id: Uuid::new_v4(),
             linked: false,
         }
@@ -60,7 +151,10 @@ impl Room {
         self.linked = true;
     }
 
-    /// Ask all bulbs in this room for their current status
+    /// Ask all enabled bulbs in this room for their current status
+    ///
+    /// Bulbs are asked concurrently, up to [Self::status_concurrency] at
+    /// a time, so a large room doesn't flood the access point
     ///
     /// # Returns
     ///   a [Result] of:
@@ -68,14 +162,92 @@ impl Room {
     ///   and [Error] if there's any error getting status from any bulb
     ///
     pub fn get_status(&mut self) -> Result<Vec<LightingResponse>> {
-        let mut resp = Vec::new();
-        if let Some(lights) = &mut self.lights {
-            for light in lights.values_mut() {
-                let status = light.get_status()?;
-                resp.push(LightingResponse::status(light.ip, status));
+        let lights: Vec<&Light> = self.enabled_lights().map(|(_, light)| light).collect();
+        let statuses = concurrently(&lights, Self::status_concurrency(), Light::get_status);
+        lights
+            .iter()
+            .zip(statuses)
+            .map(|(light, status)| Ok(LightingResponse::status(light.ip, status?)))
+            .collect()
+    }
+
+    /// Ask all enabled bulbs in this room for their status, keeping failures
+    ///
+    /// Unlike [Self::get_status], a bulb which doesn't reply doesn't fail
+    /// the others. Bulbs are asked concurrently, up to
+    /// [Self::status_concurrency] at a time.
+    ///
+    /// # Returns
+    ///   [Vec] of (light ID, [Result] of the bulb's [LightStatus])
+    ///
+    pub fn read_statuses(&self) -> Vec<(Uuid, Result<LightStatus>)> {
+        let (ids, lights): (Vec<Uuid>, Vec<&Light>) = self
+            .enabled_lights()
+            .map(|(id, light)| (*id, light))
+            .unzip();
+        let statuses = concurrently(&lights, Self::status_concurrency(), Light::get_status);
+        ids.into_iter().zip(statuses).collect()
+    }
+
+    /// Flag the light's last known status as stale, see [LightStatus::stale]
+    ///
+    /// # Returns
+    ///   [bool] of if the light is in this room and has a known status
+    ///
+    pub fn mark_stale(&mut self, light: &Uuid) -> bool {
+        match self.read_mut(light).and_then(|light| light.status.as_mut()) {
+            Some(status) => {
+                status.stale = true;
+                true
             }
+            None => false,
         }
-        Ok(resp)
+    }
+
+    /// Check which bulbs in this room answer, regardless of their settings
+    ///
+    /// All lights are probed, including disabled ones, up to
+    /// [Self::status_concurrency] at a time, see [Light::ping]
+    ///
+    /// # Returns
+    ///   [Vec] of (light ID, if it replied, round trip time if it did)
+    ///
+    pub fn reachability(&self) -> Vec<(Uuid, bool, Option<Duration>)> {
+        let (ids, lights): (Vec<Uuid>, Vec<&Light>) =
+            self.iter_lights().map(|(id, light)| (*id, light)).unzip();
+        let pings = concurrently(&lights, Self::status_concurrency(), Light::ping);
+        ids.into_iter()
+            .zip(pings)
+            .map(|(id, ping)| match ping {
+                Ok(elapsed) => (id, true, Some(elapsed)),
+                Err(_) => (id, false, None),
+            })
+            .collect()
+    }
+
+    /// Maximum number of status reads in flight at once, see [Self::get_status]
+    ///
+    /// Set with `RIZ_STATUS_CONCURRENCY` (env var), defaults to 8. Lower
+    /// it if a weak access point drops replies from large rooms.
+    ///
+    pub fn status_concurrency() -> usize {
+        env::var(STATUS_CONCURRENCY_ENV_KEY)
+            .ok()
+            .and_then(|max| max.parse::<usize>().ok())
+            .filter(|max| *max > 0)
+            .unwrap_or(DEFAULT_STATUS_CONCURRENCY)
+    }
+
+    /// Maximum number of lights allowed in a room
+    ///
+    /// Set with `RIZ_MAX_ROOM_LIGHTS` (env var), defaults to 100. Room-wide
+    /// commands queue one worker task per light, so keep this modest.
+    ///
+    pub fn max_lights() -> usize {
+        env::var(MAX_LIGHTS_ENV_KEY)
+            .ok()
+            .and_then(|max| max.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_LIGHTS)
     }
 
     /// Store a newly created [Light] in this room
@@ -85,7 +257,14 @@ impl Room {
     /// # Returns
     ///   the newly created [Uuid] for the [Light]
     ///
+    /// # Errors
+    ///   [Error::TooManyLights] if the room already has [Self::max_lights]
+    ///
     pub fn new_light(&mut self, light: Light) -> Result<Uuid> {
+        let max = Self::max_lights();
+        if self.iter_lights().count() >= max {
+            return Err(Error::too_many_lights(&self.id, max));
+        }
         self.validate_light(&light, None)?;
         let mut id = Uuid::new_v4();
         if let Some(lights) = self.lights.as_mut() {
@@ -99,6 +278,23 @@ impl Room {
         Ok(id)
     }
 
+    /// Replace the IDs of all lights in this room with newly generated ones
+    ///
+    /// Used when creating a room with initial lights, any client
+    /// supplied light IDs are never honored.
+    ///
+    /// # Errors
+    ///   [Error::TooManyLights] if the room has more than [Self::max_lights]
+    ///   [Error::InvalidIP] if more than one light in the room has the same IP
+    ///
+    pub(crate) fn reassign_light_ids(&mut self) -> Result<()> {
+        let lights = self.lights.take().unwrap_or_default();
+        for light in lights.into_values() {
+            self.new_light(light)?;
+        }
+        Ok(())
+    }
+
     /// Removes a light from the room's lights
     ///
     /// # Returns
@@ -189,6 +385,61 @@ impl Room {
         self.lights.as_ref().map(|lights| lights.keys().collect())
     }
 
+    /// Iterate over all lights in this room, by ID
+    pub fn iter_lights(&self) -> impl Iterator<Item = (&Uuid, &Light)> {
+        self.lights.iter().flat_map(|lights| lights.iter())
+    }
+
+    /// Iterate over the enabled lights in this room, by ID
+    ///
+    /// Used by all room-wide commands, see [Light::enabled]
+    ///
+    pub fn enabled_lights(&self) -> impl Iterator<Item = (&Uuid, &Light)> {
+        self.iter_lights().filter(|(_, light)| light.enabled())
+    }
+
+    /// This room's lights in a stable order, sorted by IP
+    ///
+    /// Used as each light's position when sharing a [RoomPreset]
+    ///
+    pub fn ordered_lights(&self) -> Vec<(&Uuid, &Light)> {
+        let mut lights: Vec<_> = self.iter_lights().collect();
+        lights.sort_by_key(|(_, light)| light.ip);
+        lights
+    }
+
+    /// Export this room's last known lighting as a [RoomPreset]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::net::Ipv4Addr;
+    /// use riz::models::{Light, Room};
+    ///
+    /// let mut room = Room::new("movies");
+    /// room.new_light(Light::new(Ipv4Addr::new(10, 1, 2, 3), None)).unwrap();
+    ///
+    /// let preset = room.preset();
+    /// assert_eq!(preset.name(), "movies");
+    /// assert!(preset.lights()[0].is_none());
+    /// ```
+    ///
+    pub fn preset(&self) -> RoomPreset {
+        RoomPreset {
+            name: self.name.clone(),
+            lights: self
+                .ordered_lights()
+                .into_iter()
+                .map(|(_, light)| light.status().map(LightRequest::from))
+                .collect(),
+        }
+    }
+
+    /// Iterate over all lights in this room mutably, by ID
+    pub fn iter_lights_mut(&mut self) -> impl Iterator<Item = (&Uuid, &mut Light)> {
+        self.lights.iter_mut().flat_map(|lights| lights.iter_mut())
+    }
+
     /// Read a light in this room by ID
     ///
     /// # Returns
@@ -235,6 +486,35 @@ impl Room {
         &self.name
     }
 
+    /// Accessor for this room's ID
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Accessor for this room's subnet, if set
+    pub fn subnet(&self) -> Option<&Ipv4Net> {
+        self.subnet.as_ref()
+    }
+
+    /// Set the subnet of this room's lights
+    ///
+    /// Used instead of the classful network of each light's IP to check
+    /// the light isn't the subnet's network or broadcast address
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Room;
+    ///
+    /// let room = Room::new("test").with_subnet("192.168.0.0/22".parse().unwrap());
+    /// assert_eq!(room.subnet().unwrap().prefix_len(), 22);
+    /// ```
+    ///
+    pub fn with_subnet(mut self, subnet: Ipv4Net) -> Self {
+        self.subnet = Some(subnet.trunc());
+        self
+    }
+
     /// Update our (non-light) attributes from the other instance
     ///
     /// # Examples
@@ -246,25 +526,39 @@ impl Room {
     /// let other = Room::new("bar");
     /// assert!(room.update(&other));
     /// assert_eq!(room.name(), "bar");
+    /// assert!(!room.update(&other));
     /// ```
     ///
     pub fn update(&mut self, other: &Self) -> bool {
-        if self.name == other.name {
-            return false;
+        let mut any_update = false;
+        if self.name != other.name {
+            self.name.clone_from(&other.name);
+            any_update = true;
         }
-        self.name.clone_from(&other.name);
-        true
+
+        if self.subnet != other.subnet {
+            self.subnet = other.subnet;
+            any_update = true;
+        }
+
+        any_update
     }
 
     fn validate_light(&self, light: &Light, light_id: Option<&Uuid>) -> Result<()> {
-        let ip = light.ip();
+        let ips: Vec<Ipv4Addr> = light.ips().collect();
+        for (i, ip) in ips.iter().enumerate() {
+            if ips[..i].contains(ip) {
+                return Err(Error::invalid_ip(ip, "repeated in the light's aliases"));
+            }
+        }
+
         if let Some(lights) = self.lights.as_ref() {
             for (id, known) in lights {
                 if Some(id) == light_id {
                     continue;
                 }
-                if known.ip() == ip {
-                    return Err(Error::invalid_ip(&ip, "already known"));
+                if let Some(ip) = ips.iter().find(|ip| known.has_ip(**ip)) {
+                    return Err(Error::invalid_ip(ip, "already known"));
                 }
             }
         }
@@ -297,10 +591,41 @@ pub struct Light {
       pattern = r"^(((1[\d]{0,2})|(2([0-4]?[\d]|5[0-5]))|([3-9]?[\d])|[\d])\.){0,3}((1[\d]{0,2})|(2([0-4]?[\d]|5[0-5]))|([3-9]?[\d])|[\d])$")]
     ip: Ipv4Addr,
 
+    /// More IPs of the same fixture, sent the same commands as `ip`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schema(value_type = Vec<String>)]
+    aliases: Vec<Ipv4Addr>,
+
     /// Name of light, arbitrary (user supplied)
     #[schema(min_length = 1, max_length = 100)]
     name: Option<String>,
 
+    /// Short nickname for the light, arbitrary (user supplied)
+    #[serde(default)]
+    #[schema(min_length = 1, max_length = 20)]
+    nickname: Option<String>,
+
+    /// Reply timeout in milliseconds, overrides `RIZ_BULB_TIMEOUT_MS`
+    #[serde(default)]
+    #[schema(minimum = 1)]
+    timeout_ms: Option<u64>,
+
+    /// Resends after a timeout, overrides `RIZ_BULB_RETRIES`
+    #[serde(default)]
+    retries: Option<u8>,
+
+    /// Message format for compatible non-Wiz firmware, see [WizProtocol]
+    #[serde(default)]
+    protocol: Option<WizProtocol>,
+
+    /// Disabled lights are skipped by room-wide and global commands
+    #[serde(default = "Light::default_enabled")]
+    enabled: bool,
+
+    /// Capabilities of the bulb, once its system config has been fetched
+    #[serde(default)]
+    capabilities: Option<BulbCapabilities>,
+
     /// Last known status, if any
     status: Option<LightStatus>,
 }
@@ -310,16 +635,120 @@ impl Light {
     pub fn new(ip: Ipv4Addr, name: Option<&str>) -> Self {
         Light {
             ip,
+            aliases: Vec::new(),
             name: name.map(String::from),
+            nickname: None,
+            timeout_ms: None,
+            retries: None,
+            protocol: None,
+            enabled: true,
+            capabilities: None,
             status: None,
         }
     }
 
+    fn default_enabled() -> bool {
+        true
+    }
+
+    /// Set a short nickname for this light, alongside its name
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::net::Ipv4Addr;
+    /// use riz::models::Light;
+    ///
+    /// let light = Light::new(Ipv4Addr::new(10, 1, 2, 3), Some("Ceiling Light 3"))
+    ///     .with_nickname("C3");
+    /// assert_eq!(light.name(), Some("Ceiling Light 3"));
+    /// assert_eq!(light.nickname(), Some("C3"));
+    /// ```
+    ///
+    pub fn with_nickname(mut self, nickname: &str) -> Self {
+        self.nickname = Some(nickname.to_string());
+        self
+    }
+
+    /// Set this bulb's reply timeout and resends, instead of the globals
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::net::Ipv4Addr;
+    /// use std::time::Duration;
+    /// use riz::models::Light;
+    ///
+    /// let light = Light::new(Ipv4Addr::new(10, 1, 2, 3), None)
+    ///     .with_timeouts(Duration::from_millis(2500), 3);
+    /// assert_eq!(light.timeout(), Duration::from_millis(2500));
+    /// assert_eq!(light.retries(), 3);
+    /// ```
+    ///
+    pub fn with_timeouts(mut self, timeout: Duration, retries: u8) -> Self {
+        self.timeout_ms = Some(timeout.as_millis() as u64);
+        self.retries = Some(retries);
+        self
+    }
+
+    /// Treat more IPs as part of this light, for multi-bulb fixtures
+    ///
+    /// Every command is sent to all of the fixture's IPs, and its status
+    /// is merged from all of their replies, see [Self::get_status]. Replies
+    /// are reported from [Self::ip].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::net::Ipv4Addr;
+    /// use riz::models::Light;
+    ///
+    /// let light = Light::new(Ipv4Addr::new(10, 1, 2, 3), None)
+    ///     .with_aliases(&[Ipv4Addr::new(10, 1, 2, 4)]);
+    /// assert_eq!(light.aliases(), &[Ipv4Addr::new(10, 1, 2, 4)]);
+    /// assert!(light.has_ip(Ipv4Addr::new(10, 1, 2, 4)));
+    /// assert_eq!(light.ips().count(), 2);
+    /// ```
+    ///
+    pub fn with_aliases(mut self, aliases: &[Ipv4Addr]) -> Self {
+        self.aliases = aliases.to_vec();
+        self
+    }
+
     /// Accessor for this bulb's IP address
     pub fn ip(&self) -> Ipv4Addr {
         self.ip
     }
 
+    /// Accessor for the fixture's other IPs, see [Self::with_aliases]
+    pub fn aliases(&self) -> &[Ipv4Addr] {
+        &self.aliases
+    }
+
+    /// Iterate over all of this light's IPs, [Self::ip] first
+    pub fn ips(&self) -> impl Iterator<Item = Ipv4Addr> + '_ {
+        std::iter::once(self.ip).chain(self.aliases.iter().copied())
+    }
+
+    /// Check if the IP is this light's, or one of its aliases
+    pub fn has_ip(&self, ip: Ipv4Addr) -> bool {
+        self.ips().any(|known| known == ip)
+    }
+
+    /// A copy of this light at only one of its IPs
+    fn at(&self, ip: Ipv4Addr) -> Light {
+        Light {
+            ip,
+            aliases: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Each of this fixture's IPs as a light of its own
+    fn emitters(&self) -> Vec<Light> {
+        self.ips().map(|ip| self.at(ip)).collect()
+    }
+
     /// Accessor for this bulb's name
     pub fn name(&self) -> Option<&str> {
         match &self.name {
@@ -328,6 +757,96 @@ impl Light {
         }
     }
 
+    /// Accessor for this bulb's nickname
+    pub fn nickname(&self) -> Option<&str> {
+        self.nickname.as_deref()
+    }
+
+    /// How long to wait for this bulb to reply to each command
+    ///
+    /// This bulb's own timeout if it has one, otherwise set in milliseconds
+    /// with `RIZ_BULB_TIMEOUT_MS` (env var), defaults to 1 second
+    ///
+    pub fn timeout(&self) -> Duration {
+        let ms = self
+            .timeout_ms
+            .or_else(|| {
+                env::var(BULB_TIMEOUT_ENV_KEY)
+                    .ok()
+                    .and_then(|v| v.parse::<u64>().ok())
+            })
+            .filter(|ms| *ms > 0)
+            .unwrap_or(DEFAULT_BULB_TIMEOUT_MS);
+        Duration::from_millis(ms)
+    }
+
+    /// How many times to resend a command after this bulb times out
+    ///
+    /// This bulb's own retries if it has them, otherwise set with
+    /// `RIZ_BULB_RETRIES` (env var), defaults to none
+    ///
+    pub fn retries(&self) -> u8 {
+        self.retries
+            .or_else(|| {
+                env::var(BULB_RETRIES_ENV_KEY)
+                    .ok()
+                    .and_then(|v| v.parse::<u8>().ok())
+            })
+            .unwrap_or(DEFAULT_BULB_RETRIES)
+    }
+
+    /// Set the message format for this bulb, for compatible firmware
+    pub fn with_protocol(mut self, protocol: WizProtocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Message format used with this bulb, the standard Wiz one by default
+    pub fn protocol(&self) -> WizProtocol {
+        self.protocol.clone().unwrap_or_default()
+    }
+
+    /// Enable or disable this light, see [Self::enabled]
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Disable this light, or forget its status, see [PruneAction]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::net::Ipv4Addr;
+    /// use riz::models::{Light, PruneAction};
+    ///
+    /// let mut light = Light::new(Ipv4Addr::new(10, 1, 2, 3), None);
+    /// light.prune(PruneAction::Disable);
+    /// assert!(!light.enabled());
+    /// ```
+    ///
+    pub fn prune(&mut self, action: PruneAction) {
+        match action {
+            PruneAction::Disable => self.enabled = false,
+            PruneAction::ClearStatus => self.status = None,
+        }
+    }
+
+    /// Check if this light is enabled
+    ///
+    /// Disabled lights (eg: dead or removed bulbs) keep their config, but
+    /// are skipped by room-wide and global commands and status reads.
+    /// Commands sent to a disabled light by its ID are still sent.
+    ///
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Accessor for this bulb's capabilities, if known
+    pub fn capabilities(&self) -> Option<&BulbCapabilities> {
+        self.capabilities.as_ref()
+    }
+
     /// Accessor for this bulb's last known status
     pub fn status(&self) -> Option<&LightStatus> {
         self.status.as_ref()
@@ -341,8 +860,15 @@ impl Light {
     /// If you want to update the last known state, you can pass the
     /// newly fetched status into [Self::process_reply]
     ///
+    /// A fixture with aliases is read at all of its IPs, and is emitting
+    /// if any of them are. The other values are from [Self::ip].
+    ///
     pub fn get_status(&self) -> Result<LightStatus> {
-        let resp = self.udp_response(&json!({"method": "getPilot"}))?;
+        if !self.aliases.is_empty() {
+            return self.merged_status();
+        }
+
+        let resp = self.get_pilot()?;
 
         let status: BulbStatus = match serde_json::from_value(resp) {
             Ok(v) => v,
@@ -352,69 +878,564 @@ impl Light {
         Ok(status)
     }
 
-    /// Set new lighting settings on this bulb
-    ///
-    /// Does not update self.status, you can pass the response back
-    /// into [Self::process_reply] if you want to update the internal state
-    ///
-    pub fn set(&self, payload: &Payload) -> Result<LightingResponse> {
-        if payload.is_valid() {
-            match serde_json::to_value(payload) {
-                Ok(msg) => match self.udp_response(&json!({
-                  "method": "setPilot",
-                  "params": msg,
-                })) {
-                    Ok(v) => {
-                        debug!("udp response: {:?}", v);
-                        Ok(LightingResponse::payload(self.ip, payload.clone()))
-                    }
-                    Err(e) => Err(e),
-                },
-                Err(e) => Err(Error::JsonDump(e)),
+    /// Read every IP of this fixture, see [Self::get_status]
+    fn merged_status(&self) -> Result<LightStatus> {
+        let emitters = self.emitters();
+        let emitters: Vec<&Light> = emitters.iter().collect();
+        let statuses = concurrently(&emitters, emitters.len(), Light::get_status);
+
+        let mut merged: Option<LightStatus> = None;
+        for status in statuses {
+            let status = status?;
+            match &mut merged {
+                Some(merged) => merged.emitting |= status.emitting,
+                None => merged = Some(status),
             }
-        } else {
-            Err(Error::NoAttribute)
         }
+        merged.ok_or(Error::NoReply)
     }
 
-    /// Set the [PowerMode] for the light
+    /// Ask the bulb for its status, as the raw `getPilot` reply
     ///
-    /// Works in the same fashion as [Self::set], where the action does not
-    /// mutate internal state. You can pass the response from this method
-    /// to [Self::process_reply] if you want to update this bulb's status
+    /// Unlike [Self::get_status], everything the bulb replied with is
+    /// kept (eg: `rssi`, `mac` and any values Riz doesn't model). For a
+    /// fixture with aliases, this is the reply from [Self::ip].
     ///
-    pub fn set_power(&self, power: &PowerMode) -> Result<LightingResponse> {
-        match power {
-            PowerMode::On => self.toggle_power(true),
-            PowerMode::Off => self.toggle_power(false),
-            PowerMode::Reboot => self.power_cycle(),
-        }
+    pub fn get_pilot(&self) -> Result<Value> {
+        let protocol = self.protocol();
+        self.udp_response(&protocol.message(protocol.get_pilot(), None))
     }
 
-    fn toggle_power(&self, powered: bool) -> Result<LightingResponse> {
-        self.udp_response(&json!({"method": "setState","params": { "state": powered }}))?;
-        Ok(if powered {
-            LightingResponse::power(self.ip, PowerMode::On)
-        } else {
-            LightingResponse::power(self.ip, PowerMode::Off)
-        })
+    /// Check the bulb answers, by asking for its status
+    ///
+    /// # Returns
+    ///   [Duration] of the round trip
+    ///
+    pub fn ping(&self) -> Result<Duration> {
+        let protocol = self.protocol();
+        self.udp_response_timed(&protocol.message(protocol.get_pilot(), None))
+            .map(|(_, elapsed)| elapsed)
     }
 
-    fn power_cycle(&self) -> Result<LightingResponse> {
-        self.udp_response(&json!({"method": "reboot"}))?;
-        Ok(LightingResponse::power(self.ip, PowerMode::Reboot))
-    }
+    /// Ask the bulb for its system configuration (model, firmware, etc)
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::net::Ipv4Addr;
+    /// use std::str::FromStr;
+    /// use riz::models::Light;
+    ///
+    /// let light = Light::new(Ipv4Addr::from_str("10.1.2.3").unwrap(), None);
+    /// if let Ok(config) = light.get_system_config() {
+    ///     println!("{:?} tunable white: {}", config.module_name(), config.tunable_white());
+    /// }
+    /// ```
+    ///
+    pub fn get_system_config(&self) -> Result<SystemConfig> {
+        let protocol = self.protocol();
+        let resp = self.udp_response(&protocol.message(protocol.get_system_config(), None))?;
 
-    /// Update this light's non-lighting attributes
-    fn update(&mut self, other: &Self) -> bool {
-        let mut any_update = false;
-        if self.name != other.name {
-            self.name.clone_from(&other.name);
-            any_update = true;
-        }
+        let config: BulbConfig = match serde_json::from_value(resp) {
+            Ok(v) => v,
+            Err(e) => return Err(Error::JsonLoad(e)),
+        };
+        Ok(config.result)
+    }
+
+    /// Ask the bulb to push its status to a listener whenever it changes
+    ///
+    /// The bulb sends a `syncPilot` message to port 38900 of the listener
+    /// after each change, including from the wall switch or the Wiz app,
+    /// see [crate::Worker::listen_for_pushes]. Registrations expire after
+    /// a while, so they need renewing.
+    ///
+    /// # Errors
+    ///   [Error::InvalidMac] unless the mac is 12 hex digits, optionally
+    ///   separated by `:` or `-`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::net::Ipv4Addr;
+    /// use riz::models::Light;
+    ///
+    /// let light = Light::new(Ipv4Addr::new(10, 1, 2, 3), None);
+    /// light.register(Ipv4Addr::new(10, 1, 2, 100), "aa:bb:cc:dd:ee:ff").unwrap();
+    /// ```
+    ///
+    pub fn register(&self, listener_ip: Ipv4Addr, listener_mac: &str) -> Result<()> {
+        let mac: String = listener_mac
+            .chars()
+            .filter(|c| !matches!(c, ':' | '-'))
+            .collect::<String>()
+            .to_lowercase();
+        if mac.len() != 12 || !mac.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::InvalidMac(listener_mac.to_string()));
+        }
+
+        let params = json!({
+            "phoneIp": listener_ip.to_string(),
+            "register": true,
+            "phoneMac": mac,
+        });
+        let msg = self
+            .protocol()
+            .message(WizProtocol::REGISTRATION, Some(params));
+        self.udp_response(&msg).map(|_| ())
+    }
+
+    /// Set a random color or scene on this bulb
+    ///
+    /// The pick is sent as a normal [Payload], so the response can be
+    /// passed into [Self::process_reply] like any other
+    ///
+    pub fn random(&self, mode: RandomMode) -> Result<LightingResponse> {
+        self.random_with(mode, &mut rand::thread_rng())
+    }
+
+    /// Set a random color or scene on this bulb, picked with the given RNG
+    ///
+    /// Works the same as [Self::random], see [LightRequest::random]
+    ///
+    pub fn random_with<R: Rng + ?Sized>(
+        &self,
+        mode: RandomMode,
+        rng: &mut R,
+    ) -> Result<LightingResponse> {
+        self.set(&Payload::from(&LightRequest::random(mode, rng)))
+    }
+
+    /// Send the request, then read the bulb's status back to confirm it
+    ///
+    /// The request is sent again, up to `attempts` times in all, until
+    /// the bulb reports the requested power, brightness, color, scene and
+    /// temperature. Other settings can't be read back, so aren't checked.
+    ///
+    /// # Returns
+    ///   the bulb's [LightStatus] once it matches the request
+    ///
+    /// # Errors
+    ///   [Error::UnconfirmableReboot] for reboots, or the request's
+    ///   [LightRequest::validate] error
+    ///   [Error::Unconfirmed] if the bulb never reports the requested state
+    ///
+    pub fn ensure(&self, request: &LightRequest, attempts: u8) -> Result<LightStatus> {
+        request.validate()?;
+        if matches!(request.power(), Some(PowerMode::Reboot)) {
+            return Err(Error::UnconfirmableReboot);
+        }
+
+        let payload = Payload::from(request);
+        let attempts = attempts.max(1);
+        for attempt in 1..=attempts {
+            if payload.is_valid() {
+                self.set(&payload)?;
+            }
+            if let Some(power) = request.power() {
+                if payload.is_valid() {
+                    self.settle();
+                }
+                self.set_power(power)?;
+            }
+
+            self.settle();
+            let status = self.get_status()?;
+            if request.confirmed_by(&status) {
+                return Ok(status);
+            }
+            debug!(
+                "{} not confirmed, attempt {}/{}",
+                self.ip, attempt, attempts
+            );
+        }
+        Err(Error::unconfirmed(&self.ip, attempts))
+    }
+
+    /// Flash a color on this bulb, then restore its previous settings
+    ///
+    /// The bulb's status is read first, then it shows the color at full
+    /// brightness for `on_ms` and turns off for `off_ms`, `count` times.
+    /// The previous settings are restored even if a flash fails, see
+    /// [LightRequest::from] for what can be restored.
+    ///
+    /// NB: this blocks for the whole sequence, up to a count of
+    ///     [MAX_FLASH_COUNT] and [MAX_FLASH_MS] for each on & off time
+    ///
+    /// # Errors
+    ///   [Error::InvalidFlash] if the count or times are out of range
+    ///
+    pub fn flash(&self, color: &Color, count: u8, on_ms: u64, off_ms: u64) -> Result<()> {
+        if count == 0 || count > MAX_FLASH_COUNT {
+            return Err(Error::invalid_flash(&format!(
+                "count must be 1-{}",
+                MAX_FLASH_COUNT
+            )));
+        }
+        if on_ms > MAX_FLASH_MS || off_ms > MAX_FLASH_MS {
+            return Err(Error::invalid_flash(&format!(
+                "times must be at most {}ms",
+                MAX_FLASH_MS
+            )));
+        }
+
+        let prior = self.get_status()?;
+
+        let mut payload = Payload::from(color);
+        payload.brightness(&Brightness::new());
+
+        let mut res = Ok(());
+        for _ in 0..count {
+            res = self
+                .set(&payload)
+                .and_then(|_| {
+                    thread::sleep(Duration::from_millis(on_ms));
+                    self.set_power(&PowerMode::Off)
+                })
+                .map(|_| ());
+            if res.is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(off_ms));
+        }
+
+        let restored = self.sync_from(&prior).map(|_| ());
+        if let Err(e) = &restored {
+            warn!("Failed to restore {} after flashing: {}", self.ip, e);
+        }
+        res.and(restored)
+    }
+
+    /// Set this bulb to match a status, such as another bulb's
+    ///
+    /// The settings sent are rebuilt from the status with
+    /// [LightRequest::from], a source which isn't emitting turns this
+    /// bulb off
+    ///
+    /// Does not update self.status, you can pass the response back
+    /// into [Self::process_reply] if you want to update the internal state
+    ///
+    pub fn sync_from(&self, source: &LightStatus) -> Result<LightingResponse> {
+        let req = LightRequest::from(source);
+        let payload = Payload::from(&req);
+        match req.power() {
+            Some(PowerMode::On) if payload.is_valid() => self.set(&payload),
+            Some(power) => self.set_power(power),
+            None => self.set_power(&PowerMode::Off),
+        }
+    }
+
+    /// Change this bulb's brightness relative to its current brightness
+    ///
+    /// The current brightness is read from the last known status, or
+    /// fetched from the bulb if there's none. A bulb which doesn't report
+    /// a brightness is treated as fully bright. The result is clamped to
+    /// 10-100, see [Brightness::nudge].
+    ///
+    pub fn nudge_brightness(&self, delta: i8) -> Result<LightingResponse> {
+        let brightness = self.nudged_brightness(delta)?;
+        self.set(&Payload::from(&brightness))
+    }
+
+    /// Work out the brightness [Self::nudge_brightness] would set
+    ///
+    /// Reads the bulb's status if there's no last known brightness
+    ///
+    pub fn nudged_brightness(&self, delta: i8) -> Result<Brightness> {
+        let current = match self.status.as_ref().and_then(|s| s.brightness()) {
+            Some(brightness) => Some(brightness.clone()),
+            None => self.get_status()?.brightness().cloned(),
+        };
+        Ok(current.unwrap_or_else(Brightness::new).nudge(delta))
+    }
+
+    /// Dim this bulb, warming its temperature as it dims
+    ///
+    /// The brightness and the temperature from [WarmDimCurve::from_env]
+    /// are sent together in one [Payload]
+    ///
+    pub fn warm_dim(&self, brightness: &Brightness) -> Result<LightingResponse> {
+        self.warm_dim_with(brightness, &WarmDimCurve::from_env())
+    }
+
+    /// Dim this bulb along the given curve, see [Self::warm_dim]
+    pub fn warm_dim_with(
+        &self,
+        brightness: &Brightness,
+        curve: &WarmDimCurve,
+    ) -> Result<LightingResponse> {
+        let mut payload = Payload::from(brightness);
+        payload.temp(&curve.kelvin(brightness));
+        self.set(&payload)
+    }
+
+    /// Set new lighting settings on this bulb
+    ///
+    /// Does not update self.status, you can pass the response back
+    /// into [Self::process_reply] if you want to update the internal state
+    ///
+    pub fn set(&self, payload: &Payload) -> Result<LightingResponse> {
+        self.set_timed(payload).map(|(resp, _)| resp)
+    }
+
+    /// Set new lighting settings on this bulb, timing the bulb's reply
+    ///
+    /// Works the same as [Self::set], but also returns how long the bulb
+    /// took to reply, from sending the command to receiving its response.
+    /// Useful to diagnose slow bulbs.
+    ///
+    /// NB: some firmware ignores a brightness sent along with a scene.
+    ///     With `RIZ_SPLIT_SCENE_DIMMING=true` (env var) the scene is sent
+    ///     first, then the brightness, and the round trips are summed.
+    ///
+    /// # Returns
+    ///   ([LightingResponse], [Duration]) of the reply and round trip time
+    ///
+    pub fn set_timed(&self, payload: &Payload) -> Result<(LightingResponse, Duration)> {
+        payload.validate_semantics()?;
+        if Self::split_scene_dimming() {
+            if let Some((scene, dimming)) = payload.split_dimming() {
+                let (_, first) = self.send_pilot(&scene)?;
+                self.settle();
+                let (_, second) = self.send_pilot(&dimming)?;
+                let resp = LightingResponse::payload(self.ip, payload.clone());
+                return Ok((resp, first + second));
+            }
+        }
+        self.send_pilot(payload)
+    }
+
+    /// Build the `setPilot` message for this bulb's protocol
+    fn pilot_message(&self, params: Value) -> Value {
+        let protocol = self.protocol();
+        protocol.message(protocol.set_pilot(), Some(params))
+    }
+
+    /// Check if scenes with a brightness are sent as two commands
+    ///
+    /// Newer firmware handles the combined command fine, so this is off
+    /// unless `RIZ_SPLIT_SCENE_DIMMING=true` (env var)
+    ///
+    fn split_scene_dimming() -> bool {
+        env::var(SPLIT_SCENE_DIMMING_ENV_KEY).map_or(false, |v| v == "true")
+    }
+
+    /// Send the payload as a single `setPilot` command
+    fn send_pilot(&self, payload: &Payload) -> Result<(LightingResponse, Duration)> {
+        if payload.is_valid() {
+            match serde_json::to_value(payload) {
+                Ok(params) => match self.udp_response_timed(&self.pilot_message(params)) {
+                    Ok((v, elapsed)) => {
+                        debug!("udp response: {:?}", v);
+                        let resp = LightingResponse::payload(self.ip, payload.clone());
+                        Ok((resp, elapsed))
+                    }
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(Error::JsonDump(e)),
+            }
+        } else {
+            Err(Error::NoAttribute)
+        }
+    }
+
+    /// Set the same lighting settings on many bulbs as close together as possible
+    ///
+    /// All sockets are opened before any command is sent, then the commands
+    /// are sent back to back from a single thread before waiting on replies.
+    /// This keeps scene animations roughly in phase across the bulbs.
+    ///
+    /// NB: this is best effort only. Each bulb still receives its own UDP
+    ///     datagram, so wifi jitter and each bulb's processing time can leave
+    ///     them some tens of milliseconds apart. Bulbs which were already
+    ///     playing the scene will not restart it.
+    ///
+    /// # Returns
+    ///   [Vec] of [Result] of [LightingResponse], in the same order as lights
+    ///
+    /// # Errors
+    ///   [Error::NoAttribute] if the payload is not valid
+    ///
+    pub fn set_synchronized(
+        lights: &[Light],
+        payload: &Payload,
+    ) -> Result<Vec<Result<LightingResponse>>> {
+        if !payload.is_valid() {
+            return Err(Error::NoAttribute);
+        }
+
+        let params = match serde_json::to_value(payload) {
+            Ok(params) => params,
+            Err(e) => return Err(Error::JsonDump(e)),
+        };
+
+        // every IP of a multi-bulb fixture is sent the command too
+        let (owners, emitters): (Vec<usize>, Vec<Light>) = lights
+            .iter()
+            .enumerate()
+            .flat_map(|(i, light)| light.emitters().into_iter().map(move |e| (i, e)))
+            .unzip();
+
+        // each light may have its own protocol, so its own message
+        let msgs: Vec<Value> = emitters
+            .iter()
+            .map(|light| light.with_request_id(&light.pilot_message(params.clone())))
+            .collect();
+
+        // prepare every socket first, so sending is as tight as possible
+        let sockets: Vec<Result<UdpSocket>> =
+            emitters.iter().map(|light| light.connect()).collect();
+
+        let sent: Vec<Result<UdpSocket>> = sockets
+            .into_iter()
+            .zip(&msgs)
+            .map(|(socket, msg)| {
+                socket.and_then(|socket| Self::send(&socket, &msg.to_string()).map(|_| socket))
+            })
+            .collect();
+
+        let replies = emitters
+            .iter()
+            .zip(&msgs)
+            .zip(sent)
+            .map(|((light, msg), socket)| {
+                let v = light.receive_reply(&socket?, msg)?;
+                debug!("udp response: {:?}", v);
+                if !Self::is_wiz_reply(msg, &v) {
+                    return Err(Error::NotAWizBulb(light.ip));
+                }
+                Ok(())
+            });
+
+        // a fixture only succeeds if all of its IPs replied
+        let mut results: Vec<Result<LightingResponse>> = lights
+            .iter()
+            .map(|light| Ok(LightingResponse::payload(light.ip, payload.clone())))
+            .collect();
+        for (i, reply) in owners.into_iter().zip(replies) {
+            if let Err(e) = reply {
+                if results[i].is_ok() {
+                    results[i] = Err(e);
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Pause between consecutive commands sent to this bulb
+    ///
+    /// Some (older firmware) bulbs drop commands sent in quick succession.
+    /// The delay is set in milliseconds with `RIZ_COMMAND_DELAY_MS` (env var),
+    /// by default there is no delay.
+    ///
+    pub fn settle(&self) {
+        let delay = env::var(COMMAND_DELAY_ENV_KEY)
+            .ok()
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .unwrap_or(0);
+
+        if delay > 0 {
+            debug!("settling {} for {}ms", self.ip, delay);
+            thread::sleep(Duration::from_millis(delay));
+        }
+    }
+
+    /// Set the [PowerMode] for the light
+    ///
+    /// Works in the same fashion as [Self::set], where the action does not
+    /// mutate internal state. You can pass the response from this method
+    /// to [Self::process_reply] if you want to update this bulb's status
+    ///
+    /// NB: a reboot succeeds once the command is sent, even if the bulb
+    ///     never replies, as rebooting bulbs often don't
+    ///
+    pub fn set_power(&self, power: &PowerMode) -> Result<LightingResponse> {
+        match power {
+            PowerMode::On => self.toggle_power(true),
+            PowerMode::Off => self.toggle_power(false),
+            PowerMode::Reboot => self.power_cycle(),
+        }
+    }
+
+    fn toggle_power(&self, powered: bool) -> Result<LightingResponse> {
+        let protocol = self.protocol();
+        let params = json!({ "state": powered });
+        self.udp_response(&protocol.message(protocol.set_state(), Some(params)))?;
+        Ok(if powered {
+            LightingResponse::power(self.ip, PowerMode::On)
+        } else {
+            LightingResponse::power(self.ip, PowerMode::Off)
+        })
+    }
+
+    /// Send the reboot command, without needing a reply
+    ///
+    /// Rebooting bulbs can drop off the network before replying, and some
+    /// devices never reply to a reboot at all, so once the command is sent
+    /// any failure to read the reply is logged rather than returned. Only
+    /// failing to connect or send is an error. The command isn't resent.
+    ///
+    fn power_cycle(&self) -> Result<LightingResponse> {
+        for alias in &self.aliases {
+            self.at(*alias).power_cycle()?;
+        }
+
+        let protocol = self.protocol();
+        let msg = self.with_request_id(&protocol.message(protocol.reboot(), None));
+        let raw = serde_json::to_string(&msg).map_err(Error::JsonDump)?;
+
+        let socket = self.connect()?;
+        Self::send(&socket, &raw)?;
+        match self.receive_reply(&socket, &msg) {
+            Ok(_) => {}
+            Err(e) if e.is_timeout() => info!("{} sent reboot, no reply", self.ip),
+            Err(e) => warn!("{} sent reboot, unreadable reply: {}", self.ip, e),
+        }
+        Ok(LightingResponse::power(self.ip, PowerMode::Reboot))
+    }
+
+    /// Update this light's non-lighting attributes (name, ip, timeouts, etc)
+    fn update(&mut self, other: &Self) -> bool {
+        let mut any_update = false;
+        if self.name != other.name {
+            self.name.clone_from(&other.name);
+            any_update = true;
+        }
+
+        if self.nickname != other.nickname {
+            self.nickname.clone_from(&other.nickname);
+            any_update = true;
+        }
+
+        if self.timeout_ms != other.timeout_ms {
+            self.timeout_ms = other.timeout_ms;
+            any_update = true;
+        }
+
+        if self.retries != other.retries {
+            self.retries = other.retries;
+            any_update = true;
+        }
+
+        if self.protocol != other.protocol {
+            self.protocol.clone_from(&other.protocol);
+            any_update = true;
+        }
+
+        if self.enabled != other.enabled {
+            self.enabled = other.enabled;
+            any_update = true;
+        }
 
         if self.ip != other.ip {
             self.ip = other.ip;
+            // a new address may well be a different bulb, probe it again
+            self.capabilities = None;
+            any_update = true;
+        }
+
+        if self.aliases != other.aliases {
+            self.aliases.clone_from(&other.aliases);
             any_update = true;
         }
 
@@ -422,21 +1443,34 @@ impl Light {
     }
 
     /// Update the internal state with the response of some command
+    ///
+    /// # Returns
+    ///   [bool] of if the response was for this light and changed its state
+    ///
     pub fn process_reply(&mut self, resp: &LightingResponse) -> bool {
-        if resp.ip == self.ip {
-            match &resp.response {
-                LightingResponseType::Payload(payload) => self.update_status_from_payload(payload),
-                LightingResponseType::Power(power) => self.update_status_from_power(power),
-                LightingResponseType::Status(status) => self.update_status(status),
+        if !self.has_ip(resp.ip) {
+            return false;
+        }
+
+        let status = self.status.clone();
+        let capabilities = self.capabilities.clone();
+        match &resp.response {
+            LightingResponseType::Payload(payload) => self.update_status_from_payload(payload),
+            LightingResponseType::Power(power) => self.update_status_from_power(power),
+            LightingResponseType::Status(status) => self.update_status(status),
+            LightingResponseType::Config(config) => {
+                self.capabilities = Some(BulbCapabilities::from(config))
             }
-            true
-        } else {
-            false
         }
+        self.status != status || self.capabilities != capabilities
     }
 
     fn update_status(&mut self, status: &LightStatus) {
         if let Some(known) = &mut self.status {
+            if known.diverges(status) {
+                info!("{} was changed outside of Riz", self.ip);
+                known.drift = true;
+            }
             known.update(status);
         } else {
             self.status = Some(status.clone());
@@ -460,70 +1494,702 @@ impl Light {
     }
 
     fn udp_response(&self, msg: &Value) -> Result<Value> {
-        // dump the control message to string
-        let msg = match serde_json::to_string(&msg) {
-            Ok(v) => v,
-            Err(e) => return Err(Error::JsonDump(e)),
-        };
+        self.udp_response_timed(msg).map(|(v, _)| v)
+    }
 
-        // get some udp socket from the os
-        let socket = match UdpSocket::bind("0.0.0.0:0") {
-            Ok(s) => s,
-            Err(e) => return Err(Error::socket("bind", e)),
-        };
+    /// Send the message and wait for the reply, timing the round trip
+    ///
+    /// A fixture with aliases is sent the message at all of its IPs at
+    /// once, see [Self::fan_out]
+    ///
+    fn udp_response_timed(&self, msg: &Value) -> Result<(Value, Duration)> {
+        if !self.aliases.is_empty() {
+            return self.fan_out(msg);
+        }
 
-        // set a 1 second read and write timeout
-        match socket.set_write_timeout(Some(Duration::new(1, 0))) {
-            Ok(_) => {}
-            Err(e) => return Err(Error::socket("set_write_timeout", e)),
-        };
+        let mut sent = String::new();
+        let res = self.exchange(msg, &mut sent);
+        if Self::debug_exchanges() {
+            self.record_exchange(sent, &res);
+        }
+        res
+    }
 
-        match socket.set_read_timeout(Some(Duration::new(1, 0))) {
-            Ok(_) => {}
-            Err(e) => return Err(Error::socket("set_read_timeout", e)),
-        };
+    /// Send the message to every IP of this fixture at once
+    ///
+    /// # Returns
+    ///   the reply from [Self::ip], with the slowest round trip, or the
+    ///   first error from any of the IPs
+    ///
+    fn fan_out(&self, msg: &Value) -> Result<(Value, Duration)> {
+        let emitters = self.emitters();
+        let emitters: Vec<&Light> = emitters.iter().collect();
+        let replies = concurrently(&emitters, emitters.len(), |light| {
+            light.udp_response_timed(msg)
+        });
+
+        let mut primary = None;
+        let mut slowest = Duration::ZERO;
+        for reply in replies {
+            let (reply, elapsed) = reply?;
+            slowest = slowest.max(elapsed);
+            primary.get_or_insert(reply);
+        }
+        primary.map(|reply| (reply, slowest)).ok_or(Error::NoReply)
+    }
 
-        // connect to the remote bulb at their standard port
-        match socket.connect(format!("{}:38899", self.ip)) {
-            Ok(_) => {}
-            Err(e) => return Err(Error::socket("connect", e)),
+    /// Send the message and wait for the reply, resending on timeouts
+    ///
+    /// Every attempt is sent from the same socket with its own request
+    /// `id`, so a late reply to an earlier attempt is dropped rather than
+    /// taken as the reply to the resend. The last message sent is kept
+    /// in `sent`.
+    ///
+    fn exchange(&self, msg: &Value, sent: &mut String) -> Result<(Value, Duration)> {
+        let socket = self.connect()?;
+        let retries = self.retries();
+        let mut attempt = 0;
+        loop {
+            // dump the control message to string
+            let msg = self.with_request_id(msg);
+            *sent = serde_json::to_string(&msg).map_err(Error::JsonDump)?;
+
+            let start = Instant::now();
+            Self::send(&socket, sent)?;
+            match self.receive_reply(&socket, &msg) {
+                Ok(reply) => {
+                    let elapsed = start.elapsed();
+                    debug!("{} replied in {}ms", self.ip, elapsed.as_millis());
+                    if !Self::is_wiz_reply(&msg, &reply) {
+                        return Err(Error::NotAWizBulb(self.ip));
+                    }
+                    return Ok((reply, elapsed));
+                }
+                Err(e) if e.is_timeout() && attempt < retries => {
+                    attempt += 1;
+                    debug!("{} timed out, resending ({}/{})", self.ip, attempt, retries);
+                }
+                Err(e) => return Err(e),
+            }
         }
+    }
 
-        // send the control message
-        match socket.send(msg.as_bytes()) {
-            Ok(_) => {}
-            Err(e) => return Err(Error::socket("send", e)),
-        };
+    /// Check if each bulb's last exchange is kept, see [Self::last_exchange]
+    fn debug_exchanges() -> bool {
+        env::var(DEBUG_EXCHANGES_ENV_KEY).map_or(false, |v| v == "true")
+    }
 
-        // declare a buffer of the max message size
-        let mut buffer = [0; 4096];
-        let bytes = match socket.recv(&mut buffer) {
-            Ok(b) => b,
-            Err(e) => return Err(Error::socket("receive", e)),
+    /// Keep the exchange as this bulb's last, see [Self::last_exchange]
+    fn record_exchange(&self, sent: String, res: &Result<(Value, Duration)>) {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let exchange = match res {
+            Ok((reply, elapsed)) => UdpExchange {
+                sent,
+                received: Some(reply.to_string()),
+                error: None,
+                elapsed_ms: Some(elapsed.as_millis() as u64),
+                at,
+            },
+            Err(e) => UdpExchange {
+                sent,
+                received: None,
+                error: Some(e.to_string()),
+                elapsed_ms: None,
+                at,
+            },
         };
+        lock(&EXCHANGES).insert(self.ip, exchange);
+    }
 
-        // Redeclare `buffer` as String of the received bytes
-        let buffer = match String::from_utf8(buffer[..bytes].to_vec()) {
-            Ok(s) => s,
-            Err(e) => return Err(Error::Utf8Decode(e)),
-        };
+    /// The last message sent to this bulb and its reply
+    ///
+    /// Only kept with `RIZ_DEBUG_EXCHANGES=true` (env var), for diagnosing
+    /// firmware quirks. Exchanges are kept in memory by IP, for the life
+    /// of the process, so they're shared by all copies of this light.
+    ///
+    pub fn last_exchange(&self) -> Option<UdpExchange> {
+        lock(&EXCHANGES).get(&self.ip).cloned()
+    }
 
-        // create some JSON object from the string
-        match serde_json::from_str(&buffer) {
-            Ok(v) => Ok(v),
-            Err(e) => Err(Error::JsonLoad(e)),
+    /// Add the next request `id` to the message, if this bulb's protocol
+    /// uses them
+    fn with_request_id(&self, msg: &Value) -> Value {
+        let mut msg = msg.clone();
+        if self.protocol().request_ids() {
+            if let Some(fields) = msg.as_object_mut() {
+                let id = REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+                fields.insert("id".to_string(), json!(id));
+            }
         }
+        msg
     }
-}
 
-/// Brightness can be applied in any context, values from 10 to 100
-#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema)]
-pub struct Brightness {
-    #[schema(minimum = 10, maximum = 100)]
+    /// Read the bulb's reply to the message, dropping stale replies
+    ///
+    /// A reply echoing a different `id` is late, from an earlier command
+    /// or an earlier attempt at this one (see [Self::exchange]), so it's
+    /// dropped and the next reply read. Replies without an `id` are
+    /// always accepted.
+    ///
+    fn receive_reply(&self, socket: &UdpSocket, msg: &Value) -> Result<Value> {
+        loop {
+            let reply = Self::receive(socket)?;
+            if !Self::is_stale(msg, &reply) {
+                return Ok(reply);
+            }
+            debug!("{} dropping stale reply: {}", self.ip, reply);
+        }
+    }
+
+    /// Check if the reply echoes a different `id` than the message's
+    fn is_stale(msg: &Value, reply: &Value) -> bool {
+        match (msg.get("id"), reply.get("id")) {
+            (Some(sent), Some(echoed)) => sent != echoed,
+            _ => false,
+        }
+    }
+
+    /// Check a reply looks like it came from a Wiz bulb
+    ///
+    /// Bulbs echo the method they were sent, and include their mac in
+    /// most results. Either is enough, anything else is some other service.
+    ///
+    fn is_wiz_reply(msg: &Value, reply: &Value) -> bool {
+        let echoed = reply.get("method").is_some() && reply.get("method") == msg.get("method");
+        let mac = reply
+            .get("result")
+            .and_then(|result| result.get("mac"))
+            .map_or(false, Value::is_string);
+        echoed || mac
+    }
+
+    /// Broadcast a status request to find bulbs on the local network
+    ///
+    /// Waits up to `timeout` for replies, or returns as soon as the
+    /// `expected` number of distinct bulbs (by mac) have replied. If fewer
+    /// than expected reply, the full timeout is used.
+    ///
+    /// # Returns
+    ///   [Vec] of [DiscoveredLight], sorted by IP
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use riz::models::Light;
+    ///
+    /// for found in Light::discover(Duration::from_secs(2), Some(3)).unwrap() {
+    ///     println!("{} ({})", found.ip(), found.mac());
+    /// }
+    /// ```
+    ///
+    pub fn discover(timeout: Duration, expected: Option<usize>) -> Result<Vec<DiscoveredLight>> {
+        let socket = match UdpSocket::bind("0.0.0.0:0") {
+            Ok(s) => s,
+            Err(e) => return Err(Error::socket("bind", e)),
+        };
+
+        if let Err(e) = socket.set_broadcast(true) {
+            return Err(Error::socket("set_broadcast", e));
+        }
+
+        let protocol = WizProtocol::default();
+        let msg = protocol
+            .message(protocol.get_pilot(), Some(json!({})))
+            .to_string();
+        if let Err(e) = socket.send_to(msg.as_bytes(), (Ipv4Addr::BROADCAST, BULB_PORT)) {
+            return Err(Error::socket("send_to", e));
+        }
+
+        let deadline = Instant::now() + timeout;
+        let mut found: BTreeMap<String, Ipv4Addr> = BTreeMap::new();
+        let mut buffer = [0; 4096];
+
+        while expected.map_or(true, |expected| found.len() < expected) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+
+            if let Err(e) = socket.set_read_timeout(Some(remaining)) {
+                return Err(Error::socket("set_read_timeout", e));
+            }
+
+            let (bytes, addr) = match socket.recv_from(&mut buffer) {
+                Ok(v) => v,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => break,
+                Err(e) => return Err(Error::socket("receive", e)),
+            };
+
+            let ip = match addr.ip() {
+                IpAddr::V4(ip) => ip,
+                IpAddr::V6(_) => continue,
+            };
+
+            let reply: Value = match serde_json::from_slice(&buffer[..bytes]) {
+                Ok(v) => v,
+                Err(e) => {
+                    debug!("Ignoring invalid discovery reply from {}: {}", ip, e);
+                    continue;
+                }
+            };
+
+            match reply["result"]["mac"].as_str() {
+                Some(mac) => {
+                    found.insert(mac.to_string(), ip);
+                }
+                None => debug!("Ignoring discovery reply without mac from {}", ip),
+            }
+        }
+
+        let mut lights: Vec<_> = found
+            .into_iter()
+            .map(|(mac, ip)| DiscoveredLight { ip, mac })
+            .collect();
+        lights.sort_by_key(|light| light.ip);
+        Ok(lights)
+    }
+
+    /// Open a UDP socket connected to this bulb
+    fn connect(&self) -> Result<UdpSocket> {
+        // get some udp socket from the os
+        let socket = match UdpSocket::bind("0.0.0.0:0") {
+            Ok(s) => s,
+            Err(e) => return Err(Error::socket("bind", e)),
+        };
+
+        // set the read and write timeout, see Self::timeout
+        let timeout = self.timeout();
+        match socket.set_write_timeout(Some(timeout)) {
+            Ok(_) => {}
+            Err(e) => return Err(Error::socket("set_write_timeout", e)),
+        };
+
+        match socket.set_read_timeout(Some(timeout)) {
+            Ok(_) => {}
+            Err(e) => return Err(Error::socket("set_read_timeout", e)),
+        };
+
+        // connect to the remote bulb at their standard port
+        match socket.connect((self.ip, BULB_PORT)) {
+            Ok(_) => Ok(socket),
+            Err(e) => Err(Error::socket("connect", e)),
+        }
+    }
+
+    /// Send the control message on the connected socket
+    fn send(socket: &UdpSocket, msg: &str) -> Result<()> {
+        match socket.send(msg.as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::socket("send", e)),
+        }
+    }
+
+    /// Decode the bytes of a bulb's reply as a UTF-8 string
+    ///
+    /// Some firmware pads its replies, so anything after the last `}` is
+    /// dropped and any other invalid bytes are replaced, with a warning.
+    /// Set `RIZ_STRICT_UTF8=true` (env var) to refuse these replies.
+    ///
+    /// # Errors
+    ///   [Error::Utf8Decode] for invalid bytes, only when strict
+    ///
+    fn decode(bytes: &[u8]) -> Result<String> {
+        if env::var(STRICT_UTF8_ENV_KEY).map_or(false, |v| v == "true") {
+            return String::from_utf8(bytes.to_vec()).map_err(Error::Utf8Decode);
+        }
+
+        let end = bytes
+            .iter()
+            .rposition(|b| *b == b'}')
+            .map_or(bytes.len(), |i| i + 1);
+        if end < bytes.len() {
+            debug!("Dropping {} bytes after the reply", bytes.len() - end);
+        }
+
+        match String::from_utf8_lossy(&bytes[..end]) {
+            Cow::Borrowed(s) => Ok(s.to_string()),
+            Cow::Owned(s) => {
+                warn!("Replaced invalid UTF-8 in a bulb reply: {}", s);
+                Ok(s)
+            }
+        }
+    }
+
+    /// Read the bulb's JSON reply from the connected socket
+    fn receive(socket: &UdpSocket) -> Result<Value> {
+        // declare a buffer of the max message size
+        let mut buffer = [0; 4096];
+        let bytes = match socket.recv(&mut buffer) {
+            Ok(b) => b,
+            Err(e) => return Err(Error::socket("receive", e)),
+        };
+
+        // Redeclare `buffer` as String of the received bytes
+        let buffer = Self::decode(&buffer[..bytes])?;
+
+        // create some JSON object from the string
+        match serde_json::from_str(&buffer) {
+            Ok(v) => Ok(v),
+            Err(e) => Err(Error::JsonLoad(e)),
+        }
+    }
+}
+
+/// The last message sent to a bulb and its reply, see [Light::last_exchange]
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct UdpExchange {
+    /// Message sent to the bulb, as JSON
+    sent: String,
+
+    /// Reply from the bulb, as JSON, if it replied
+    received: Option<String>,
+
+    /// Why the exchange failed, if it did
+    error: Option<String>,
+
+    /// Round trip time, if the bulb replied
+    elapsed_ms: Option<u64>,
+
+    /// When the exchange happened, in unix seconds
+    at: u64,
+}
+
+impl UdpExchange {
+    /// Accessor for the message sent to the bulb
+    pub fn sent(&self) -> &str {
+        &self.sent
+    }
+
+    /// Accessor for the bulb's reply, if it replied
+    pub fn received(&self) -> Option<&str> {
+        self.received.as_deref()
+    }
+
+    /// Accessor for why the exchange failed, if it did
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// A bulb which replied to [Light::discover]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct DiscoveredLight {
+    /// IPv4 address the bulb replied from
+    #[schema(value_type = String, example = "192.168.1.50")]
+    ip: Ipv4Addr,
+
+    /// Bulb wifi mac address
+    mac: String,
+}
+
+impl DiscoveredLight {
+    /// Accessor for the bulb's IP address
+    pub fn ip(&self) -> Ipv4Addr {
+        self.ip
+    }
+
+    /// Accessor for the bulb's mac address
+    pub fn mac(&self) -> &str {
+        &self.mac
+    }
+}
+
+/// Method names and envelope of the JSON messages sent to bulbs
+///
+/// Defaults to the standard Wiz protocol. Compatible third-party firmware
+/// may name the methods differently, or want `params` in every message.
+/// Set a light's `protocol` to override any of these.
+///
+/// # Examples
+///
+/// ```
+/// use riz::models::WizProtocol;
+///
+/// let protocol = WizProtocol::default();
+/// assert_eq!(protocol.get_pilot(), WizProtocol::GET_PILOT);
+/// assert_eq!(
+///     protocol.message(protocol.get_pilot(), None).to_string(),
+///     r#"{"method":"getPilot"}"#,
+/// );
+///
+/// let custom: WizProtocol = serde_json::from_str(
+///     r#"{"get_pilot": "GetPilot", "always_params": true}"#
+/// ).unwrap();
+/// assert_eq!(
+///     custom.message(custom.get_pilot(), None).to_string(),
+///     r#"{"method":"GetPilot","params":{}}"#,
+/// );
+/// assert_eq!(custom.set_pilot(), WizProtocol::SET_PILOT);
+/// ```
+///
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+#[serde(default)]
+pub struct WizProtocol {
+    /// Method to read the bulb's status
+    #[schema(example = "getPilot")]
+    get_pilot: String,
+
+    /// Method to set the bulb's lighting
+    #[schema(example = "setPilot")]
+    set_pilot: String,
+
+    /// Method to turn the bulb on or off
+    #[schema(example = "setState")]
+    set_state: String,
+
+    /// Method to reboot the bulb
+    #[schema(example = "reboot")]
+    reboot: String,
+
+    /// Method to read the bulb's system configuration
+    #[schema(example = "getSystemConfig")]
+    get_system_config: String,
+
+    /// Include `params` in every message, empty if there are none
+    always_params: bool,
+
+    /// Send an incrementing `id` with each message, to match up replies
+    request_ids: bool,
+}
+
+impl WizProtocol {
+    /// Standard method to read the bulb's status
+    pub const GET_PILOT: &'static str = "getPilot";
+
+    /// Standard method to set the bulb's lighting
+    pub const SET_PILOT: &'static str = "setPilot";
+
+    /// Standard method to turn the bulb on or off
+    pub const SET_STATE: &'static str = "setState";
+
+    /// Standard method to reboot the bulb
+    pub const REBOOT: &'static str = "reboot";
+
+    /// Standard method to read the bulb's system configuration
+    pub const GET_SYSTEM_CONFIG: &'static str = "getSystemConfig";
+
+    /// Method to ask the bulb to push its status, see [Light::register]
+    pub const REGISTRATION: &'static str = "registration";
+
+    /// Method of the status messages pushed by registered bulbs
+    pub const SYNC_PILOT: &'static str = "syncPilot";
+
+    /// Accessor for the method to read the bulb's status
+    pub fn get_pilot(&self) -> &str {
+        &self.get_pilot
+    }
+
+    /// Accessor for the method to set the bulb's lighting
+    pub fn set_pilot(&self) -> &str {
+        &self.set_pilot
+    }
+
+    /// Accessor for the method to turn the bulb on or off
+    pub fn set_state(&self) -> &str {
+        &self.set_state
+    }
+
+    /// Accessor for the method to reboot the bulb
+    pub fn reboot(&self) -> &str {
+        &self.reboot
+    }
+
+    /// Accessor for the method to read the bulb's system configuration
+    pub fn get_system_config(&self) -> &str {
+        &self.get_system_config
+    }
+
+    /// Check if an `id` is sent with each message, see [Self::message]
+    pub fn request_ids(&self) -> bool {
+        self.request_ids
+    }
+
+    /// Build the message to send for the method, with optional params
+    ///
+    /// NB: the `id` is added when sending, it's unique to each message
+    pub fn message(&self, method: &str, params: Option<Value>) -> Value {
+        match params {
+            Some(params) => json!({"method": method, "params": params}),
+            None if self.always_params => json!({"method": method, "params": {}}),
+            None => json!({ "method": method }),
+        }
+    }
+}
+
+impl Default for WizProtocol {
+    fn default() -> Self {
+        WizProtocol {
+            get_pilot: Self::GET_PILOT.to_string(),
+            set_pilot: Self::SET_PILOT.to_string(),
+            set_state: Self::SET_STATE.to_string(),
+            reboot: Self::REBOOT.to_string(),
+            get_system_config: Self::GET_SYSTEM_CONFIG.to_string(),
+            always_params: false,
+            request_ids: true,
+        }
+    }
+}
+
+/// Selects lights across all rooms by their details and last known status
+///
+/// Lights must match every filter which is set, an empty filter matches
+/// all lights
+///
+/// # Examples
+///
+/// ```
+/// use std::net::Ipv4Addr;
+/// use riz::models::{Light, LightFilter};
+/// use uuid::Uuid;
+///
+/// let filter: LightFilter = serde_json::from_str(r#"{"scene": "Cozy"}"#).unwrap();
+/// let light = Light::new(Ipv4Addr::new(10, 1, 2, 3), None);
+/// assert!(!filter.matches(&Uuid::new_v4(), &light));
+/// assert!(LightFilter::default().matches(&Uuid::new_v4(), &light));
+/// ```
+///
+#[serde_with::skip_serializing_none]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct LightFilter {
+    /// Only lights in this room
+    room: Option<Uuid>,
+
+    /// Only lights last known to be playing this scene
+    scene: Option<SceneMode>,
+
+    /// Only lights last known to be on (true) or off (false)
+    on: Option<bool>,
+}
+
+impl LightFilter {
+    /// Check if the light, in the room, matches this filter
+    ///
+    /// Disabled lights never match, see [Light::enabled]
+    ///
+    pub fn matches(&self, room_id: &Uuid, light: &Light) -> bool {
+        if !light.enabled || self.room.map_or(false, |room| room != *room_id) {
+            return false;
+        }
+
+        let status = light.status();
+        if let Some(scene) = &self.scene {
+            if status.and_then(|s| s.scene()) != Some(scene) {
+                return false;
+            }
+        }
+
+        match self.on {
+            Some(on) => status.map_or(false, |s| s.emitting() == on),
+            None => true,
+        }
+    }
+}
+
+/// A room's lighting settings, without any IPs or IDs, to share its look
+///
+/// Exported with [Room::preset], and applied to another room's lights by
+/// position with [Self::requests]
+///
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct RoomPreset {
+    /// Name of the room the preset was exported from
+    name: String,
+
+    /// Settings for each light by position, see [Room::ordered_lights],
+    /// null for lights without a known status
+    lights: Vec<Option<LightRequest>>,
+}
+
+impl RoomPreset {
+    /// Accessor for the name of the room this was exported from
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Accessor for each light's settings, by position
+    pub fn lights(&self) -> &[Option<LightRequest>] {
+        &self.lights
+    }
+
+    /// Pair the room's lights with this preset's settings, by position
+    ///
+    /// Lights past the end of the preset, or in a position without
+    /// settings, are left out
+    ///
+    /// # Returns
+    ///   [Vec] of the light ID, [Light] and its [LightRequest]
+    ///
+    pub fn requests<'a>(&'a self, room: &'a Room) -> Vec<(&'a Uuid, &'a Light, &'a LightRequest)> {
+        room.ordered_lights()
+            .into_iter()
+            .zip(&self.lights)
+            .filter_map(|((id, light), req)| req.as_ref().map(|req| (id, light, req)))
+            .collect()
+    }
+}
+
+/// A light in a room, by their IDs
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct LightRef {
+    /// Room ID
+    room_id: Uuid,
+
+    /// Light ID
+    light_id: Uuid,
+}
+
+impl LightRef {
+    /// Create a new reference to the light in the room
+    pub fn new(room_id: &Uuid, light_id: &Uuid) -> Self {
+        LightRef {
+            room_id: *room_id,
+            light_id: *light_id,
+        }
+    }
+
+    /// Accessor for the room ID
+    pub fn room_id(&self) -> &Uuid {
+        &self.room_id
+    }
+
+    /// Accessor for the light ID
+    pub fn light_id(&self) -> &Uuid {
+        &self.light_id
+    }
+}
+
+/// How to tidy up a light which no longer replies, see [Light::prune]
+///
+/// Lights are never deleted by pruning, either change can be undone
+///
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneAction {
+    /// Disable the light, so room-wide commands skip it
+    Disable,
+
+    /// Forget the light's last known status
+    ClearStatus,
+}
+
+/// Brightness can be applied in any context, values from 10 to 100
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Eq)]
+pub struct Brightness {
+    #[schema(minimum = 10, maximum = 100)]
     value: u8,
 }
 
 impl Brightness {
+    /// Lowest valid brightness percent
+    pub const MIN: u8 = 10;
+
+    /// Highest valid brightness percent
+    pub const MAX: u8 = 100;
+
     /// Create a new Brightness with the default value
     ///
     /// # Examples
@@ -587,360 +2253,1701 @@ impl Brightness {
         }
     }
 
+    /// Create a new Brightness relative to this one, clamped to [Self::MIN]-[Self::MAX]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Brightness;
+    ///
+    /// let brightness = Brightness::create(50).unwrap();
+    /// assert_eq!(brightness.nudge(10).value(), 60);
+    /// assert_eq!(brightness.nudge(-10).value(), 40);
+    /// assert_eq!(brightness.nudge(70).value(), 100);
+    /// assert_eq!(brightness.nudge(-128).value(), 10);
+    /// ```
+    ///
+    pub fn nudge(&self, delta: i8) -> Self {
+        let value = (i16::from(self.value) + i16::from(delta))
+            .clamp(i16::from(Self::MIN), i16::from(Self::MAX));
+        Brightness { value: value as u8 }
+    }
+
     /// Check if the value is within the valid range
     fn valid(value: u8) -> bool {
-        (10..=100).contains(&value)
+        (Self::MIN..=Self::MAX).contains(&value)
     }
 }
 
-/// Speed can be applied to select scenes only, values from 20 to 200
-#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema)]
-pub struct Speed {
-    #[schema(minimum = 20, maximum = 200)]
-    value: u8,
-}
+/// Speed can be applied to select scenes only, values from 20 to 200
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Eq)]
+pub struct Speed {
+    #[schema(minimum = 20, maximum = 200)]
+    value: u8,
+}
+
+impl Speed {
+    /// Lowest valid speed
+    pub const MIN: u8 = 20;
+
+    /// Highest valid speed
+    pub const MAX: u8 = 200;
+
+    /// Create a new speed setting with the default value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Speed;
+    ///
+    /// assert_eq!(Speed::new().value(), 100);
+    /// ```
+    ///
+    pub fn new() -> Self {
+        Speed { value: 100 }
+    }
+
+    /// Accessor for our read-only value
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    /// Create a new speed setting with the given value
+    ///
+    /// # Returns
+    ///   [Speed] when value is within the valid range
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Speed;
+    ///
+    /// assert!(Speed::create(19).is_none());
+    /// assert!(Speed::create(20).is_some());
+    /// assert!(Speed::create(200).is_some());
+    /// assert!(Speed::create(201).is_none());
+    /// ```
+    ///
+    pub fn create(value: u8) -> Option<Self> {
+        if Self::valid(value) {
+            Some(Speed { value })
+        } else {
+            None
+        }
+    }
+
+    /// Create a new speed setting with the given value if within
+    /// the valid range, otherwise the default value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Speed;
+    ///
+    /// assert_eq!(Speed::create_or(19).value(), 100);
+    /// assert_eq!(Speed::create_or(20).value(), 20);
+    /// assert_eq!(Speed::create_or(200).value(), 200);
+    /// assert_eq!(Speed::create_or(201).value(), 100);
+    /// ```
+    ///
+    pub fn create_or(value: u8) -> Self {
+        Speed {
+            value: if Self::valid(value) { value } else { 100 },
+        }
+    }
+
+    fn valid(value: u8) -> bool {
+        (Self::MIN..=Self::MAX).contains(&value)
+    }
+}
+
+/// A scene with its speed, which is only meaningful with a scene
+///
+/// Converting to a [Payload] always sets both, using the default [Speed]
+/// when none is given, so a speed is never sent without its scene
+///
+/// # Examples
+///
+/// ```
+/// use riz::models::{DynamicScene, Payload, SceneMode, Speed};
+///
+/// let scene = DynamicScene::new(SceneMode::Cozy, Speed::create(150));
+/// let payload = serde_json::to_value(Payload::from(&scene)).unwrap();
+/// assert_eq!(payload["sceneId"], 6);
+/// assert_eq!(payload["speed"], 150);
+///
+/// let scene = DynamicScene::new(SceneMode::Party, None);
+/// let payload = serde_json::to_value(Payload::from(&scene)).unwrap();
+/// assert_eq!(payload["speed"], 100);
+/// ```
+///
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Eq)]
+pub struct DynamicScene {
+    /// Scene to play
+    scene: SceneMode,
+
+    /// Speed to play it at, or the default speed
+    speed: Option<Speed>,
+}
+
+impl DynamicScene {
+    /// Create a new scene with an optional speed
+    pub fn new(scene: SceneMode, speed: Option<Speed>) -> Self {
+        DynamicScene { scene, speed }
+    }
+
+    /// Accessor for the scene
+    pub fn scene(&self) -> &SceneMode {
+        &self.scene
+    }
+
+    /// Accessor for the speed, if one was given
+    pub fn speed(&self) -> Option<&Speed> {
+        self.speed.as_ref()
+    }
+}
+
+/// Kelvin sets a temperature mode, values from 1000 to 8000
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Eq)]
+pub struct Kelvin {
+    #[schema(minimum = 1000, maximum = 8000)]
+    kelvin: u16,
+}
+
+impl Kelvin {
+    /// Lowest valid temperature, in Kelvin
+    pub const MIN: u16 = 1000;
+
+    /// Highest valid temperature, in Kelvin
+    pub const MAX: u16 = 8000;
+
+    /// Create a new Kelvin setting with the default value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Kelvin;
+    ///
+    /// assert_eq!(Kelvin::new().kelvin(), 1000);
+    /// ```
+    ///
+    pub fn new() -> Self {
+        Kelvin { kelvin: 1000 }
+    }
+
+    /// Accessor for our read-only kelvin setting
+    pub fn kelvin(&self) -> u16 {
+        self.kelvin
+    }
+
+    /// Create a new Kelvin setting with the given value
+    ///
+    /// # Returns
+    ///   [Kelvin] when value is within the valid range
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Kelvin;
+    ///
+    /// assert!(Kelvin::create(999).is_none());
+    /// assert!(Kelvin::create(1000).is_some());
+    /// assert!(Kelvin::create(8000).is_some());
+    /// assert!(Kelvin::create(8001).is_none());
+    /// ```
+    ///
+    pub fn create(kelvin: u16) -> Option<Self> {
+        if Self::valid(kelvin) {
+            Some(Kelvin { kelvin })
+        } else {
+            None
+        }
+    }
+
+    /// Create a new Kelvin setting with the given value if within
+    /// the valid range, otherwise the default value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Kelvin;
+    ///
+    /// assert_eq!(Kelvin::create_or(999).kelvin(), 1000);
+    /// assert_eq!(Kelvin::create_or(2700).kelvin(), 2700);
+    /// assert_eq!(Kelvin::create_or(8000).kelvin(), 8000);
+    /// assert_eq!(Kelvin::create_or(8001).kelvin(), 1000);
+    /// ```
+    ///
+    pub fn create_or(kelvin: u16) -> Self {
+        Kelvin {
+            kelvin: if Self::valid(kelvin) { kelvin } else { 1000 },
+        }
+    }
+
+    /// Check if the value is within the valid range
+    fn valid(kelvin: u16) -> bool {
+        (Self::MIN..=Self::MAX).contains(&kelvin)
+    }
+}
+
+/// How a light warms as it dims, see [Light::warm_dim]
+///
+/// The temperature runs in a straight line from `dim` at the lowest
+/// brightness to `bright` at full brightness, like an incandescent bulb.
+/// Set as `<dim>-<bright>` in Kelvin with `RIZ_WARM_DIM_KELVIN` (env var),
+/// defaults to 2200-2700.
+///
+/// # Examples
+///
+/// ```
+/// use riz::models::{Brightness, Kelvin, WarmDimCurve};
+///
+/// let curve = WarmDimCurve::new(Kelvin::create(2000).unwrap(), Kelvin::create(3000).unwrap());
+/// assert_eq!(curve.kelvin(&Brightness::create(10).unwrap()).kelvin(), 2000);
+/// assert_eq!(curve.kelvin(&Brightness::create(55).unwrap()).kelvin(), 2500);
+/// assert_eq!(curve.kelvin(&Brightness::create(100).unwrap()).kelvin(), 3000);
+///
+/// let curve: WarmDimCurve = "1800-3000".parse().unwrap();
+/// assert_eq!(curve.kelvin(&Brightness::create(100).unwrap()).kelvin(), 3000);
+/// ```
+///
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Eq)]
+pub struct WarmDimCurve {
+    /// Temperature at the lowest brightness
+    dim: Kelvin,
+
+    /// Temperature at full brightness
+    bright: Kelvin,
+}
+
+impl Default for WarmDimCurve {
+    fn default() -> Self {
+        WarmDimCurve {
+            dim: Kelvin { kelvin: 2200 },
+            bright: Kelvin { kelvin: 2700 },
+        }
+    }
+}
+
+impl FromStr for WarmDimCurve {
+    type Err = String;
+
+    /// Create a new curve from `<dim>-<bright>` in Kelvin, each 1000-8000
+    fn from_str(s: &str) -> StdResult<Self, String> {
+        let invalid = || format!("invalid warm dim curve: {}", s);
+        let (dim, bright) = s.split_once('-').ok_or_else(invalid)?;
+        let kelvin = |v: &str| v.trim().parse().ok().and_then(Kelvin::create);
+        match (kelvin(dim), kelvin(bright)) {
+            (Some(dim), Some(bright)) => Ok(WarmDimCurve { dim, bright }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl WarmDimCurve {
+    /// Create a curve from the dimmest to the brightest temperature
+    pub fn new(dim: Kelvin, bright: Kelvin) -> Self {
+        WarmDimCurve { dim, bright }
+    }
+
+    /// Read the curve from `RIZ_WARM_DIM_KELVIN` (env var), or the default
+    pub fn from_env() -> Self {
+        match env::var(WARM_DIM_ENV_KEY) {
+            Ok(v) => v.parse().unwrap_or_else(|e| {
+                warn!("Ignoring {}: {}", WARM_DIM_ENV_KEY, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Temperature for the brightness along this curve
+    pub fn kelvin(&self, brightness: &Brightness) -> Kelvin {
+        let span = i32::from(Brightness::MAX - Brightness::MIN);
+        let step = i32::from(brightness.value().saturating_sub(Brightness::MIN));
+        let (dim, bright) = (i32::from(self.dim.kelvin), i32::from(self.bright.kelvin));
+        let kelvin = dim + (bright - dim) * step.min(span) / span;
+        Kelvin::create_or(kelvin as u16)
+    }
+}
+
+/// White describes a cool or warm white mode, values from 1 to 100
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Eq)]
+pub struct White {
+    #[schema(minimum = 1, maximum = 100)]
+    value: u8,
+}
+
+impl White {
+    /// Lowest valid white value
+    pub const MIN: u8 = 1;
+
+    /// Highest valid white value
+    pub const MAX: u8 = 100;
+
+    /// Create a new white setting with the default value
+    pub fn new() -> Self {
+        White { value: 100 }
+    }
+
+    /// Accessor for our read-only value
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    /// Create a new white setting with the given value
+    ///
+    /// # Returns
+    ///   [White] if the value provided is within the valid range
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::White;
+    ///
+    /// assert!(White::create(0).is_none());
+    /// assert!(White::create(1).is_some());
+    /// assert!(White::create(100).is_some());
+    /// assert!(White::create(101).is_none());
+    /// ```
+    ///
+    pub fn create(value: u8) -> Option<Self> {
+        if Self::valid(value) {
+            Some(White { value })
+        } else {
+            None
+        }
+    }
+
+    /// Create a new white setting with the given value if within
+    /// the valid range, otherwise the default value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::White;
+    ///
+    /// assert_eq!(White::create_or(0).value(), 100);
+    /// assert_eq!(White::create_or(1).value(), 1);
+    /// assert_eq!(White::create_or(100).value(), 100);
+    /// assert_eq!(White::create_or(101).value(), 100);
+    /// ```
+    ///
+    pub fn create_or(value: u8) -> Self {
+        White {
+            value: if Self::valid(value) { value } else { 100 },
+        }
+    }
+
+    /// Check if the value is within the valid range
+    fn valid(value: u8) -> bool {
+        (Self::MIN..=Self::MAX).contains(&value)
+    }
+}
+
+/// FanSpeed sets the fan speed on Wiz fan fixtures, values from 1 to 6
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Eq)]
+pub struct FanSpeed {
+    #[schema(minimum = 1, maximum = 6)]
+    value: u8,
+}
+
+impl FanSpeed {
+    /// Create a new fan speed setting with the default value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::FanSpeed;
+    ///
+    /// assert_eq!(FanSpeed::new().value(), 1);
+    /// ```
+    ///
+    pub fn new() -> Self {
+        FanSpeed { value: 1 }
+    }
+
+    /// Accessor for our read-only value
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    /// Create a new fan speed setting with the given value
+    ///
+    /// # Returns
+    ///   [FanSpeed] when value is within the valid range
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::FanSpeed;
+    ///
+    /// assert!(FanSpeed::create(0).is_none());
+    /// assert!(FanSpeed::create(1).is_some());
+    /// assert!(FanSpeed::create(6).is_some());
+    /// assert!(FanSpeed::create(7).is_none());
+    /// ```
+    ///
+    pub fn create(value: u8) -> Option<Self> {
+        if Self::valid(value) {
+            Some(FanSpeed { value })
+        } else {
+            None
+        }
+    }
+
+    /// Create a new fan speed setting with the given value if within
+    /// the valid range, otherwise the default value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::FanSpeed;
+    ///
+    /// assert_eq!(FanSpeed::create_or(0).value(), 1);
+    /// assert_eq!(FanSpeed::create_or(4).value(), 4);
+    /// assert_eq!(FanSpeed::create_or(7).value(), 1);
+    /// ```
+    ///
+    pub fn create_or(value: u8) -> Self {
+        FanSpeed {
+            value: if Self::valid(value) { value } else { 1 },
+        }
+    }
+
+    /// Check if the value is within the valid range
+    fn valid(value: u8) -> bool {
+        (1..=6).contains(&value)
+    }
+}
+
+/// Ratio splits output between the zones of dual-head fixtures,
+/// values from 0 to 100
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Eq)]
+pub struct Ratio {
+    #[schema(minimum = 0, maximum = 100)]
+    value: u8,
+}
+
+impl Ratio {
+    /// Create a new ratio setting with the default (even) value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Ratio;
+    ///
+    /// assert_eq!(Ratio::new().value(), 50);
+    /// ```
+    ///
+    pub fn new() -> Self {
+        Ratio { value: 50 }
+    }
+
+    /// Accessor for our read-only value
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    /// Create a new ratio setting with the given value
+    ///
+    /// # Returns
+    ///   [Ratio] when value is within the valid range
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Ratio;
+    ///
+    /// assert!(Ratio::create(0).is_some());
+    /// assert!(Ratio::create(100).is_some());
+    /// assert!(Ratio::create(101).is_none());
+    /// ```
+    ///
+    pub fn create(value: u8) -> Option<Self> {
+        if Self::valid(value) {
+            Some(Ratio { value })
+        } else {
+            None
+        }
+    }
+
+    /// Create a new ratio setting with the given value if within
+    /// the valid range, otherwise the default value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Ratio;
+    ///
+    /// assert_eq!(Ratio::create_or(0).value(), 0);
+    /// assert_eq!(Ratio::create_or(100).value(), 100);
+    /// assert_eq!(Ratio::create_or(101).value(), 50);
+    /// ```
+    ///
+    pub fn create_or(value: u8) -> Self {
+        Ratio {
+            value: if Self::valid(value) { value } else { 50 },
+        }
+    }
+
+    /// Check if the value is within the valid range
+    fn valid(value: u8) -> bool {
+        value <= 100
+    }
+}
+
+/// Color is any RGB color, values from 0 to 255
+///
+/// Deserializes from its channels, or a string as parsed by
+/// [Color::from_str], like `"#ffaa00"` or `"255,170,0"`
+///
+/// # Examples
+///
+/// ```
+/// use riz::models::Color;
+///
+/// let parsed: Vec<Color> = serde_json::from_str(
+///     r##"[{"red": 255, "green": 170, "blue": 0}, "#ffaa00", "255,170,0"]"##,
+/// )
+/// .unwrap();
+/// assert!(parsed.iter().all(|c| c == &Color::rgb(255, 170, 0)));
+/// ```
+///
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Eq)]
+#[serde(try_from = "ColorForm")]
+pub struct Color {
+    #[schema(maximum = 255)]
+    red: u8,
+    #[schema(maximum = 255)]
+    green: u8,
+    #[schema(maximum = 255)]
+    blue: u8,
+}
+
+impl Color {
+    /// Create a new default color
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use riz::models::Color;
+    ///
+    /// assert_eq!(Color::new(), Color::from_str("0,0,0").unwrap());
+    /// assert_ne!(Color::new(), Color::from_str("0,1,0").unwrap());
+    /// ```
+    ///
+    pub fn new() -> Self {
+        Color {
+            red: 0,
+            green: 0,
+            blue: 0,
+        }
+    }
+
+    /// Accessor for this color's read-only red value
+    pub fn red(&self) -> u8 {
+        self.red
+    }
+
+    /// Accessor for this color's read-only green value
+    pub fn green(&self) -> u8 {
+        self.green
+    }
+
+    /// Accessor for this color's read-only blue value
+    pub fn blue(&self) -> u8 {
+        self.blue
+    }
+
+    /// Create a new color from its red, green and blue values
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use riz::models::Color;
+    ///
+    /// assert_eq!(Color::rgb(255, 0, 10), Color::from_str("255,0,10").unwrap());
+    /// ```
+    ///
+    pub fn rgb(red: u8, green: u8, blue: u8) -> Self {
+        Color { red, green, blue }
+    }
+
+    /// Get this color's values as a (red, green, blue) tuple
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Color;
+    ///
+    /// assert_eq!(Color::rgb(1, 2, 3).as_tuple(), (1, 2, 3));
+    /// ```
+    ///
+    pub fn as_tuple(&self) -> (u8, u8, u8) {
+        (self.red, self.green, self.blue)
+    }
+
+    /// Get this color's values as a [red, green, blue] array
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Color;
+    ///
+    /// assert_eq!(Color::rgb(1, 2, 3).as_array(), [1, 2, 3]);
+    /// ```
+    ///
+    pub fn as_array(&self) -> [u8; 3] {
+        [self.red, self.green, self.blue]
+    }
+
+    /// Get this color as a hex string, like `#ff8000`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Color;
+    ///
+    /// assert_eq!(Color::rgb(255, 128, 0).hex(), "#ff8000");
+    /// ```
+    ///
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+
+    /// Parse `rrggbb` hex digits, without the leading `#`
+    fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(Color {
+            red: channel(0)?,
+            green: channel(2)?,
+            blue: channel(4)?,
+        })
+    }
+
+    /// Create a new color with the channels set in the partial color
+    /// replacing the channels of this color
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{Color, PartialColor};
+    ///
+    /// let partial = PartialColor::new(Some(255), None, None);
+    /// assert_eq!(Color::rgb(0, 10, 20).merge(&partial), Color::rgb(255, 10, 20));
+    /// ```
+    ///
+    pub fn merge(&self, partial: &PartialColor) -> Self {
+        Color {
+            red: partial.red.unwrap_or(self.red),
+            green: partial.green.unwrap_or(self.green),
+            blue: partial.blue.unwrap_or(self.blue),
+        }
+    }
+}
+
+impl From<(u8, u8, u8)> for Color {
+    /// Create a new Color from a (red, green, blue) tuple
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Color;
+    ///
+    /// assert_eq!(Color::from((10, 20, 30)), Color::rgb(10, 20, 30));
+    /// ```
+    ///
+    fn from((red, green, blue): (u8, u8, u8)) -> Self {
+        Color { red, green, blue }
+    }
+}
+
+impl From<[u8; 3]> for Color {
+    /// Create a new Color from a [red, green, blue] array
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Color;
+    ///
+    /// assert_eq!(Color::from([10, 20, 30]), Color::rgb(10, 20, 30));
+    /// ```
+    ///
+    fn from([red, green, blue]: [u8; 3]) -> Self {
+        Color { red, green, blue }
+    }
+}
+
+impl From<&Color> for (u8, u8, u8) {
+    fn from(color: &Color) -> Self {
+        color.as_tuple()
+    }
+}
+
+impl From<&Color> for [u8; 3] {
+    fn from(color: &Color) -> Self {
+        color.as_array()
+    }
+}
+
+/// Forms a [Color] is accepted in, when deserializing
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorForm {
+    Channels { red: u8, green: u8, blue: u8 },
+    Text(String),
+}
+
+impl TryFrom<ColorForm> for Color {
+    type Error = String;
+
+    fn try_from(form: ColorForm) -> StdResult<Self, String> {
+        match form {
+            ColorForm::Channels { red, green, blue } => Ok(Color { red, green, blue }),
+            ColorForm::Text(text) => Color::from_str(&text),
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    /// Create a new Color from a string slice
+    ///
+    /// Expected format is r,g,b where each value can be 0-255,
+    /// values outside this range will be converted to zero.
+    /// Hex strings like `#ffeeff` are also accepted.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use riz::models::Color;
+    ///
+    /// assert!(Color::from_str("100,80,240").is_ok());
+    /// assert!(Color::from_str("100,80,240,255").is_err());
+    /// assert_eq!(Color::from_str("#ffeeff").unwrap(), Color::rgb(255, 238, 255));
+    /// assert!(Color::from_str("#ffeef").is_err());
+    /// assert!(Color::from_str("#ffeegg").is_err());
+    ///
+    /// assert_eq!(
+    ///   Color::from_str("1000,-2,256").unwrap(),
+    ///   Color::from_str("0,0,0").unwrap()
+    /// );
+    /// ```
+    ///
+    fn from_str(s: &str) -> StdResult<Self, String> {
+        if let Some(hex) = s.strip_prefix('#') {
+            return Self::from_hex(hex).ok_or_else(|| "Invalid hex color string".to_string());
+        }
+
+        let parts: Vec<_> = s.split(',').map(|c| c.parse::<u8>().unwrap_or(0)).collect();
+
+        if parts.len() == 3 {
+            Ok(Color {
+                red: parts[0],
+                green: parts[1],
+                blue: parts[2],
+            })
+        } else {
+            Err("Invalid color string".to_string())
+        }
+    }
+}
+
+/// Partial RGB color, only the set channels are changed, values from 0 to 255
+///
+/// Wiz bulbs need all three channels, so this is merged onto a known
+/// [Color] with [Color::merge] before being sent.
+///
+#[serde_with::skip_serializing_none]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub struct PartialColor {
+    #[schema(maximum = 255)]
+    red: Option<u8>,
+    #[schema(maximum = 255)]
+    green: Option<u8>,
+    #[schema(maximum = 255)]
+    blue: Option<u8>,
+}
+
+impl PartialColor {
+    /// Create a new partial color, with any of the channels set
+    pub fn new(red: Option<u8>, green: Option<u8>, blue: Option<u8>) -> Self {
+        PartialColor { red, green, blue }
+    }
+}
+
+/// API request for a lighting settings change on a [Light]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct LightRequest {
+    // brightness percent, valid from 10 to 100
+    // to be used with setbrightness --dim <value>
+    brightness: Option<Brightness>,
+
+    // set the rgb color value, valid from 0 to 255
+    // to be used with setrgbcolor --r <r> --g <g> --b <b>
+    color: Option<Color>,
+
+    // Color changing speed, from 20 to 200 (time %)
+    // to be used with setspeed --speed <value>
+    speed: Option<Speed>,
+
+    // Color temperature, in kelvins from 1000 to 8000
+    // to be used with setcolortemp --temp <value>
+    temp: Option<Kelvin>,
+
+    // Scene to select, from enum
+    // to be used with setscene --scene <value>
+    scene: Option<SceneMode>,
+
+    // Scene to select by its Wiz ID, from 1 to 255, for scenes the bulb
+    // has which aren't in [SceneMode] yet. Sent instead of `scene` if
+    // both are set, see [LightRequest::scene_id]
+    #[schema(minimum = 1, maximum = 255)]
+    scene_id: Option<u8>,
+
+    // If we would like to adjust the light's power
+    power: Option<PowerMode>,
+
+    // If we'd like to set the cool white value
+    cool: Option<White>,
+
+    // If we'd like to set the warm white value
+    warm: Option<White>,
+
+    // Fan power, for fan fixtures only
+    fan_state: Option<bool>,
+
+    // Fan speed, from 1 to 6, for fan fixtures only
+    fan_speed: Option<FanSpeed>,
+
+    // Split between zones, from 0 to 100, for dual-head fixtures only
+    ratio: Option<Ratio>,
+
+    // Change only some channels of the light's last known color,
+    // for single lights only, see [LightRequest::merge_color]
+    partial_color: Option<PartialColor>,
+
+    // Only send to bulbs which are currently on, this costs an extra
+    // status read round trip to each bulb before sending
+    only_if_on: Option<bool>,
+}
+
+impl LightRequest {
+    /// Create a new request with no settings
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::LightRequest;
+    ///
+    /// let req = LightRequest::empty();
+    /// assert!(req.power().is_none());
+    /// assert!(req.scene().is_none());
+    /// ```
+    ///
+    pub fn empty() -> Self {
+        LightRequest::default()
+    }
+
+    /// Create a new request which only changes the [PowerMode]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{LightRequest, PowerMode};
+    ///
+    /// let req = LightRequest::from_power(PowerMode::Off);
+    /// assert!(matches!(req.power(), Some(PowerMode::Off)));
+    /// ```
+    ///
+    pub fn from_power(power: PowerMode) -> Self {
+        LightRequest {
+            power: Some(power),
+            ..Default::default()
+        }
+    }
+
+    /// Create the safe default request, to recover a room from a bad setting
+    ///
+    /// Set as a JSON [LightRequest] with `RIZ_RESET_REQUEST` (env var),
+    /// otherwise warm white (2700K) at full brightness, turned on. An
+    /// unreadable or invalid setting is logged and the default used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{LightRequest, PowerMode};
+    ///
+    /// let req = LightRequest::reset();
+    /// assert!(matches!(req.power(), Some(PowerMode::On)));
+    /// assert_eq!(req.brightness().unwrap().value(), 100);
+    /// assert_eq!(req.temp().unwrap().kelvin(), 2700);
+    /// ```
+    ///
+    pub fn reset() -> Self {
+        let configured = env::var(RESET_REQUEST_ENV_KEY).ok().and_then(|v| {
+            match serde_json::from_str::<LightRequest>(&v) {
+                Ok(req) if req.validate().is_ok() && req.partial_color.is_none() => Some(req),
+                _ => {
+                    warn!("Ignoring invalid {}: {}", RESET_REQUEST_ENV_KEY, v);
+                    None
+                }
+            }
+        });
+
+        configured.unwrap_or_else(|| LightRequest {
+            power: Some(PowerMode::On),
+            brightness: Some(Brightness::new()),
+            temp: Some(Kelvin { kelvin: 2700 }),
+            ..Default::default()
+        })
+    }
+
+    /// Create a new request with a random [Color] and/or [SceneMode]
+    ///
+    /// Pass a seeded RNG for repeatable picks
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use riz::models::{LightRequest, RandomMode};
+    ///
+    /// let req = LightRequest::random(RandomMode::Scene, &mut StdRng::seed_from_u64(7));
+    /// assert!(req.scene().is_some());
+    /// assert!(req.color().is_none());
+    /// ```
+    ///
+    pub fn random<R: Rng + ?Sized>(mode: RandomMode, rng: &mut R) -> Self {
+        let scene = match mode {
+            RandomMode::Color => false,
+            RandomMode::Scene => true,
+            RandomMode::Any => rng.gen_bool(0.5),
+        };
+
+        if scene {
+            LightRequest {
+                scene: SceneMode::iter().choose(rng),
+                ..Default::default()
+            }
+        } else {
+            LightRequest {
+                color: Some(Color::rgb(rng.gen(), rng.gen(), rng.gen())),
+                ..Default::default()
+            }
+        }
+    }
+
+    /// Accessor to get this request's optional [PowerMode] setting
+    pub fn power(&self) -> Option<&PowerMode> {
+        self.power.as_ref()
+    }
+
+    /// Accessor to get this request's optional [SceneMode] setting
+    pub fn scene(&self) -> Option<&SceneMode> {
+        self.scene.as_ref()
+    }
+
+    /// Accessor to get this request's optional raw scene ID setting
+    ///
+    /// The ID is sent to the bulb as is, without checking it's a known
+    /// [SceneMode]. When both are set, the scene ID is sent and the
+    /// [SceneMode] is ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{LightRequest, LightStatus, Payload};
+    ///
+    /// let req: LightRequest = serde_json::from_str(
+    ///     r#"{"scene": "Party", "scene_id": 250}"#
+    /// ).unwrap();
+    /// assert!(req.validate().is_ok());
+    /// assert_eq!(req.scene_id(), Some(250));
+    ///
+    /// let status = LightStatus::from(&Payload::from(&req));
+    /// assert!(status.scene().is_none());
+    /// assert_eq!(status.unknown_scene(), Some(250));
+    /// ```
+    ///
+    pub fn scene_id(&self) -> Option<u8> {
+        self.scene_id
+    }
+
+    /// Accessor to get this request's optional [Brightness] setting
+    pub fn brightness(&self) -> Option<&Brightness> {
+        self.brightness.as_ref()
+    }
+
+    /// Accessor to get this request's optional [Kelvin] setting
+    pub fn temp(&self) -> Option<&Kelvin> {
+        self.temp.as_ref()
+    }
+
+    /// Accessor to get this request's optional [Color] setting
+    pub fn color(&self) -> Option<&Color> {
+        self.color.as_ref()
+    }
+
+    /// Accessor to get this request's optional [PartialColor] setting
+    pub fn partial_color(&self) -> Option<&PartialColor> {
+        self.partial_color.as_ref()
+    }
+
+    /// Check if this request should only be sent to bulbs which are on
+    ///
+    /// Each bulb's status is read before sending, bulbs which aren't
+    /// emitting are skipped
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::LightRequest;
+    ///
+    /// let req: LightRequest = serde_json::from_str(
+    ///     r#"{"brightness": {"value": 30}, "only_if_on": true}"#
+    /// ).unwrap();
+    /// assert!(req.only_if_on());
+    /// assert!(!LightRequest::empty().only_if_on());
+    /// ```
+    ///
+    pub fn only_if_on(&self) -> bool {
+        self.only_if_on.unwrap_or(false)
+    }
+
+    /// Resolve a partial color into a full color for this request
+    ///
+    /// The partial color is merged onto this request's color if it has
+    /// one, otherwise onto the light's last known color. Does nothing
+    /// without a partial color.
+    ///
+    /// # Errors
+    ///   [Error::NoKnownColor] if there's no color to merge onto
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{Color, LightRequest};
+    ///
+    /// let mut req: LightRequest = serde_json::from_str(
+    ///     r#"{"partial_color": {"green": 100}}"#
+    /// ).unwrap();
+    /// assert!(req.merge_color(None).is_err());
+    ///
+    /// req.merge_color(Some(&Color::rgb(1, 2, 3))).unwrap();
+    /// assert_eq!(req.color(), Some(&Color::rgb(1, 100, 3)));
+    /// assert!(req.partial_color().is_none());
+    /// ```
+    ///
+    pub fn merge_color(&mut self, known: Option<&Color>) -> Result<()> {
+        let partial = match self.partial_color.take() {
+            Some(partial) => partial,
+            None => return Ok(()),
+        };
+
+        match self.color.as_ref().or(known) {
+            Some(color) => {
+                self.color = Some(color.merge(&partial));
+                Ok(())
+            }
+            None => {
+                self.partial_color = Some(partial);
+                Err(Error::NoKnownColor)
+            }
+        }
+    }
+
+    /// Check that this request can be sent to a bulb
+    ///
+    /// # Errors
+    ///   [Error::RebootWithPayload] if a reboot is requested alongside
+    ///   any lighting settings, the bulb drops off the network to reboot
+    ///
+    ///   [Error::ColorWithWhite] if a color is requested alongside cool
+    ///   or warm white, see [Payload::validate_semantics]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::LightRequest;
+    ///
+    /// let req: LightRequest = serde_json::from_str(r#"{"power": "Reboot"}"#).unwrap();
+    /// assert!(req.validate().is_ok());
+    ///
+    /// let req: LightRequest = serde_json::from_str(
+    ///     r#"{"power": "Reboot", "brightness": {"value": 50}}"#
+    /// ).unwrap();
+    /// assert!(req.validate().is_err());
+    /// ```
+    ///
+    pub fn validate(&self) -> Result<()> {
+        if matches!(self.power, Some(PowerMode::Reboot)) && self.has_lighting() {
+            return Err(Error::RebootWithPayload);
+        }
+        if (self.color.is_some() || self.partial_color.is_some())
+            && (self.cool.is_some() || self.warm.is_some())
+        {
+            return Err(Error::ColorWithWhite);
+        }
+        if self.scene_id == Some(0) {
+            return Err(Error::InvalidSceneId);
+        }
+        Ok(())
+    }
+
+    /// Check if the bulb's status shows this request's readable settings
+    ///
+    /// Compares the power, brightness, color, scene and temperature which
+    /// were requested, see [Light::ensure]
+    ///
+    fn confirmed_by(&self, status: &LightStatus) -> bool {
+        let mut expected = LightStatus::from(&Payload::from(self));
+        if let Some(power) = &self.power {
+            expected.update_from_power(power);
+        }
+
+        let diff = expected.diff(status);
+        !((self.power.is_some() && diff.emitting)
+            || (self.brightness.is_some() && diff.brightness)
+            || (self.color.is_some() && diff.color)
+            || ((self.scene.is_some() || self.scene_id.is_some()) && diff.scene)
+            || (self.temp.is_some() && diff.temp))
+    }
+
+    /// Check if any lighting (non-power) settings are requested
+    fn has_lighting(&self) -> bool {
+        self.brightness.is_some()
+            || self.color.is_some()
+            || self.speed.is_some()
+            || self.temp.is_some()
+            || self.scene.is_some()
+            || self.scene_id.is_some()
+            || self.cool.is_some()
+            || self.warm.is_some()
+            || self.fan_state.is_some()
+            || self.fan_speed.is_some()
+            || self.ratio.is_some()
+            || self.partial_color.is_some()
+    }
+}
+
+impl From<&PowerMode> for LightRequest {
+    fn from(power: &PowerMode) -> Self {
+        LightRequest::from_power(power.clone())
+    }
+}
+
+impl From<&Brightness> for LightRequest {
+    fn from(brightness: &Brightness) -> Self {
+        LightRequest {
+            brightness: Some(brightness.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Create a request which would put a bulb back into this status
+///
+/// Only the active context ([LightStatus::last], or the first known of
+/// scene, color, temp, cool & warm) is kept, with the brightness and the
+/// speed for scenes. A bulb which isn't emitting is only turned off.
+///
+/// # Examples
+///
+/// ```
+/// use riz::models::{Color, LightRequest, LightStatus, Payload, PowerMode};
+///
+/// let status = LightStatus::from(&Payload::from(&Color::rgb(255, 0, 0)));
+/// let req = LightRequest::from(&status);
+/// assert!(matches!(req.power(), Some(PowerMode::On)));
+/// assert_eq!(req.color(), Some(&Color::rgb(255, 0, 0)));
+/// assert!(req.scene().is_none());
+/// ```
+///
+impl From<&LightStatus> for LightRequest {
+    fn from(status: &LightStatus) -> Self {
+        if !status.emitting {
+            return LightRequest::from_power(PowerMode::Off);
+        }
+
+        let last = status.last.clone().or_else(|| {
+            if status.scene.is_some() {
+                Some(LastSet::Scene)
+            } else if status.color.is_some() {
+                Some(LastSet::Color)
+            } else if status.temp.is_some() {
+                Some(LastSet::Temp)
+            } else if status.cool.is_some() {
+                Some(LastSet::Cool)
+            } else if status.warm.is_some() {
+                Some(LastSet::Warm)
+            } else {
+                None
+            }
+        });
+
+        let mut req = LightRequest {
+            power: Some(PowerMode::On),
+            brightness: status.brightness.clone(),
+            ..Default::default()
+        };
+        match last {
+            Some(LastSet::Scene) => {
+                req.scene = status.scene.clone();
+                req.speed = status.speed.clone();
+            }
+            Some(LastSet::Color) => req.color = status.color.clone(),
+            Some(LastSet::Temp) => req.temp = status.temp.clone(),
+            Some(LastSet::Cool) => req.cool = status.cool.clone(),
+            Some(LastSet::Warm) => req.warm = status.warm.clone(),
+            None => {}
+        }
+        req
+    }
+}
+
+/// What to pick from for a random lighting setting, see [Light::random]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, Copy, ToSchema, PartialEq, Eq)]
+pub enum RandomMode {
+    /// Any RGB color
+    Color,
+
+    /// Any preset scene
+    Scene,
+
+    /// Either a color or a scene
+    #[default]
+    Any,
+}
+
+impl FromStr for RandomMode {
+    type Err = String;
+
+    /// Parse a random mode by name, ignoring case
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use riz::models::RandomMode;
+    ///
+    /// assert_eq!(RandomMode::from_str("scene"), Ok(RandomMode::Scene));
+    /// assert_eq!(RandomMode::from_str("Any"), Ok(RandomMode::Any));
+    /// assert!(RandomMode::from_str("disco").is_err());
+    /// ```
+    ///
+    fn from_str(s: &str) -> StdResult<Self, String> {
+        match s.to_lowercase().as_str() {
+            "color" => Ok(RandomMode::Color),
+            "scene" => Ok(RandomMode::Scene),
+            "any" => Ok(RandomMode::Any),
+            _ => Err(format!("Invalid random mode: {} (color, scene or any)", s)),
+        }
+    }
+}
+
+/// Describes a potential emitting state of a [Light]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub enum PowerMode {
+    /// Send a reboot command to the light
+    Reboot,
+
+    /// Tell the bulb to emit light
+    On,
+
+    /// Tell the bulb to stop emitting light
+    Off,
+}
+
+/// Preset lighting modes
+///
+/// Ordered by their Wiz scene ID
+///
+#[derive(
+    Debug, Serialize, Deserialize, Clone, ToSchema, EnumIter, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum SceneMode {
+    Ocean = 1,
+    Romance = 2,
+    Sunset = 3,
+    Party = 4,
+    Fireplace = 5,
+    Cozy = 6,
+    Forest = 7,
+    PastelColors = 8,
+    WakeUp = 9,
+    Bedtime = 10,
+    WarmWhite = 11,
+    Daylight = 12,
+    CoolWhite = 13,
+    NightLight = 14,
+    Focus = 15,
+    Relax = 16,
+    TrueColors = 17,
+    TvTime = 18,
+    Plantgrowth = 19,
+    Spring = 20,
+    Summer = 21,
+    Fall = 22,
+    Deepdive = 23,
+    Jungle = 24,
+    Mojito = 25,
+    Club = 26,
+    Christmas = 27,
+    Halloween = 28,
+    Candlelight = 29,
+    GoldenWhite = 30,
+    Pulse = 31,
+    Steampunk = 32,
+    Diwali = 33,
+}
+
+impl SceneMode {
+    pub fn create(value: u8) -> Option<Self> {
+        // this is suboptimal...
+        SceneMode::iter().find(|scene| scene.clone() as u8 == value)
+    }
 
-impl Speed {
-    /// Create a new speed setting with the default value
+    /// Built-in display name for this scene
     ///
     /// # Examples
     ///
     /// ```
-    /// use riz::models::Speed;
+    /// use riz::models::SceneMode;
     ///
-    /// assert_eq!(Speed::new().value(), 100);
+    /// assert_eq!(SceneMode::PastelColors.title(), "Pastel Colors");
     /// ```
     ///
-    pub fn new() -> Self {
-        Speed { value: 100 }
-    }
-
-    /// Accessor for our read-only value
-    pub fn value(&self) -> u8 {
-        self.value
+    pub fn title(&self) -> String {
+        format!("{:?}", self)
+            .from_case(Case::Pascal)
+            .to_case(Case::Title)
     }
 
-    /// Create a new speed setting with the given value
+    /// Check if a bulb with the given `moduleName` can play this scene
     ///
-    /// # Returns
-    ///   [Speed] when value is within the valid range
+    /// Tunable white (`TW`) and dimmable white (`DW`) bulbs only support
+    /// a subset of scenes, everything else (RGB or unrecognized modules)
+    /// is assumed to support them all. The bulb type is read from the
+    /// module name, see [SystemConfig::rgb].
     ///
     /// # Examples
     ///
     /// ```
-    /// use riz::models::Speed;
+    /// use riz::models::SceneMode;
     ///
-    /// assert!(Speed::create(19).is_none());
-    /// assert!(Speed::create(20).is_some());
-    /// assert!(Speed::create(200).is_some());
-    /// assert!(Speed::create(201).is_none());
+    /// assert!(SceneMode::Ocean.supported_by("ESP01_SHRGB_03"));
+    /// assert!(!SceneMode::Ocean.supported_by("ESP01_SHTW1C_31"));
+    /// assert!(SceneMode::Cozy.supported_by("ESP01_SHTW1C_31"));
+    /// assert!(!SceneMode::Cozy.supported_by("ESP06_SHDW9_01"));
+    /// assert!(SceneMode::Bedtime.supported_by("ESP06_SHDW9_01"));
     /// ```
     ///
-    pub fn create(value: u8) -> Option<Self> {
-        if Self::valid(value) {
-            Some(Speed { value })
+    pub fn supported_by(&self, module_name: &str) -> bool {
+        let scene = self.clone() as u8;
+        let bulb_type = bulb_type(module_name);
+        if bulb_type.contains("TW") {
+            TW_SCENES.contains(&scene)
+        } else if bulb_type.contains("DW") {
+            DW_SCENES.contains(&scene)
         } else {
-            None
+            true
         }
     }
 
-    /// Create a new speed setting with the given value if within
-    /// the valid range, otherwise the default value
+    /// Menu grouping for this scene
     ///
     /// # Examples
     ///
     /// ```
-    /// use riz::models::Speed;
+    /// use riz::models::{SceneCategory, SceneMode};
     ///
-    /// assert_eq!(Speed::create_or(19).value(), 100);
-    /// assert_eq!(Speed::create_or(20).value(), 20);
-    /// assert_eq!(Speed::create_or(200).value(), 200);
-    /// assert_eq!(Speed::create_or(201).value(), 100);
+    /// assert_eq!(SceneMode::Focus.category(), SceneCategory::White);
+    /// assert_eq!(SceneMode::Ocean.category(), SceneCategory::Dynamic);
+    /// assert_eq!(SceneMode::Halloween.category(), SceneCategory::Seasonal);
     /// ```
     ///
-    pub fn create_or(value: u8) -> Self {
-        Speed {
-            value: if Self::valid(value) { value } else { 100 },
+    pub fn category(&self) -> SceneCategory {
+        match self {
+            SceneMode::WakeUp
+            | SceneMode::Bedtime
+            | SceneMode::WarmWhite
+            | SceneMode::Daylight
+            | SceneMode::CoolWhite
+            | SceneMode::NightLight
+            | SceneMode::Focus
+            | SceneMode::Relax
+            | SceneMode::TrueColors
+            | SceneMode::TvTime
+            | SceneMode::Plantgrowth
+            | SceneMode::Candlelight
+            | SceneMode::GoldenWhite => SceneCategory::White,
+            SceneMode::Spring
+            | SceneMode::Summer
+            | SceneMode::Fall
+            | SceneMode::Christmas
+            | SceneMode::Halloween
+            | SceneMode::Diwali => SceneCategory::Seasonal,
+            _ => SceneCategory::Dynamic,
         }
     }
+}
 
-    fn valid(value: u8) -> bool {
-        (20..=200).contains(&value)
-    }
+/// Menu grouping of [SceneMode]s, ordered as they should be listed
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SceneCategory {
+    /// Static or slowly changing white light
+    White,
+
+    /// Colorful, changing scenes
+    Dynamic,
+
+    /// Holiday and season themed scenes
+    Seasonal,
 }
 
-/// Kelvin sets a temperature mode, values from 1000 to 8000
-#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema)]
-pub struct Kelvin {
-    #[schema(minimum = 1000, maximum = 8000)]
-    kelvin: u16,
+/// Display name for a [SceneMode] by its Wiz scene ID
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct SceneName {
+    /// Wiz scene ID, as sent to the bulb
+    id: u8,
+
+    /// Display name for the scene
+    name: String,
+
+    /// Menu grouping for the scene
+    category: SceneCategory,
 }
 
-impl Kelvin {
-    /// Create a new Kelvin setting with the default value
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use riz::models::Kelvin;
-    ///
-    /// assert_eq!(Kelvin::new().kelvin(), 1000);
-    /// ```
-    ///
-    pub fn new() -> Self {
-        Kelvin { kelvin: 1000 }
+impl SceneName {
+    /// Accessor for the Wiz scene ID
+    pub fn id(&self) -> u8 {
+        self.id
     }
 
-    /// Accessor for our read-only kelvin setting
-    pub fn kelvin(&self) -> u16 {
-        self.kelvin
+    /// Accessor for the scene's display name
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    /// Create a new Kelvin setting with the given value
-    ///
-    /// # Returns
-    ///   [Kelvin] when value is within the valid range
+    /// Accessor for the scene's menu grouping
+    pub fn category(&self) -> &SceneCategory {
+        &self.category
+    }
+
+    /// Group the scenes by category, keeping their order within each
     ///
     /// # Examples
     ///
     /// ```
-    /// use riz::models::Kelvin;
+    /// use riz::models::{SceneCategory, SceneName, SceneNames};
     ///
-    /// assert!(Kelvin::create(999).is_none());
-    /// assert!(Kelvin::create(1000).is_some());
-    /// assert!(Kelvin::create(8000).is_some());
-    /// assert!(Kelvin::create(8001).is_none());
+    /// let groups = SceneName::group(SceneNames::default().list());
+    /// let seasonal = &groups[&SceneCategory::Seasonal];
+    /// assert_eq!(seasonal.first().unwrap().name(), "Spring");
     /// ```
     ///
-    pub fn create(kelvin: u16) -> Option<Self> {
-        if (1000..=8000).contains(&kelvin) {
-            Some(Kelvin { kelvin })
-        } else {
-            None
+    pub fn group(scenes: Vec<SceneName>) -> BTreeMap<SceneCategory, Vec<SceneName>> {
+        let mut groups: BTreeMap<SceneCategory, Vec<SceneName>> = BTreeMap::new();
+        for scene in scenes {
+            groups
+                .entry(scene.category.clone())
+                .or_default()
+                .push(scene);
         }
+        groups
     }
 }
 
-/// White describes a cool or warm white mode, values from 1 to 100
-#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema)]
-pub struct White {
-    #[schema(minimum = 1, maximum = 100)]
-    value: u8,
+/// User provided display names for scenes, by Wiz scene ID
+///
+/// Loaded from the JSON file at `RIZ_SCENE_NAMES` (env var), if set.
+/// Scenes without an override use their built-in [SceneMode::title]
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use riz::models::{SceneMode, SceneNames};
+///
+/// let names = SceneNames::new(HashMap::from([(1, "Pool".to_string())]));
+/// assert_eq!(names.name(&SceneMode::Ocean), "Pool");
+/// assert_eq!(names.name(&SceneMode::Romance), "Romance");
+/// ```
+///
+#[derive(Debug, Default, Clone)]
+pub struct SceneNames {
+    names: HashMap<u8, String>,
+    favorites: Vec<u8>,
 }
 
-impl White {
-    /// Create a new white setting with the default value
-    pub fn new() -> Self {
-        White { value: 100 }
+impl SceneNames {
+    /// Create scene name overrides from a map of scene ID to name
+    pub fn new(names: HashMap<u8, String>) -> Self {
+        SceneNames {
+            names,
+            favorites: Vec::new(),
+        }
     }
 
-    /// Create a new white setting with the given value
-    ///
-    /// # Returns
-    ///   [White] if the value provided is within the valid range
-    ///
-    /// # Examples
+    /// Mark the scene IDs as favorites, see [Self::list_favorites]
+    pub fn with_favorites(mut self, favorites: Vec<u8>) -> Self {
+        self.favorites = favorites;
+        self
+    }
+
+    /// Load scene name overrides from the file at `RIZ_SCENE_NAMES`
     ///
-    /// ```
-    /// use riz::models::White;
+    /// The file should be a JSON object of scene ID to name, eg:
+    /// `{"1": "Pool", "6": "Reading"}`. If unset or invalid, no
+    /// overrides are used.
     ///
-    /// assert!(White::create(0).is_none());
-    /// assert!(White::create(1).is_some());
-    /// assert!(White::create(100).is_some());
-    /// assert!(White::create(101).is_none());
-    /// ```
+    /// Favorite scenes are loaded from `RIZ_FAVORITE_SCENES`, a comma
+    /// separated list of scene IDs, eg: `4,6,12`. Invalid IDs are ignored.
     ///
-    pub fn create(value: u8) -> Option<Self> {
-        if (1..=100).contains(&value) {
-            Some(White { value })
-        } else {
-            None
-        }
+    pub fn from_env() -> Self {
+        SceneNames::new(Self::names_from_env()).with_favorites(Self::favorites_from_env())
     }
-}
 
-/// Color is any RGB color, values from 0 to 255
-#[derive(Default, Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
-pub struct Color {
-    #[schema(maximum = 255)]
-    red: u8,
-    #[schema(maximum = 255)]
-    green: u8,
-    #[schema(maximum = 255)]
-    blue: u8,
-}
+    fn names_from_env() -> HashMap<u8, String> {
+        let path = match env::var(SCENE_NAMES_ENV_KEY) {
+            Ok(path) => path,
+            Err(_) => return HashMap::new(),
+        };
 
-impl Color {
-    /// Create a new default color
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use std::str::FromStr;
-    /// use riz::models::Color;
-    ///
-    /// assert_eq!(Color::new(), Color::from_str("0,0,0").unwrap());
-    /// assert_ne!(Color::new(), Color::from_str("0,1,0").unwrap());
-    /// ```
-    ///
-    pub fn new() -> Self {
-        Color {
-            red: 0,
-            green: 0,
-            blue: 0,
+        match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(names) => names,
+                Err(e) => {
+                    warn!("Failed to decode scene names from {}: {:?}", path, e);
+                    HashMap::new()
+                }
+            },
+            Err(e) => {
+                warn!("Failed to read scene names from {}: {:?}", path, e);
+                HashMap::new()
+            }
         }
     }
 
-    /// Accessor for this color's read-only red value
-    pub fn red(&self) -> u8 {
-        self.red
-    }
+    fn favorites_from_env() -> Vec<u8> {
+        let favorites = match env::var(FAVORITE_SCENES_ENV_KEY) {
+            Ok(favorites) => favorites,
+            Err(_) => return Vec::new(),
+        };
 
-    /// Accessor for this color's read-only green value
-    pub fn green(&self) -> u8 {
-        self.green
+        favorites
+            .split(',')
+            .filter_map(|id| match id.trim().parse::<u8>() {
+                Ok(id) if SceneMode::create(id).is_some() => Some(id),
+                _ => {
+                    warn!("Ignoring invalid favorite scene: {}", id);
+                    None
+                }
+            })
+            .collect()
     }
 
-    /// Accessor for this color's read-only blue value
-    pub fn blue(&self) -> u8 {
-        self.blue
+    /// Display name for the scene, falling back to the built-in title
+    pub fn name(&self, scene: &SceneMode) -> String {
+        match self.names.get(&(scene.clone() as u8)) {
+            Some(name) => name.clone(),
+            None => scene.title(),
+        }
     }
-}
 
-impl FromStr for Color {
-    type Err = String;
+    /// List all scenes with their display names, in ID order
+    pub fn list(&self) -> Vec<SceneName> {
+        self.list_filtered(|_| true)
+    }
 
-    /// Create a new Color from a string slice
+    /// List scenes a bulb with the given `moduleName` can play, in ID order
     ///
-    /// Expected format is r,g,b where each value can be 0-255,
-    /// values outside this range will be converted to zero.
+    /// # Examples
     ///
-    /// Examples:
+    /// ```
+    /// use riz::models::SceneNames;
     ///
+    /// let names = SceneNames::default();
+    /// assert_eq!(names.list_supported("ESP01_SHRGB_03").len(), names.list().len());
+    /// assert_eq!(names.list_supported("ESP06_SHDW9_01").len(), 8);
     /// ```
-    /// use std::str::FromStr;
-    /// use riz::models::Color;
     ///
-    /// assert!(Color::from_str("100,80,240").is_ok());
-    /// assert!(Color::from_str("100,80,240,255").is_err());
-    /// assert!(Color::from_str("#ffeeff").is_err());
+    pub fn list_supported(&self, module_name: &str) -> Vec<SceneName> {
+        self.list_filtered(|scene| scene.supported_by(module_name))
+    }
+
+    /// List only the favorite scenes, in ID order
+    ///
+    /// # Examples
     ///
-    /// assert_eq!(
-    ///   Color::from_str("1000,-2,256").unwrap(),
-    ///   Color::from_str("0,0,0").unwrap()
-    /// );
     /// ```
+    /// use riz::models::SceneNames;
     ///
-    fn from_str(s: &str) -> StdResult<Self, String> {
-        let parts: Vec<_> = s.split(',').map(|c| c.parse::<u8>().unwrap_or(0)).collect();
+    /// let names = SceneNames::default().with_favorites(vec![12, 4]);
+    /// let ids: Vec<u8> = names.list_favorites().iter().map(|s| s.id()).collect();
+    /// assert_eq!(ids, vec![4, 12]);
+    /// ```
+    ///
+    pub fn list_favorites(&self) -> Vec<SceneName> {
+        self.list_filtered(|scene| self.favorites.contains(&(scene.clone() as u8)))
+    }
 
-        if parts.len() == 3 {
-            Ok(Color {
-                red: parts[0],
-                green: parts[1],
-                blue: parts[2],
+    fn list_filtered(&self, filter: impl Fn(&SceneMode) -> bool) -> Vec<SceneName> {
+        SceneMode::iter()
+            .filter(|scene| filter(scene))
+            .map(|scene| SceneName {
+                id: scene.clone() as u8,
+                name: self.name(&scene),
+                category: scene.category(),
             })
-        } else {
-            Err("Invalid color string".to_string())
-        }
+            .collect()
     }
 }
 
-/// API request for a lighting settings change on a [Light]
-#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
-pub struct LightRequest {
-    // brightness percent, valid from 10 to 100
-    // to be used with setbrightness --dim <value>
-    brightness: Option<Brightness>,
-
-    // set the rgb color value, valid from 0 to 255
-    // to be used with setrgbcolor --r <r> --g <g> --b <b>
-    color: Option<Color>,
-
-    // Color changing speed, from 20 to 200 (time %)
-    // to be used with setspeed --speed <value>
-    speed: Option<Speed>,
-
-    // Color temperature, in kelvins from 1000 to 8000
-    // to be used with setcolortemp --temp <value>
-    temp: Option<Kelvin>,
-
-    // Scene to select, from enum
-    // to be used with setscene --scene <value>
-    scene: Option<SceneMode>,
-
-    // If we would like to adjust the light's power
-    power: Option<PowerMode>,
-
-    // If we'd like to set the cool white value
-    cool: Option<White>,
+/// Which fields differ between two [LightStatus], see [LightStatus::diff]
+#[derive(Default, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub struct StatusDiff {
+    /// If the bulb started or stopped emitting
+    pub emitting: bool,
 
-    // If we'd like to set the warm white value
-    warm: Option<White>,
-}
+    /// If the brightness changed
+    pub brightness: bool,
 
-impl LightRequest {
-    /// Accessor to get this request's optional [PowerMode] setting
-    pub fn power(&self) -> Option<&PowerMode> {
-        self.power.as_ref()
-    }
-}
+    /// If the color changed
+    pub color: bool,
 
-/// Describes a potential emitting state of a [Light]
-#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
-pub enum PowerMode {
-    /// Send a reboot command to the light
-    Reboot,
+    /// If the scene changed, known or not
+    pub scene: bool,
 
-    /// Tell the bulb to emit light
-    On,
+    /// If the speed changed
+    pub speed: bool,
 
-    /// Tell the bulb to stop emitting light
-    Off,
-}
+    /// If the temperature changed
+    pub temp: bool,
 
-/// Preset lighting modes
-#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, EnumIter, PartialEq)]
-pub enum SceneMode {
-    Ocean = 1,
-    Romance = 2,
-    Sunset = 3,
-    Party = 4,
-    Fireplace = 5,
-    Cozy = 6,
-    Forest = 7,
-    PastelColors = 8,
-    WakeUp = 9,
-    Bedtime = 10,
-    WarmWhite = 11,
-    Daylight = 12,
-    CoolWhite = 13,
-    NightLight = 14,
-    Focus = 15,
-    Relax = 16,
-    TrueColors = 17,
-    TvTime = 18,
-    Plantgrowth = 19,
-    Spring = 20,
-    Summer = 21,
-    Fall = 22,
-    Deepdive = 23,
-    Jungle = 24,
-    Mojito = 25,
-    Club = 26,
-    Christmas = 27,
-    Halloween = 28,
-    Candlelight = 29,
-    GoldenWhite = 30,
-    Pulse = 31,
-    Steampunk = 32,
-    Diwali = 33,
+    /// If the cool white value changed
+    pub cool: bool,
+
+    /// If the warm white value changed
+    pub warm: bool,
+
+    /// If the last set context changed
+    pub last: bool,
 }
 
-impl SceneMode {
-    pub fn create(value: u8) -> Option<Self> {
-        // this is suboptimal...
-        SceneMode::iter().find(|scene| scene.clone() as u8 == value)
+impl StatusDiff {
+    /// Check if nothing changed
+    pub fn is_empty(&self) -> bool {
+        *self == StatusDiff::default()
     }
 }
 
@@ -949,7 +3956,7 @@ impl SceneMode {
 /// This could potentially still be wrong, the API is not the only
 /// way to change state on the bulbs, and we don't monitor/poll...
 ///
-#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq, Eq)]
 pub enum LastSet {
     /// The last set context was an RGB color
     Color,
@@ -995,7 +4002,7 @@ impl LastSet {
 /// for all potential contexts, while also displaying the active context.
 ///
 #[serde_with::skip_serializing_none]
-#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
 pub struct LightStatus {
     /// Current color, if set
     color: Option<Color>,
@@ -1009,6 +4016,10 @@ pub struct LightStatus {
     /// Currently playing scene, if any
     scene: Option<SceneMode>,
 
+    /// Scene ID reported by the bulb which isn't a known [SceneMode],
+    /// likely from newer firmware
+    unknown_scene: Option<u8>,
+
     /// Last set speed value, if known
     speed: Option<Speed>,
 
@@ -1023,9 +4034,51 @@ pub struct LightStatus {
 
     /// Last set value, if any
     last: Option<LastSet>,
+
+    /// Any other values reported by the bulb which aren't modeled above
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    #[schema(value_type = Object)]
+    extra: Map<String, Value>,
+
+    /// If a status read found the light changed outside of Riz
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    drift: bool,
+
+    /// If the light didn't reply to the latest status read, and this is
+    /// its last known status instead
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    stale: bool,
 }
 
 impl LightStatus {
+    /// Parse a `syncPilot` message pushed by a registered bulb
+    ///
+    /// # Returns
+    ///   [None] if the message isn't a `syncPilot` status
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::LightStatus;
+    ///
+    /// let msg = br#"{"method":"syncPilot","env":"pro","params":{
+    ///     "mac":"aabbccddeeff","rssi":-60,"src":"udp","state":true,
+    ///     "sceneId":0,"temp":2700,"dimming":50}}"#;
+    /// let status = LightStatus::from_sync_pilot(msg).unwrap();
+    /// assert!(status.emitting());
+    /// assert_eq!(status.brightness().unwrap().value(), 50);
+    ///
+    /// assert!(LightStatus::from_sync_pilot(br#"{"method":"getPilot"}"#).is_none());
+    /// ```
+    ///
+    pub fn from_sync_pilot(raw: &[u8]) -> Option<Self> {
+        let push: BulbPush = serde_json::from_slice(raw).ok()?;
+        if push.method != WizProtocol::SYNC_PILOT {
+            return None;
+        }
+        Some(LightStatus::from(&push.params))
+    }
+
     /// Accessor to get the last set context by reference
     pub fn last(&self) -> Option<&LastSet> {
         self.last.as_ref()
@@ -1051,6 +4104,11 @@ impl LightStatus {
         self.scene.as_ref()
     }
 
+    /// Accessor for a playing scene ID which isn't a known [SceneMode]
+    pub fn unknown_scene(&self) -> Option<u8> {
+        self.unknown_scene
+    }
+
     /// Accessor to get the last set speed value by reference
     pub fn speed(&self) -> Option<&Speed> {
         self.speed.as_ref()
@@ -1071,6 +4129,82 @@ impl LightStatus {
         self.warm.as_ref()
     }
 
+    /// Accessor to get any unmodeled values last reported by the bulb
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+
+    /// Check if the light was changed outside of Riz since the last
+    /// setting Riz sent it (eg, by the wall switch or the Wiz app)
+    pub fn drift(&self) -> bool {
+        self.drift
+    }
+
+    /// Check if this is the last known status of a light which didn't
+    /// reply to the latest status read, see [Room::read_statuses]
+    pub fn stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Check if a fresh status read disagrees with this (stored) status
+    ///
+    /// Only values the bulb reports and Riz tracks are compared, that's
+    /// if it's emitting, and its color when both have one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use riz::models::{Color, LightStatus, Payload, PowerMode};
+    ///
+    /// let known = LightStatus::from(&Payload::from(&Color::from_str("255,0,0").unwrap()));
+    /// let fresh = LightStatus::from(&Payload::from(&Color::from_str("0,0,255").unwrap()));
+    /// assert!(known.diverges(&fresh));
+    /// assert!(!known.diverges(&known.clone()));
+    ///
+    /// assert!(known.diverges(&LightStatus::from(&PowerMode::Off)));
+    /// ```
+    ///
+    pub fn diverges(&self, fresh: &LightStatus) -> bool {
+        if self.emitting != fresh.emitting {
+            return true;
+        }
+        matches!((&self.color, &fresh.color), (Some(known), Some(color)) if known != color)
+    }
+
+    /// Compare with another status, field by field
+    ///
+    /// Unlike [Self::diverges] every modeled field is compared, a field
+    /// which is known in only one of the statuses counts as changed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{Brightness, LightStatus, Payload, SceneMode};
+    ///
+    /// let cozy = LightStatus::from(&Payload::from(&SceneMode::Cozy));
+    /// assert!(cozy.diff(&cozy).is_empty());
+    ///
+    /// let dim = LightStatus::from(&Payload::from(&Brightness::create(20).unwrap()));
+    /// let diff = cozy.diff(&dim);
+    /// assert!(diff.scene && diff.brightness && diff.last);
+    /// assert!(!diff.emitting && !diff.color);
+    /// ```
+    ///
+    pub fn diff(&self, other: &Self) -> StatusDiff {
+        StatusDiff {
+            emitting: self.emitting != other.emitting,
+            brightness: self.brightness != other.brightness,
+            color: self.color != other.color,
+            scene: self.scene != other.scene || self.unknown_scene != other.unknown_scene,
+            speed: self.speed != other.speed,
+            temp: self.temp != other.temp,
+            cool: self.cool != other.cool,
+            warm: self.warm != other.warm,
+            last: self.last != other.last,
+        }
+    }
+
     /// Update this status with the values from the other
     ///
     /// Any values set in other become set in self, otherwise
@@ -1099,6 +4233,7 @@ impl LightStatus {
         }
         self.emitting = other.emitting;
         self.scene.clone_from(&other.scene);
+        self.unknown_scene = other.unknown_scene;
         if let Some(speed) = &other.speed {
             self.speed = Some(speed.clone());
         }
@@ -1114,9 +4249,14 @@ impl LightStatus {
         if let Some(last) = &other.last {
             self.last = Some(last.clone());
         }
+        if !other.extra.is_empty() {
+            self.extra.clone_from(&other.extra);
+        }
+        self.stale = false;
     }
 
     fn update_from_payload(&mut self, payload: &Payload) {
+        self.drift = false;
         if let Some(color) = payload.get_color() {
             self.color = Some(color);
             self.last = Some(LastSet::Color);
@@ -1133,6 +4273,7 @@ impl LightStatus {
         }
         if let Some(scene) = payload.scene {
             self.scene = SceneMode::create(scene);
+            self.unknown_scene = (scene != 0 && self.scene.is_none()).then_some(scene);
             self.last = Some(LastSet::Scene);
         }
         if let Some(cool) = payload.cool {
@@ -1146,6 +4287,7 @@ impl LightStatus {
     }
 
     fn update_from_power(&mut self, power: &PowerMode) {
+        self.drift = false;
         match power {
             PowerMode::Off => self.emitting = false,
             _ => self.emitting = true,
@@ -1153,6 +4295,74 @@ impl LightStatus {
     }
 }
 
+impl fmt::Display for LightStatus {
+    /// One-line summary of the status, showing the last set context
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{Brightness, Color, LightStatus, Payload, SceneMode};
+    ///
+    /// let mut payload = Payload::from(&SceneMode::Cozy);
+    /// payload.brightness(&Brightness::create(80).unwrap());
+    /// let status = LightStatus::from(&payload);
+    /// assert_eq!(status.to_string(), "on · 80% · scene=Cozy");
+    ///
+    /// let status = LightStatus::from(&Payload::from(&Color::rgb(255, 0, 0)));
+    /// assert_eq!(status.to_string(), "on · color=255,0,0");
+    /// ```
+    ///
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", if self.emitting { "on" } else { "off" })?;
+
+        if let Some(brightness) = &self.brightness {
+            write!(f, " · {}%", brightness.value)?;
+        }
+
+        let last = match &self.last {
+            Some(last) => Some(last.clone()),
+            None if self.scene.is_some() => Some(LastSet::Scene),
+            None if self.color.is_some() => Some(LastSet::Color),
+            None => None,
+        };
+
+        match last {
+            Some(LastSet::Color) => {
+                if let Some(color) = &self.color {
+                    write!(f, " · color={},{},{}", color.red, color.green, color.blue)?;
+                }
+            }
+            Some(LastSet::Scene) => {
+                if let Some(scene) = &self.scene {
+                    write!(f, " · scene={:?}", scene)?;
+                }
+            }
+            Some(LastSet::Temp) => {
+                if let Some(temp) = &self.temp {
+                    write!(f, " · temp={}K", temp.kelvin)?;
+                }
+            }
+            Some(LastSet::Cool) => {
+                if let Some(cool) = &self.cool {
+                    write!(f, " · cool={}", cool.value)?;
+                }
+            }
+            Some(LastSet::Warm) => {
+                if let Some(warm) = &self.warm {
+                    write!(f, " · warm={}", warm.value)?;
+                }
+            }
+            None => {}
+        }
+
+        if let Some(id) = self.unknown_scene {
+            write!(f, " · scene={} (unknown)", id)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl From<&Payload> for LightStatus {
     fn from(payload: &Payload) -> Self {
         let color = payload.get_color();
@@ -1168,6 +4378,7 @@ impl From<&Payload> for LightStatus {
         } else {
             None
         };
+        let unknown_scene = payload.scene.filter(|id| *id != 0 && scene.is_none());
 
         let speed = if let Some(speed) = payload.speed {
             Speed::create(speed)
@@ -1203,6 +4414,10 @@ impl From<&Payload> for LightStatus {
             cool,
             warm,
             last: LastSet::from(payload),
+            extra: Map::new(),
+            unknown_scene,
+            drift: false,
+            stale: false,
         }
     }
 }
@@ -1219,14 +4434,22 @@ impl From<&PowerMode> for LightStatus {
             cool: None,
             warm: None,
             last: None,
+            extra: Map::new(),
+            unknown_scene: None,
+            drift: false,
+            stale: false,
         }
     }
 }
 
 impl From<&BulbStatus> for LightStatus {
     fn from(bulb: &BulbStatus) -> Self {
-        let res = &bulb.result;
+        LightStatus::from(&bulb.result)
+    }
+}
 
+impl From<&BulbStatusResult> for LightStatus {
+    fn from(res: &BulbStatusResult) -> Self {
         LightStatus {
             color: res.get_color(),
             brightness: Brightness::create(res.dimming.unwrap_or(0)),
@@ -1234,11 +4457,19 @@ impl From<&BulbStatus> for LightStatus {
             warm: White::create(res.warm.unwrap_or(0)),
             emitting: res.emitting,
             scene: SceneMode::create(res.scene),
+            unknown_scene: match res.scene {
+                0 => None,
+                id if SceneMode::create(id).is_none() => Some(id),
+                _ => None,
+            },
             // NB: these are not returned from getPilot...
             //     best we can do is track what we set then
             speed: None,
             temp: None,
             last: None,
+            extra: res.extra.clone(),
+            drift: false,
+            stale: false,
         }
     }
 }
@@ -1258,6 +4489,13 @@ struct BulbStatus {
     result: BulbStatusResult,
 }
 
+/// Status pushed by a registered bulb, see [LightStatus::from_sync_pilot]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct BulbPush {
+    method: String,
+    params: BulbStatusResult,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct BulbStatusResult {
     /// red (0-255)
@@ -1296,6 +4534,10 @@ struct BulbStatusResult {
     /// bulb's warm white value
     #[serde(rename = "w")]
     warm: Option<u8>,
+
+    /// any other values reported, depending on model and firmware
+    #[serde(flatten)]
+    extra: Map<String, Value>,
 }
 
 impl BulbStatusResult {
@@ -1308,6 +4550,308 @@ impl BulbStatusResult {
     }
 }
 
+/// System configuration, as reported by the bulb from `getSystemConfig`
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemConfig {
+    /// Bulb wifi mac address
+    mac: Option<String>,
+
+    /// Hardware module name, describes the bulb's model and capabilities
+    module_name: Option<String>,
+
+    /// Firmware version string
+    fw_version: Option<String>,
+}
+
+impl SystemConfig {
+    /// Accessor for the bulb's mac address, if reported
+    pub fn mac(&self) -> Option<&str> {
+        self.mac.as_deref()
+    }
+
+    /// Accessor for the bulb's module name, if reported
+    pub fn module_name(&self) -> Option<&str> {
+        self.module_name.as_deref()
+    }
+
+    /// Accessor for the bulb's firmware version, if reported
+    pub fn fw_version(&self) -> Option<&str> {
+        self.fw_version.as_deref()
+    }
+
+    /// Check if the module name describes a full color (`RGB`) bulb
+    ///
+    /// Only the bulb type part of the module name is checked, see
+    /// [SceneMode::supported_by]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::SystemConfig;
+    ///
+    /// let config: SystemConfig = serde_json::from_str(
+    ///     r#"{"moduleName": "ESP01_SHRGB_03"}"#
+    /// ).unwrap();
+    /// assert!(config.rgb());
+    ///
+    /// let config: SystemConfig = serde_json::from_str(
+    ///     r#"{"moduleName": "RGB01_SHTW1C_31"}"#
+    /// ).unwrap();
+    /// assert!(!config.rgb());
+    /// ```
+    ///
+    pub fn rgb(&self) -> bool {
+        self.module_name()
+            .map_or(false, |name| bulb_type(name).contains("RGB"))
+    }
+
+    /// Check if the module name describes a bulb which takes cool & warm white
+    ///
+    /// Tunable white (`TW`) bulbs do, as do full color (`RGB`) bulbs which
+    /// drive cool & warm white LEDs alongside their color. Dimmable white
+    /// (`DW`) bulbs, sockets and modules named outside the
+    /// `chip_type_revision` scheme don't. Only the bulb type part of the
+    /// module name is checked, this is a naming convention rather than a
+    /// capability the bulb reports.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::SystemConfig;
+    ///
+    /// let config: SystemConfig = serde_json::from_str(
+    ///     r#"{"moduleName": "ESP01_SHTW1C_31"}"#
+    /// ).unwrap();
+    /// assert!(config.tunable_white());
+    ///
+    /// let config: SystemConfig = serde_json::from_str(
+    ///     r#"{"moduleName": "ESP01_SHRGB_03"}"#
+    /// ).unwrap();
+    /// assert!(config.tunable_white());
+    ///
+    /// let config: SystemConfig = serde_json::from_str(
+    ///     r#"{"moduleName": "ESP06_SHDW9_01"}"#
+    /// ).unwrap();
+    /// assert!(!config.tunable_white());
+    ///
+    /// let config: SystemConfig = serde_json::from_str(
+    ///     r#"{"moduleName": "ESPTW_SHDW9_01"}"#
+    /// ).unwrap();
+    /// assert!(!config.tunable_white());
+    /// ```
+    ///
+    pub fn tunable_white(&self) -> bool {
+        self.module_name().map_or(false, |name| {
+            let bulb_type = bulb_type(name);
+            bulb_type.contains("TW") || bulb_type.contains("RGB")
+        })
+    }
+
+    /// Parse the bulb's firmware version, if reported and readable
+    pub fn firmware(&self) -> Option<FirmwareVersion> {
+        self.fw_version().and_then(|v| v.parse().ok())
+    }
+
+    /// Check if the bulb's firmware supports the feature
+    ///
+    /// Bulbs with an unknown or unreadable firmware version are treated
+    /// as not supporting any [FirmwareFeature]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{FirmwareFeature, SystemConfig};
+    ///
+    /// let config: SystemConfig = serde_json::from_str(r#"{"fwVersion": "1.22.0"}"#).unwrap();
+    /// assert!(config.supports(FirmwareFeature::Transitions));
+    /// assert!(!config.supports(FirmwareFeature::ExtendedScenes));
+    ///
+    /// let config: SystemConfig = serde_json::from_str("{}").unwrap();
+    /// assert!(!config.supports(FirmwareFeature::DualWhite));
+    /// ```
+    ///
+    pub fn supports(&self, feature: FirmwareFeature) -> bool {
+        self.firmware()
+            .map_or(false, |version| version >= feature.min_version())
+    }
+
+    /// Check the bulb's firmware supports the feature, see [Self::supports]
+    ///
+    /// # Errors
+    ///   [Error::UnsupportedFirmware] naming the feature and firmware
+    ///
+    pub fn require(&self, feature: FirmwareFeature) -> Result<()> {
+        if self.supports(feature) {
+            Ok(())
+        } else {
+            Err(Error::unsupported_firmware(
+                &format!("{:?}", feature),
+                self.fw_version().unwrap_or("unknown"),
+            ))
+        }
+    }
+}
+
+/// Wiz firmware version, reported as `x.y.z`, comparable by each part
+///
+/// # Examples
+///
+/// ```
+/// use riz::models::FirmwareVersion;
+///
+/// let version: FirmwareVersion = "1.22.0".parse().unwrap();
+/// assert_eq!(version, FirmwareVersion::new(1, 22, 0));
+/// assert!(version > "1.9.12".parse().unwrap());
+/// assert_eq!(version.to_string(), "1.22.0");
+///
+/// assert!("1.22".parse::<FirmwareVersion>().is_err());
+/// assert!("1.x.0".parse::<FirmwareVersion>().is_err());
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FirmwareVersion {
+    major: u16,
+    minor: u16,
+    patch: u16,
+}
+
+impl FirmwareVersion {
+    /// Create a version from its parts
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+        FirmwareVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for FirmwareVersion {
+    type Err = String;
+
+    /// Parse `x.y.z`, surrounding whitespace is ignored
+    fn from_str(s: &str) -> StdResult<Self, String> {
+        let parts: Vec<_> = s.trim().split('.').map(|p| p.parse::<u16>()).collect();
+        match parts.as_slice() {
+            [Ok(major), Ok(minor), Ok(patch)] => Ok(FirmwareVersion::new(*major, *minor, *patch)),
+            _ => Err(format!("Invalid firmware version: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Bulb features which depend on the firmware version, see [SystemConfig::supports]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirmwareFeature {
+    /// Fading between settings over a transition time
+    Transitions,
+
+    /// Scenes added after the original 32
+    ExtendedScenes,
+
+    /// Setting cool & warm white channels together
+    DualWhite,
+}
+
+impl FirmwareFeature {
+    /// Oldest firmware known to support this feature
+    pub fn min_version(&self) -> FirmwareVersion {
+        match self {
+            FirmwareFeature::Transitions => FirmwareVersion::new(1, 22, 0),
+            FirmwareFeature::ExtendedScenes => FirmwareVersion::new(1, 25, 0),
+            FirmwareFeature::DualWhite => FirmwareVersion::new(1, 16, 0),
+        }
+    }
+}
+
+/// What a bulb can do, derived from its [SystemConfig]
+///
+/// Cached on the [Light] after its system config is first fetched, so
+/// the bulb doesn't need to be probed again
+///
+/// # Examples
+///
+/// ```
+/// use riz::models::{BulbCapabilities, SystemConfig};
+///
+/// let config: SystemConfig = serde_json::from_str(
+///     r#"{"moduleName": "ESP01_SHRGB_03"}"#
+/// ).unwrap();
+/// let caps = BulbCapabilities::from(&config);
+/// assert!(caps.rgb());
+/// assert!(caps.tunable_white());
+/// assert!(caps.dimmable());
+///
+/// let config: SystemConfig = serde_json::from_str(
+///     r#"{"moduleName": "ESP10_SOCKET_06"}"#
+/// ).unwrap();
+/// let caps = BulbCapabilities::from(&config);
+/// assert!(!caps.rgb());
+/// assert!(!caps.dimmable());
+/// ```
+///
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct BulbCapabilities {
+    /// Hardware module name the capabilities were derived from
+    module_name: Option<String>,
+
+    /// Bulb can set an RGB color
+    rgb: bool,
+
+    /// Bulb takes cool & warm white, see [SystemConfig::tunable_white]
+    tunable_white: bool,
+
+    /// Bulb brightness can be set
+    dimmable: bool,
+}
+
+impl BulbCapabilities {
+    /// Accessor for the module name the capabilities were derived from
+    pub fn module_name(&self) -> Option<&str> {
+        self.module_name.as_deref()
+    }
+
+    /// Check if the bulb can set an RGB color
+    pub fn rgb(&self) -> bool {
+        self.rgb
+    }
+
+    /// Check if the bulb takes cool & warm white, see [SystemConfig::tunable_white]
+    pub fn tunable_white(&self) -> bool {
+        self.tunable_white
+    }
+
+    /// Check if the bulb brightness can be set
+    pub fn dimmable(&self) -> bool {
+        self.dimmable
+    }
+}
+
+impl From<&SystemConfig> for BulbCapabilities {
+    fn from(config: &SystemConfig) -> Self {
+        let module_name = config.module_name();
+        BulbCapabilities {
+            module_name: module_name.map(String::from),
+            rgb: config.rgb(),
+            tunable_white: config.tunable_white(),
+            dimmable: !module_name.map_or(false, |name| bulb_type(name).contains("SOCKET")),
+        }
+    }
+}
+
+/// Envelope of the bulb's reply to `getSystemConfig`
+#[derive(Debug, Deserialize)]
+struct BulbConfig {
+    result: SystemConfig,
+}
+
 /// Response which could alter the state of a [Light]
 ///
 /// Used with [Light::process_reply] or [Room::process_reply]. Or use
@@ -1343,6 +4887,24 @@ impl LightingResponse {
             response: LightingResponseType::Status(status),
         }
     }
+
+    /// Create a [LightingResponse] for a [Ipv4Addr] from a [SystemConfig]
+    pub fn config(ip: Ipv4Addr, config: SystemConfig) -> Self {
+        LightingResponse {
+            ip,
+            response: LightingResponseType::Config(config),
+        }
+    }
+
+    /// Accessor for the IP of the light which replied
+    pub fn ip(&self) -> Ipv4Addr {
+        self.ip
+    }
+
+    /// Accessor for the details of the reply
+    pub fn response(&self) -> &LightingResponseType {
+        &self.response
+    }
 }
 
 /// Reply path payload details for modifying [Light] state
@@ -1356,6 +4918,122 @@ pub enum LightingResponseType {
 
     /// Response from a bulb status fetch
     Status(LightStatus),
+
+    /// Response from a bulb system config fetch
+    Config(SystemConfig),
+}
+
+/// Tracks the per-light outcome of a [LightRequest] sent to many lights
+///
+/// # Examples
+///
+/// ```
+/// use uuid::Uuid;
+/// use riz::models::{Operation, OperationStatus};
+///
+/// let light_id = Uuid::new_v4();
+/// let mut op = Operation::new(&[light_id]);
+/// assert!(!op.is_complete());
+///
+/// op.complete(&light_id, None);
+/// assert!(op.is_complete());
+/// assert_eq!(op.lights()[&light_id].status(), &OperationStatus::Ok);
+/// ```
+///
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct Operation {
+    /// Outcome of the request for each light, by light ID
+    lights: HashMap<Uuid, LightOutcome>,
+}
+
+impl Operation {
+    /// Create a new operation with all lights pending
+    pub fn new(lights: &[Uuid]) -> Self {
+        Operation {
+            lights: lights
+                .iter()
+                .map(|id| (*id, LightOutcome::pending()))
+                .collect(),
+        }
+    }
+
+    /// Accessor for the outcome of each light, by light ID
+    pub fn lights(&self) -> &HashMap<Uuid, LightOutcome> {
+        &self.lights
+    }
+
+    /// Record the outcome for a light, with an error message on failure
+    pub fn complete(&mut self, light_id: &Uuid, error: Option<String>) {
+        if let Some(outcome) = self.lights.get_mut(light_id) {
+            outcome.status = if error.is_some() {
+                OperationStatus::Error
+            } else {
+                OperationStatus::Ok
+            };
+            outcome.error = error;
+        }
+    }
+
+    /// Record that the request was not sent to a light
+    pub fn skip(&mut self, light_id: &Uuid) {
+        if let Some(outcome) = self.lights.get_mut(light_id) {
+            outcome.status = OperationStatus::Skipped;
+            outcome.error = None;
+        }
+    }
+
+    /// Check if every light in this operation has an outcome
+    pub fn is_complete(&self) -> bool {
+        self.lights
+            .values()
+            .all(|outcome| outcome.status != OperationStatus::Pending)
+    }
+}
+
+/// Outcome of an [Operation] for a single [Light]
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct LightOutcome {
+    /// Current status of the request for this light
+    status: OperationStatus,
+
+    /// Error message, if the request failed
+    error: Option<String>,
+}
+
+impl LightOutcome {
+    fn pending() -> Self {
+        LightOutcome {
+            status: OperationStatus::Pending,
+            error: None,
+        }
+    }
+
+    /// Accessor for the status of the request for this light
+    pub fn status(&self) -> &OperationStatus {
+        &self.status
+    }
+
+    /// Accessor for the error message, if the request failed
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Describes the progress of an [Operation] for a single [Light]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema, PartialEq)]
+pub enum OperationStatus {
+    /// The request was dispatched, but the bulb has not replied yet
+    Pending,
+
+    /// The bulb acknowledged the request
+    Ok,
+
+    /// The request could not be dispatched, or the bulb failed to reply
+    Error,
+
+    /// The request was not sent, the bulb was off (see `only_if_on`)
+    Skipped,
 }
 
 /// JSON payload to send at Wiz lights to modify their settings
@@ -1385,9 +5063,53 @@ pub struct Payload {
     cool: Option<u8>,
     #[serde(rename = "w")]
     warm: Option<u8>,
+
+    #[serde(rename = "fanState")]
+    fan_state: Option<u8>,
+    #[serde(rename = "fanSpeed")]
+    fan_speed: Option<u8>,
+
+    ratio: Option<u8>,
 }
 
 impl Payload {
+    /// Split a scene & brightness payload into two payloads
+    ///
+    /// The first has everything but the brightness, the second has only
+    /// the brightness. See [Light::set_timed] for why this is useful.
+    ///
+    /// # Returns
+    ///   [None] unless the payload has both a scene and brightness
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{Brightness, Payload, SceneMode};
+    ///
+    /// let mut payload = Payload::from(&SceneMode::Cozy);
+    /// assert!(payload.split_dimming().is_none());
+    ///
+    /// payload.brightness(&Brightness::create(50).unwrap());
+    /// let (scene, dimming) = payload.split_dimming().unwrap();
+    /// assert_eq!(serde_json::to_string(&scene).unwrap(), r#"{"sceneId":6}"#);
+    /// assert_eq!(serde_json::to_string(&dimming).unwrap(), r#"{"dimming":50}"#);
+    /// ```
+    ///
+    pub fn split_dimming(&self) -> Option<(Payload, Payload)> {
+        if self.scene.is_none() || self.dimming.is_none() {
+            return None;
+        }
+
+        let mut scene = self.clone();
+        scene.dimming = None;
+
+        let dimming = Payload {
+            dimming: self.dimming,
+            ..Default::default()
+        };
+        Some((scene, dimming))
+    }
+
     /// Create a new blank payload
     ///
     /// Note that at least one helper method must be called if creating a
@@ -1418,7 +5140,39 @@ impl Payload {
             blue: None,
             cool: None,
             warm: None,
+            fan_state: None,
+            fan_speed: None,
+            ratio: None,
+        }
+    }
+
+    /// Check the payload's settings make sense together
+    ///
+    /// Bulbs sent an RGB color with cool or warm white only apply one of
+    /// them, so the combination is refused rather than guessed at
+    ///
+    /// # Errors
+    ///   [Error::ColorWithWhite] if any RGB channel is set with cool or warm white
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use riz::models::{Color, Payload, White};
+    ///
+    /// let mut payload = Payload::from(&Color::from_str("255,0,0").unwrap());
+    /// assert!(payload.validate_semantics().is_ok());
+    ///
+    /// payload.warm(&White::create(50).unwrap());
+    /// assert!(payload.validate_semantics().is_err());
+    /// ```
+    ///
+    pub fn validate_semantics(&self) -> Result<()> {
+        let rgb = self.red.is_some() || self.green.is_some() || self.blue.is_some();
+        if rgb && (self.cool.is_some() || self.warm.is_some()) {
+            return Err(Error::ColorWithWhite);
         }
+        Ok(())
     }
 
     /// Checks if this payload is valid
@@ -1447,6 +5201,9 @@ impl Payload {
             || (self.red.is_some() && self.green.is_some() && self.blue.is_some())
             || self.cool.is_some()
             || self.warm.is_some()
+            || self.fan_state.is_some()
+            || self.fan_speed.is_some()
+            || self.ratio.is_some()
     }
 
     /// Set the SceneMode to use in this payload, by reference
@@ -1594,6 +5351,98 @@ impl Payload {
         self.warm = Some(warm.value);
     }
 
+    /// Create a payload setting only the cool white value
+    ///
+    /// There's no `From<&White>`, as it couldn't tell cool from warm
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{Payload, White};
+    ///
+    /// let payload = Payload::cool_white(&White::create(50).unwrap());
+    /// let json = serde_json::to_value(&payload).unwrap();
+    /// assert_eq!(json, serde_json::json!({"c": 50}));
+    /// ```
+    ///
+    pub fn cool_white(cool: &White) -> Self {
+        let mut p = Payload::new();
+        p.cool(cool);
+        p
+    }
+
+    /// Create a payload setting only the warm white value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{Payload, White};
+    ///
+    /// let payload = Payload::warm_white(&White::create(50).unwrap());
+    /// let json = serde_json::to_value(&payload).unwrap();
+    /// assert_eq!(json, serde_json::json!({"w": 50}));
+    /// ```
+    ///
+    pub fn warm_white(warm: &White) -> Self {
+        let mut p = Payload::new();
+        p.warm(warm);
+        p
+    }
+
+    /// Set the fan power state in this payload
+    ///
+    /// Only fan fixtures will act on this, standard bulbs ignore it
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::Payload;
+    ///
+    /// let mut payload = Payload::new();
+    /// payload.fan_state(true);
+    /// assert_eq!(payload.is_valid(), true);
+    /// ```
+    ///
+    pub fn fan_state(&mut self, on: bool) {
+        self.fan_state = Some(on as u8);
+    }
+
+    /// Set the fan speed value in this payload, by reference
+    ///
+    /// Only fan fixtures will act on this, standard bulbs ignore it
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{FanSpeed, Payload};
+    ///
+    /// let mut payload = Payload::new();
+    /// payload.fan_speed(&FanSpeed::create(3).unwrap());
+    /// assert_eq!(payload.is_valid(), true);
+    /// ```
+    ///
+    pub fn fan_speed(&mut self, fan_speed: &FanSpeed) {
+        self.fan_speed = Some(fan_speed.value);
+    }
+
+    /// Set the zone ratio value in this payload, by reference
+    ///
+    /// Only dual-head fixtures will act on this, standard bulbs ignore it
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::models::{Payload, Ratio};
+    ///
+    /// let mut payload = Payload::new();
+    /// payload.ratio(&Ratio::create(25).unwrap());
+    /// assert_eq!(payload.is_valid(), true);
+    /// ```
+    ///
+    pub fn ratio(&mut self, ratio: &Ratio) {
+        self.ratio = Some(ratio.value);
+    }
+
     /// Helper method to create a color when we have one set
     fn get_color(&self) -> Option<Color> {
         if let (Some(red), Some(green), Some(blue)) = (self.red, self.green, self.blue) {
@@ -1636,6 +5485,14 @@ impl From<&Speed> for Payload {
     }
 }
 
+impl From<&DynamicScene> for Payload {
+    fn from(scene: &DynamicScene) -> Self {
+        let mut p = Payload::from(&scene.scene);
+        p.speed(scene.speed.as_ref().unwrap_or(&Speed::new()));
+        p
+    }
+}
+
 impl From<&LightRequest> for Payload {
     fn from(req: &LightRequest) -> Self {
         let mut p = Payload::new();
@@ -1654,12 +5511,24 @@ impl From<&LightRequest> for Payload {
         if let Some(scene) = &req.scene {
             p.scene(scene);
         }
+        if let Some(id) = req.scene_id {
+            p.scene = Some(id);
+        }
         if let Some(cool) = &req.cool {
             p.cool(cool);
         }
         if let Some(warm) = &req.warm {
             p.warm(warm);
         }
+        if let Some(fan_state) = req.fan_state {
+            p.fan_state(fan_state);
+        }
+        if let Some(fan_speed) = &req.fan_speed {
+            p.fan_speed(fan_speed);
+        }
+        if let Some(ratio) = &req.ratio {
+            p.ratio(ratio);
+        }
         p
     }
 }
@@ -1671,3 +5540,549 @@ impl From<&Brightness> for Payload {
         p
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_refuses_invalid_mac() {
+        let light = Light::new(Ipv4Addr::new(10, 1, 2, 3), None);
+        let listener = Ipv4Addr::new(10, 1, 2, 100);
+        for mac in ["", "aa:bb:cc:dd:ee", "gg:bb:cc:dd:ee:ff", "aabbccddeeff00"] {
+            let res = light.register(listener, mac);
+            assert!(matches!(res, Err(Error::InvalidMac(_))), "{}", mac);
+        }
+    }
+
+    #[test]
+    fn bulb_status_keeps_extra_fields() {
+        let bulb: BulbStatus = serde_json::from_value(json!({
+            "method": "getPilot",
+            "env": "pro",
+            "result": {
+                "mac": "a8bb50000000",
+                "rssi": -60,
+                "state": true,
+                "sceneId": 4,
+                "dimming": 50,
+                "play": true,
+                "fanSpeed": 3,
+            }
+        }))
+        .unwrap();
+
+        let status = LightStatus::from(&bulb);
+        assert_eq!(status.scene(), Some(&SceneMode::Party));
+        assert_eq!(status.extra().get("play"), Some(&json!(true)));
+        assert_eq!(status.extra().get("fanSpeed"), Some(&json!(3)));
+        assert!(status.extra().get("mac").is_none());
+    }
+
+    #[test]
+    fn unknown_scene_kept() {
+        let bulb: BulbStatus = serde_json::from_value(json!({
+            "method": "getPilot",
+            "env": "pro",
+            "result": {
+                "mac": "a8bb50000000",
+                "rssi": -60,
+                "state": true,
+                "sceneId": 200,
+            }
+        }))
+        .unwrap();
+
+        let mut status = LightStatus::from(&bulb);
+        assert!(status.scene().is_none());
+        assert_eq!(status.unknown_scene(), Some(200));
+        assert_eq!(status.to_string(), "on · scene=200 (unknown)");
+
+        status.update_from_payload(&Payload::from(&SceneMode::Party));
+        assert_eq!(status.scene(), Some(&SceneMode::Party));
+        assert!(status.unknown_scene().is_none());
+    }
+
+    #[test]
+    fn raw_scene_id_requested() {
+        let req: LightRequest = serde_json::from_str(r#"{"scene_id": 0}"#).unwrap();
+        assert!(matches!(req.validate(), Err(Error::InvalidSceneId)));
+        assert!(serde_json::from_str::<LightRequest>(r#"{"scene_id": 256}"#).is_err());
+
+        let req: LightRequest = serde_json::from_str(r#"{"scene_id": 4}"#).unwrap();
+        assert!(req.has_lighting());
+        let status = LightStatus::from(&Payload::from(&req));
+        assert_eq!(status.scene(), Some(&SceneMode::Party));
+        assert!(req.confirmed_by(&status));
+    }
+
+    #[test]
+    fn random_request_seeded() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        for mode in [RandomMode::Color, RandomMode::Scene, RandomMode::Any] {
+            let a = LightRequest::random(mode, &mut StdRng::seed_from_u64(42));
+            let b = LightRequest::random(mode, &mut StdRng::seed_from_u64(42));
+            assert_eq!(
+                serde_json::to_value(&a).unwrap(),
+                serde_json::to_value(&b).unwrap()
+            );
+            assert!(Payload::from(&a).is_valid());
+            assert!(a.color().is_some() != a.scene().is_some());
+        }
+
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..20 {
+            assert!(LightRequest::random(RandomMode::Color, &mut rng)
+                .scene()
+                .is_none());
+            assert!(LightRequest::random(RandomMode::Scene, &mut rng)
+                .color()
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn status_diff_fields() {
+        let red = LightStatus::from(&Payload::from(&Color::rgb(255, 0, 0)));
+        let mut blue = LightStatus::from(&Payload::from(&Color::rgb(0, 0, 255)));
+        assert_eq!(
+            red.diff(&blue),
+            StatusDiff {
+                color: true,
+                ..Default::default()
+            }
+        );
+
+        blue.update_from_power(&PowerMode::Off);
+        let diff = red.diff(&blue);
+        assert!(diff.color && diff.emitting);
+        assert!(!diff.last && !diff.scene);
+
+        let mut unknown = red.clone();
+        unknown.unknown_scene = Some(200);
+        assert!(red.diff(&unknown).scene);
+    }
+
+    #[test]
+    fn request_restores_status() {
+        let mut payload = Payload::from(&SceneMode::Cozy);
+        payload.brightness(&Brightness::create(30).unwrap());
+        payload.color(&Color::rgb(1, 2, 3));
+        let mut status = LightStatus::from(&payload);
+
+        let req = LightRequest::from(&status);
+        assert_eq!(req.scene(), Some(&SceneMode::Cozy));
+        assert_eq!(req.brightness, Brightness::create(30));
+        assert!(req.color().is_none());
+
+        status.update_from_power(&PowerMode::Off);
+        let req = LightRequest::from(&status);
+        assert!(matches!(req.power(), Some(PowerMode::Off)));
+        assert!(!req.has_lighting());
+    }
+
+    #[test]
+    fn request_confirmed_by_status() {
+        let req: LightRequest = serde_json::from_value(json!({
+            "power": "Off",
+            "brightness": {"value": 40},
+        }))
+        .unwrap();
+
+        let mut status = LightStatus::from(&Payload::from(&Brightness::create(40).unwrap()));
+        assert!(!req.confirmed_by(&status));
+
+        status.update_from_power(&PowerMode::Off);
+        assert!(req.confirmed_by(&status));
+
+        // unrequested fields aren't compared
+        status.update_from_payload(&Payload::from(&Color::rgb(1, 2, 3)));
+        assert!(req.confirmed_by(&status));
+
+        status.update_from_payload(&Payload::from(&Brightness::create(41).unwrap()));
+        assert!(!req.confirmed_by(&status));
+    }
+
+    #[test]
+    fn status_drift_detected() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let mut light = Light::new(ip, None);
+
+        let payload = Payload::from(&Color::from_str("255,0,0").unwrap());
+        light.process_reply(&LightingResponse::payload(ip, payload.clone()));
+        assert!(!light.status().unwrap().drift());
+
+        let bulb: BulbStatus = serde_json::from_value(json!({
+            "method": "getPilot",
+            "env": "pro",
+            "result": {
+                "mac": "a8bb50000000",
+                "rssi": -60,
+                "state": false,
+                "sceneId": 0,
+                "r": 255,
+                "g": 0,
+                "b": 0,
+            },
+        }))
+        .unwrap();
+        let status = LightStatus::from(&bulb);
+        light.process_reply(&LightingResponse::status(ip, status.clone()));
+        assert!(light.status().unwrap().drift());
+
+        // a matching read doesn't clear it, only a setting sent by riz
+        light.process_reply(&LightingResponse::status(ip, status));
+        assert!(light.status().unwrap().drift());
+
+        light.process_reply(&LightingResponse::payload(ip, payload));
+        assert!(!light.status().unwrap().drift());
+    }
+
+    #[test]
+    fn alias_replies_processed() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let alias = Ipv4Addr::new(10, 1, 2, 4);
+        let mut light = Light::new(ip, None).with_aliases(&[alias]);
+
+        let emitters = light.emitters();
+        assert_eq!(emitters.len(), 2);
+        assert!(emitters.iter().all(|e| e.aliases().is_empty()));
+        assert_eq!(emitters[1].ip(), alias);
+
+        let payload = Payload::from(&Color::rgb(255, 0, 0));
+        assert!(light.process_reply(&LightingResponse::payload(alias, payload)));
+        assert_eq!(
+            light.status().unwrap().color(),
+            Some(&Color::rgb(255, 0, 0))
+        );
+
+        let payload = Payload::from(&Color::rgb(0, 255, 0));
+        let other = Ipv4Addr::new(10, 1, 2, 5);
+        assert!(!light.process_reply(&LightingResponse::payload(other, payload)));
+    }
+
+    #[test]
+    fn stale_status_flagged() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let mut room = Room::new("test");
+        let unknown = room
+            .new_light(Light::new(Ipv4Addr::new(10, 1, 2, 4), None))
+            .unwrap();
+        let id = room.new_light(Light::new(ip, None)).unwrap();
+        assert!(!room.mark_stale(&unknown));
+
+        let payload = Payload::from(&Color::rgb(255, 0, 0));
+        room.process_reply(&LightingResponse::payload(ip, payload.clone()));
+        assert!(room.mark_stale(&id));
+        let status = room.read(&id).unwrap().status().unwrap();
+        assert!(status.stale());
+        assert_eq!(status.color(), Some(&Color::rgb(255, 0, 0)));
+
+        let status = LightStatus::from(&payload);
+        room.process_reply(&LightingResponse::status(ip, status));
+        assert!(!room.read(&id).unwrap().status().unwrap().stale());
+    }
+
+    #[test]
+    fn light_nickname_updated() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let mut light: Light = serde_json::from_value(json!({
+            "ip": "10.1.2.3",
+            "name": "Ceiling Light 3",
+        }))
+        .unwrap();
+        assert_eq!(light.nickname(), None);
+
+        let other = Light::new(ip, Some("Ceiling Light 3")).with_nickname("C3");
+        assert!(light.update(&other));
+        assert_eq!(light.nickname(), Some("C3"));
+        assert!(!light.update(&other));
+    }
+
+    #[test]
+    fn light_timeouts_updated() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let mut light = Light::new(ip, None);
+        assert_eq!(
+            light.timeout(),
+            Duration::from_millis(DEFAULT_BULB_TIMEOUT_MS)
+        );
+        assert_eq!(light.retries(), DEFAULT_BULB_RETRIES);
+
+        let other = Light::new(ip, None).with_timeouts(Duration::from_millis(3000), 2);
+        assert!(light.update(&other));
+        assert_eq!(light.timeout(), Duration::from_secs(3));
+        assert_eq!(light.retries(), 2);
+        assert!(!light.update(&other));
+
+        let zero = Light::new(ip, None).with_timeouts(Duration::ZERO, 0);
+        assert_eq!(
+            zero.timeout(),
+            Duration::from_millis(DEFAULT_BULB_TIMEOUT_MS)
+        );
+    }
+
+    #[test]
+    fn brightness_nudged_from_status() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let mut light = Light::new(ip, None);
+        let payload = Payload::from(&Brightness::create(95).unwrap());
+        light.process_reply(&LightingResponse::payload(ip, payload));
+
+        assert_eq!(light.nudged_brightness(-10).unwrap().value(), 85);
+        assert_eq!(light.nudged_brightness(10).unwrap().value(), 100);
+    }
+
+    #[test]
+    fn light_filter_matches() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let room_id = Uuid::new_v4();
+        let mut light = Light::new(ip, None);
+        light.process_reply(&LightingResponse::payload(
+            ip,
+            Payload::from(&SceneMode::Cozy),
+        ));
+
+        let filter: LightFilter =
+            serde_json::from_value(json!({"room": room_id, "scene": "Cozy", "on": true})).unwrap();
+        assert!(filter.matches(&room_id, &light));
+        assert!(!filter.matches(&Uuid::new_v4(), &light));
+
+        let filter: LightFilter = serde_json::from_value(json!({"scene": "Party"})).unwrap();
+        assert!(!filter.matches(&room_id, &light));
+
+        let filter: LightFilter = serde_json::from_value(json!({"on": false})).unwrap();
+        assert!(!filter.matches(&room_id, &light));
+        assert!(!filter.matches(&room_id, &Light::new(ip, None)));
+    }
+
+    #[test]
+    fn room_preset_by_position() {
+        let mut source = Room::new("source");
+        for (last, scene) in [(2, SceneMode::Cozy), (1, SceneMode::Party)] {
+            let ip = Ipv4Addr::new(10, 1, 2, last);
+            let mut light = Light::new(ip, None);
+            light.process_reply(&LightingResponse::payload(ip, Payload::from(&scene)));
+            source.new_light(light).unwrap();
+        }
+
+        let preset = source.preset();
+        let dumped = serde_json::to_string(&preset).unwrap();
+        assert!(!dumped.contains("10.1.2"));
+
+        let mut target = Room::new("target");
+        for last in [9, 8, 7] {
+            target
+                .new_light(Light::new(Ipv4Addr::new(10, 0, 0, last), None))
+                .unwrap();
+        }
+
+        let requests = preset.requests(&target);
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].1.ip(), Ipv4Addr::new(10, 0, 0, 7));
+        assert_eq!(requests[0].2.scene(), Some(&SceneMode::Party));
+        assert_eq!(requests[1].1.ip(), Ipv4Addr::new(10, 0, 0, 8));
+        assert_eq!(requests[1].2.scene(), Some(&SceneMode::Cozy));
+    }
+
+    #[test]
+    fn padded_replies_decoded() {
+        let mut reply = br#"{"method":"getPilot","result":{"mac":"a8bb"}}"#.to_vec();
+        reply.extend([0, 0, 0xff]);
+        let decoded = Light::decode(&reply).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&decoded).unwrap(),
+            json!({"method": "getPilot", "result": {"mac": "a8bb"}})
+        );
+
+        let decoded = Light::decode(b"{\"a\":\"\xffb\"}").unwrap();
+        assert_eq!(decoded, "{\"a\":\"\u{fffd}b\"}");
+    }
+
+    #[test]
+    fn last_exchange_kept_by_ip() {
+        let ip = Ipv4Addr::new(10, 9, 8, 7);
+        let light = Light::new(ip, None);
+        assert!(light.last_exchange().is_none());
+
+        let reply = json!({"method": "getPilot", "result": {"state": true}});
+        light.record_exchange("sent".to_string(), &Ok((reply, Duration::from_millis(5))));
+
+        let exchange = Light::new(ip, Some("copy")).last_exchange().unwrap();
+        assert_eq!(exchange.sent(), "sent");
+        assert!(exchange.received().unwrap().contains("getPilot"));
+        assert!(exchange.error().is_none());
+
+        light.record_exchange("again".to_string(), &Err(Error::NoReply));
+        let exchange = light.last_exchange().unwrap();
+        assert_eq!(exchange.sent(), "again");
+        assert!(exchange.received().is_none() && exchange.error().is_some());
+    }
+
+    #[test]
+    fn firmware_versions_compared() {
+        let parse = |v: &str| v.parse::<FirmwareVersion>();
+        assert_eq!(parse(" 1.16.64 "), Ok(FirmwareVersion::new(1, 16, 64)));
+        assert!(parse("1.16.64").unwrap() < parse("1.22.0").unwrap());
+        assert!(parse("2.0.0").unwrap() > parse("1.99.99").unwrap());
+        for bad in ["", "1.22", "1.22.0.1", "1.-2.0", "v1.22.0"] {
+            assert!(parse(bad).is_err(), "{} parsed", bad);
+        }
+
+        let config: SystemConfig = serde_json::from_value(json!({"fwVersion": "1.21.9"})).unwrap();
+        assert!(config.supports(FirmwareFeature::DualWhite));
+        assert!(config.require(FirmwareFeature::DualWhite).is_ok());
+        assert_eq!(
+            config
+                .require(FirmwareFeature::Transitions)
+                .unwrap_err()
+                .to_string(),
+            "Transitions is not supported on firmware 1.21.9"
+        );
+    }
+
+    #[test]
+    fn disabled_lights_skipped() {
+        let light: Light = serde_json::from_value(json!({"ip": "10.1.2.3"})).unwrap();
+        assert!(light.enabled());
+
+        let mut room = Room::new("test");
+        room.new_light(light).unwrap();
+        room.new_light(Light::new(Ipv4Addr::new(10, 1, 2, 4), None).with_enabled(false))
+            .unwrap();
+        assert_eq!(room.iter_lights().count(), 2);
+
+        let enabled: Vec<_> = room.enabled_lights().map(|(_, l)| l.ip()).collect();
+        assert_eq!(enabled, vec![Ipv4Addr::new(10, 1, 2, 3)]);
+
+        let disabled = Light::new(Ipv4Addr::new(10, 1, 2, 5), None).with_enabled(false);
+        assert!(!LightFilter::default().matches(&room.id(), &disabled));
+    }
+
+    #[test]
+    fn wiz_replies_recognised() {
+        let msg = json!({"method": WizProtocol::GET_PILOT});
+        assert!(Light::is_wiz_reply(
+            &msg,
+            &json!({"method": "getPilot", "env": "pro", "result": {"mac": "a8bb50000000"}})
+        ));
+        assert!(Light::is_wiz_reply(
+            &msg,
+            &json!({"result": {"mac": "a8bb50000000"}})
+        ));
+        assert!(Light::is_wiz_reply(
+            &json!({"method": "setPilot"}),
+            &json!({"method": "setPilot", "env": "pro", "result": {"success": true}})
+        ));
+        assert!(!Light::is_wiz_reply(&msg, &json!({"status": "ok"})));
+        assert!(!Light::is_wiz_reply(&msg, &json!({"method": "setPilot"})));
+        assert!(!Light::is_wiz_reply(&json!({}), &json!({})));
+    }
+
+    #[test]
+    fn stale_replies_dropped() {
+        let light = Light::new(Ipv4Addr::new(10, 1, 2, 3), None);
+        let first = light.with_request_id(&json!({"method": WizProtocol::GET_PILOT}));
+        let second = light.with_request_id(&json!({"method": WizProtocol::GET_PILOT}));
+        assert_ne!(first.get("id"), second.get("id"));
+
+        let reply = json!({"method": WizProtocol::GET_PILOT, "id": first["id"], "result": {}});
+        assert!(!Light::is_stale(&first, &reply));
+        assert!(Light::is_stale(&second, &reply));
+        assert!(!Light::is_stale(&second, &json!({"result": {}})));
+
+        // a resend is stamped with a new id, so late replies to the first try are stale
+        let resend = light.with_request_id(&first);
+        assert_ne!(resend.get("id"), first.get("id"));
+        assert!(Light::is_stale(&resend, &reply));
+
+        let protocol: WizProtocol = serde_json::from_value(json!({"request_ids": false})).unwrap();
+        let light = light.with_protocol(protocol);
+        let msg = light.with_request_id(&json!({"method": WizProtocol::GET_PILOT}));
+        assert!(msg.get("id").is_none());
+    }
+
+    #[test]
+    fn light_protocol_updated() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let mut light = Light::new(ip, None);
+        assert_eq!(
+            light.pilot_message(json!({"dimming": 50})),
+            json!({"method": WizProtocol::SET_PILOT, "params": {"dimming": 50}})
+        );
+
+        let protocol: WizProtocol =
+            serde_json::from_value(json!({"set_pilot": "SetPilot"})).unwrap();
+        let other = Light::new(ip, None).with_protocol(protocol);
+        assert!(light.update(&other));
+        assert!(!light.update(&other));
+        assert_eq!(
+            light.pilot_message(json!({"dimming": 50})),
+            json!({"method": "SetPilot", "params": {"dimming": 50}})
+        );
+        assert_eq!(light.protocol().get_pilot(), WizProtocol::GET_PILOT);
+    }
+
+    #[test]
+    fn capabilities_cached_from_config() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let mut light = Light::new(ip, None);
+        assert!(light.capabilities().is_none());
+
+        let config: SystemConfig =
+            serde_json::from_value(json!({"moduleName": "ESP01_SHTW1C_31"})).unwrap();
+        light.process_reply(&LightingResponse::config(ip, config));
+
+        let caps = light.capabilities().unwrap();
+        assert_eq!(caps.module_name(), Some("ESP01_SHTW1C_31"));
+        assert!(caps.tunable_white());
+        assert!(!caps.rgb());
+
+        let value = serde_json::to_value(&light).unwrap();
+        let read: Light = serde_json::from_value(value).unwrap();
+        assert_eq!(read.capabilities(), light.capabilities());
+
+        assert!(light.update(&Light::new(Ipv4Addr::new(10, 1, 2, 4), None)));
+        assert!(light.capabilities().is_none());
+    }
+
+    #[test]
+    fn unchanged_reply_not_an_update() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let mut light = Light::new(ip, None);
+
+        let mut payload = Payload::from(&Brightness::create(50).unwrap());
+        payload.speed(&Speed::create(120).unwrap());
+        assert!(light.process_reply(&LightingResponse::payload(ip, payload.clone())));
+        assert!(!light.process_reply(&LightingResponse::payload(ip, payload)));
+
+        let status = light.status().unwrap();
+        assert_eq!(status.brightness(), Brightness::create(50).as_ref());
+        assert_eq!(status.speed(), Speed::create(120).as_ref());
+        assert_ne!(status.brightness(), Brightness::create(60).as_ref());
+
+        let other = Ipv4Addr::new(10, 1, 2, 4);
+        let payload = Payload::from(&Kelvin::create(2700).unwrap());
+        assert!(!light.process_reply(&LightingResponse::payload(other, payload)));
+    }
+
+    #[test]
+    fn payload_skips_unset_fixture_params() {
+        let req: LightRequest = serde_json::from_value(json!({
+            "brightness": {"value": 50},
+        }))
+        .unwrap();
+        let value = serde_json::to_value(Payload::from(&req)).unwrap();
+        assert_eq!(value, json!({"dimming": 50}));
+
+        let req: LightRequest = serde_json::from_value(json!({
+            "fan_state": true,
+            "fan_speed": {"value": 4},
+            "ratio": {"value": 30},
+        }))
+        .unwrap();
+        let value = serde_json::to_value(Payload::from(&req)).unwrap();
+        assert_eq!(value, json!({"fanState": 1, "fanSpeed": 4, "ratio": 30}));
+    }
+}
@@ -0,0 +1,484 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::web::Data;
+use log::{error, info, warn};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    models::{LightRequest, PowerMode},
+    storage::STORAGE_ENV_KEY,
+    sync::lock,
+    Error, Origin, Result, Storage, Worker,
+};
+
+/// How often the presence task checks for lights to turn on or off
+const PRESENCE_TICK: Duration = Duration::from_secs(60);
+
+/// Source recorded in the audit log for presence changes
+const PRESENCE_AUDIT_SOURCE: &str = "presence";
+
+/// Largest UTC offset allowed for active hours, in minutes
+const MAX_UTC_OFFSET_MINUTES: i16 = 14 * 60;
+
+/// Hours of the day (local, see [PresenceSchedule]) lights may be on
+///
+/// The end hour is exclusive, and may be before the start hour to wrap
+/// past midnight. Equal hours are active all day.
+///
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+pub struct ActiveHours {
+    /// First active hour, from 0 to 23
+    #[schema(maximum = 23)]
+    start: u8,
+
+    /// First inactive hour after the start, from 0 to 23
+    #[schema(maximum = 23)]
+    end: u8,
+}
+
+impl ActiveHours {
+    /// Create new active hours, from the start hour up to the end hour
+    pub fn new(start: u8, end: u8) -> Self {
+        ActiveHours { start, end }
+    }
+
+    /// Check if the hour (0 to 23) is within the active hours
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::ActiveHours;
+    ///
+    /// let evening = ActiveHours::new(18, 23);
+    /// assert!(evening.contains(18));
+    /// assert!(!evening.contains(23));
+    ///
+    /// let night = ActiveHours::new(22, 2);
+    /// assert!(night.contains(23) && night.contains(1));
+    /// assert!(!night.contains(12));
+    /// ```
+    ///
+    pub fn contains(&self, hour: u8) -> bool {
+        match self.start.cmp(&self.end) {
+            std::cmp::Ordering::Less => hour >= self.start && hour < self.end,
+            std::cmp::Ordering::Greater => hour >= self.start || hour < self.end,
+            std::cmp::Ordering::Equal => true,
+        }
+    }
+}
+
+/// Settings for simulating occupancy, persisted to `presence.json`
+///
+/// While within the active hours, each light in the enabled rooms is
+/// given a `randomness` percent chance to turn on at each decision. Lights
+/// which turn on stay on for a random time between the min and max on
+/// minutes, then turn off and wait a random time in the same range
+/// before the next decision. All lights are turned off outside the
+/// active hours.
+///
+/// Only these settings are persisted, never the random schedule.
+///
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct PresenceSchedule {
+    /// Rooms with presence enabled
+    #[serde(default)]
+    rooms: Vec<Uuid>,
+
+    /// Hours of the day lights may be on
+    active_hours: ActiveHours,
+
+    /// Shortest time a light stays on or off, in minutes
+    #[schema(minimum = 1)]
+    min_on_minutes: u32,
+
+    /// Longest time a light stays on or off, in minutes
+    #[schema(maximum = 1440)]
+    max_on_minutes: u32,
+
+    /// Percent chance a light turns on at each decision, from 0 to 100
+    #[schema(maximum = 100)]
+    randomness: u8,
+
+    /// Offset of local time from UTC for the active hours, in minutes
+    #[serde(default)]
+    utc_offset_minutes: i16,
+}
+
+impl Default for PresenceSchedule {
+    fn default() -> Self {
+        PresenceSchedule {
+            rooms: Vec::new(),
+            active_hours: ActiveHours::new(18, 23),
+            min_on_minutes: 15,
+            max_on_minutes: 90,
+            randomness: 50,
+            utc_offset_minutes: 0,
+        }
+    }
+}
+
+impl PresenceSchedule {
+    /// Accessor for the rooms with presence enabled
+    pub fn rooms(&self) -> &[Uuid] {
+        &self.rooms
+    }
+
+    /// Accessor for the hours of the day lights may be on
+    pub fn active_hours(&self) -> &ActiveHours {
+        &self.active_hours
+    }
+
+    /// Check the settings are within range
+    ///
+    /// # Errors
+    ///   [Error::InvalidSchedule] describing the first invalid setting
+    ///
+    pub fn validate(&self) -> Result<()> {
+        if self.active_hours.start > 23 || self.active_hours.end > 23 {
+            return Err(Error::invalid_schedule("active hours must be 0-23"));
+        }
+        if self.min_on_minutes == 0 || self.min_on_minutes > self.max_on_minutes {
+            return Err(Error::invalid_schedule(
+                "min_on_minutes must be at least 1, and at most max_on_minutes",
+            ));
+        }
+        if self.max_on_minutes > 24 * 60 {
+            return Err(Error::invalid_schedule(
+                "max_on_minutes must be at most 1440",
+            ));
+        }
+        if self.randomness > 100 {
+            return Err(Error::invalid_schedule("randomness must be 0-100"));
+        }
+        if self.utc_offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES {
+            return Err(Error::invalid_schedule(
+                "utc_offset_minutes must be within 14 hours",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check if the time is within the active hours, in local time
+    pub fn is_active(&self, time: SystemTime) -> bool {
+        let minutes = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() / 60)
+            .unwrap_or(0) as i64;
+        let local = (minutes + i64::from(self.utc_offset_minutes)).rem_euclid(24 * 60);
+        self.active_hours.contains((local / 60) as u8)
+    }
+
+    /// Pick a random time for a light to stay on or off
+    fn duration<R: Rng + ?Sized>(&self, rng: &mut R) -> Duration {
+        let minutes = rng.gen_range(self.min_on_minutes..=self.max_on_minutes);
+        Duration::from_secs(u64::from(minutes) * 60)
+    }
+}
+
+/// The presence schedule, and where it's persisted
+///
+/// Expected to be wrapped by a [Mutex], then wrapped with a [Data], and
+/// cloned to each request and the task started by [start_presence]
+///
+#[derive(Debug)]
+pub struct Presence {
+    schedule: PresenceSchedule,
+    file_path: PathBuf,
+}
+
+impl Default for Presence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Presence {
+    /// Load the schedule from `presence.json` in `RIZ_STORAGE_PATH` (env var)
+    ///
+    /// A missing or invalid file uses the default schedule, with no
+    /// rooms enabled
+    ///
+    pub fn new() -> Self {
+        let path = env::var(STORAGE_ENV_KEY).unwrap_or(".".to_string());
+        Self::with_path(Path::new(&path).join("presence.json"))
+    }
+
+    /// Load the schedule from the given file path
+    pub fn with_path(file_path: PathBuf) -> Self {
+        let schedule = match fs::read_to_string(&file_path) {
+            Ok(content) => match serde_json::from_str::<PresenceSchedule>(&content) {
+                Ok(schedule) if schedule.validate().is_ok() => schedule,
+                _ => {
+                    warn!("Ignoring invalid {}", file_path.display());
+                    PresenceSchedule::default()
+                }
+            },
+            Err(_) => PresenceSchedule::default(),
+        };
+
+        Presence {
+            schedule,
+            file_path,
+        }
+    }
+
+    /// Accessor for the current schedule
+    pub fn schedule(&self) -> &PresenceSchedule {
+        &self.schedule
+    }
+
+    /// Replace the schedule, including its enabled rooms
+    ///
+    /// # Errors
+    ///   [Error::InvalidSchedule] if any setting is out of range
+    ///   [Error::Persist] if `presence.json` could not be written
+    ///
+    pub fn set_schedule(&mut self, schedule: PresenceSchedule) -> Result<()> {
+        schedule.validate()?;
+        self.schedule = schedule;
+        self.write()
+    }
+
+    /// Enable presence for the room
+    ///
+    /// # Errors
+    ///   [Error::Persist] if `presence.json` could not be written
+    ///
+    pub fn enable(&mut self, room: &Uuid) -> Result<()> {
+        if !self.schedule.rooms.contains(room) {
+            self.schedule.rooms.push(*room);
+            self.write()?;
+        }
+        Ok(())
+    }
+
+    /// Disable presence for the room, its lights are turned off
+    ///
+    /// # Errors
+    ///   [Error::Persist] if `presence.json` could not be written
+    ///
+    pub fn disable(&mut self, room: &Uuid) -> Result<()> {
+        let before = self.schedule.rooms.len();
+        self.schedule.rooms.retain(|id| id != room);
+        if self.schedule.rooms.len() != before {
+            self.write()?;
+        }
+        Ok(())
+    }
+
+    fn write(&self) -> Result<()> {
+        let contents = serde_json::to_string(&self.schedule).map_err(Error::JsonDump)?;
+        fs::write(&self.file_path, contents)
+            .map_err(|e| Error::persist(&self.file_path.to_string_lossy(), e))
+    }
+}
+
+/// What the presence task last did with a light
+struct LightState {
+    room_id: Uuid,
+    ip: Ipv4Addr,
+    on: bool,
+    next: Instant,
+}
+
+impl LightState {
+    fn new(room_id: Uuid, ip: Ipv4Addr, now: Instant) -> Self {
+        LightState {
+            room_id,
+            ip,
+            on: false,
+            next: now,
+        }
+    }
+
+    /// Decide if the light should change, see [PresenceSchedule]
+    fn step<R: Rng + ?Sized>(
+        &mut self,
+        schedule: &PresenceSchedule,
+        active: bool,
+        now: Instant,
+        rng: &mut R,
+    ) -> Option<PowerMode> {
+        if !active {
+            self.next = now;
+            return self.turn_off();
+        }
+        if now < self.next {
+            return None;
+        }
+
+        self.next = now + schedule.duration(rng);
+        if self.on {
+            self.turn_off()
+        } else if rng.gen_range(0..100) < schedule.randomness {
+            self.on = true;
+            Some(PowerMode::On)
+        } else {
+            None
+        }
+    }
+
+    fn turn_off(&mut self) -> Option<PowerMode> {
+        if self.on {
+            self.on = false;
+            Some(PowerMode::Off)
+        } else {
+            None
+        }
+    }
+}
+
+/// Start the background task turning lights on and off for presence
+///
+/// The schedule is read again each minute, so changes apply without a
+/// restart. Lights in rooms which are disabled while on are turned off.
+///
+pub fn start_presence(
+    presence: Data<Mutex<Presence>>,
+    storage: Data<Mutex<Storage>>,
+    worker: Data<Mutex<Worker>>,
+) {
+    thread::spawn(move || {
+        let mut states: HashMap<Uuid, LightState> = HashMap::new();
+        let mut rng = rand::thread_rng();
+
+        loop {
+            let schedule = lock(&presence).schedule().clone();
+            let active = schedule.is_active(SystemTime::now());
+            let now = Instant::now();
+
+            let lights: Vec<(Uuid, Uuid, Ipv4Addr)> = {
+                let data = lock(&storage);
+                schedule
+                    .rooms()
+                    .iter()
+                    .filter_map(|room_id| data.read(room_id).map(|room| (*room_id, room)))
+                    .flat_map(|(room_id, room)| {
+                        room.enabled_lights()
+                            .map(|(light_id, light)| (room_id, *light_id, light.ip()))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect()
+            };
+
+            let mut changes = Vec::new();
+            for (room_id, light_id, ip) in &lights {
+                let state = states
+                    .entry(*light_id)
+                    .or_insert_with(|| LightState::new(*room_id, *ip, now));
+                state.ip = *ip;
+                if let Some(power) = state.step(&schedule, active, now, &mut rng) {
+                    changes.push((*room_id, *light_id, *ip, power));
+                }
+            }
+
+            // turn off any lights left on in rooms which were disabled
+            states.retain(|light_id, state| {
+                let kept = lights.iter().any(|(_, id, _)| id == light_id);
+                if !kept {
+                    if let Some(power) = state.turn_off() {
+                        changes.push((state.room_id, *light_id, state.ip, power));
+                    }
+                }
+                kept
+            });
+
+            if !changes.is_empty() {
+                info!("Presence changing {} lights", changes.len());
+                let origin = Origin::background(PRESENCE_AUDIT_SOURCE);
+                let mut worker = lock(&worker);
+                for (room_id, light_id, ip, power) in changes {
+                    let req = LightRequest::from_power(power);
+                    if let Err(e) = worker.create_light_task(&room_id, &light_id, ip, req, &origin)
+                    {
+                        error!("Failed to queue presence change for {}: {}", ip, e);
+                    }
+                }
+            }
+
+            thread::sleep(PRESENCE_TICK);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn schedule_validated() {
+        assert!(PresenceSchedule::default().validate().is_ok());
+
+        let schedule = PresenceSchedule {
+            active_hours: ActiveHours::new(18, 24),
+            ..Default::default()
+        };
+        assert!(schedule.validate().is_err());
+
+        let schedule = PresenceSchedule {
+            min_on_minutes: 100,
+            ..Default::default()
+        };
+        assert!(schedule.validate().is_err());
+
+        let schedule = PresenceSchedule {
+            randomness: 101,
+            ..Default::default()
+        };
+        assert!(schedule.validate().is_err());
+    }
+
+    #[test]
+    fn schedule_active_in_local_time() {
+        let mut schedule = PresenceSchedule::default();
+        let utc_19h = UNIX_EPOCH + Duration::from_secs(19 * 60 * 60);
+        assert!(schedule.is_active(utc_19h));
+
+        schedule.utc_offset_minutes = -5 * 60;
+        assert!(!schedule.is_active(utc_19h));
+    }
+
+    #[test]
+    fn presence_steps_lights() {
+        let schedule = PresenceSchedule {
+            randomness: 100,
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(7);
+        let now = Instant::now();
+        let mut state = LightState::new(Uuid::new_v4(), Ipv4Addr::new(10, 1, 2, 3), now);
+
+        assert!(matches!(
+            state.step(&schedule, true, now, &mut rng),
+            Some(PowerMode::On)
+        ));
+        assert!(state.next >= now + Duration::from_secs(15 * 60));
+        assert!(state.step(&schedule, true, now, &mut rng).is_none());
+
+        let later = state.next;
+        assert!(matches!(
+            state.step(&schedule, true, later, &mut rng),
+            Some(PowerMode::Off)
+        ));
+
+        state.on = true;
+        assert!(matches!(
+            state.step(&schedule, false, later, &mut rng),
+            Some(PowerMode::Off)
+        ));
+        assert!(state.step(&schedule, false, later, &mut rng).is_none());
+    }
+}
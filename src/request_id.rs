@@ -0,0 +1,105 @@
+use std::{
+    fmt,
+    future::{ready, Ready},
+};
+
+use actix_web::{dev::Payload, dev::ServiceRequest, FromRequest, HttpMessage, HttpRequest};
+use uuid::Uuid;
+
+/// Header a request ID is read from, and returned in
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request ID accepted from a client
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// Identifies an API request in the logs, from handler to bulb reply
+///
+/// Taken from the `X-Request-Id` header when a client sends a valid one,
+/// otherwise generated. Extract it in a route to trace the commands it
+/// dispatches, see [crate::Origin::api].
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    /// Generate a new random request ID
+    pub fn new() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+
+    /// Use the client's request ID, if it's 1-64 visible ASCII characters
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::RequestId;
+    ///
+    /// assert_eq!(RequestId::parse("abc-123").unwrap().as_str(), "abc-123");
+    /// assert!(RequestId::parse("").is_none());
+    /// assert!(RequestId::parse("has space").is_none());
+    /// ```
+    ///
+    pub fn parse(value: &str) -> Option<Self> {
+        let valid = !value.is_empty()
+            && value.len() <= MAX_REQUEST_ID_LEN
+            && value.chars().all(|c| c.is_ascii_graphic());
+        valid.then(|| Self(value.to_string()))
+    }
+
+    /// Assign the request its ID, from the header or generated
+    ///
+    /// Called by the API's middleware for every request, the ID is kept
+    /// in the request's extensions for routes to extract
+    ///
+    pub fn assign(req: &ServiceRequest) -> Self {
+        let id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Self::parse)
+            .unwrap_or_default();
+        req.extensions_mut().insert(id.clone());
+        id
+    }
+
+    /// Accessor for the ID's text
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromRequest for RequestId {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    /// The ID assigned by the middleware, or a new one without it
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let id = req.extensions().get::<RequestId>().cloned();
+        ready(Ok(id.unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_id_parse_limits() {
+        assert!(RequestId::parse(&"a".repeat(MAX_REQUEST_ID_LEN)).is_some());
+        assert!(RequestId::parse(&"a".repeat(MAX_REQUEST_ID_LEN + 1)).is_none());
+        assert!(RequestId::parse("tab\there").is_none());
+        assert_ne!(RequestId::new(), RequestId::new());
+    }
+}
@@ -1,3 +1,10 @@
+pub mod audit;
+pub mod config;
+pub mod discover;
 pub mod health;
 pub mod lights;
+pub mod maintenance;
+pub mod operations;
+pub mod presence;
 pub mod rooms;
+pub mod scenes;
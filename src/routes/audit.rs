@@ -0,0 +1,45 @@
+//! Riz API routes for the command audit log
+
+use std::sync::Mutex;
+
+use actix_web::{
+    get,
+    web::{Data, Query},
+    HttpResponse, Responder, Result,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::{sync::lock, worker::Worker};
+
+/// Optional filters for reading the audit log
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AuditQuery {
+    /// Only include commands sent to this light ID
+    light: Option<Uuid>,
+
+    /// Only include commands sent at or after this time (unix seconds)
+    since: Option<u64>,
+}
+
+/// Read dispatched commands from the audit log, oldest first
+///
+/// # Path
+///   `GET /v1/audit?light=<uuid>&since=<u64>`
+///
+/// # Responses
+///   - `200`: [Vec] of [crate::AuditEntry]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = Vec<AuditEntry>),
+    ),
+    params(AuditQuery),
+)]
+#[get("/v1/audit")]
+async fn list(query: Query<AuditQuery>, worker: Data<Mutex<Worker>>) -> Result<impl Responder> {
+    let worker = lock(&worker);
+    let entries = worker.audit_log().query(query.light.as_ref(), query.since);
+    Ok(HttpResponse::Ok().json(entries))
+}
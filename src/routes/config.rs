@@ -0,0 +1,72 @@
+//! Riz API route for the effective configuration
+
+use std::net::Ipv4Addr;
+
+use actix_web::{get, web::Data, HttpResponse, Responder, Result};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Settings the running API resolved from its env vars at startup
+///
+/// NB: never add secrets here, this is served as-is by `GET /v1/config`
+///
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EffectiveConfig {
+    /// Path to `rooms.json`, from `RIZ_STORAGE_PATH`
+    pub storage_path: String,
+
+    /// Listening port, from `RIZ_PORT`
+    pub port: u16,
+
+    /// Listening address
+    #[schema(value_type = String, example = "0.0.0.0")]
+    pub bind_address: Ipv4Addr,
+
+    /// Allowed CORS origin, from `RIZ_CORS_ORIGIN`
+    pub cors_origin: String,
+
+    /// Number of threads sending commands to bulbs
+    pub worker_threads: usize,
+
+    /// Largest accepted JSON request body, from `RIZ_MAX_BODY_BYTES`
+    pub max_body_bytes: usize,
+
+    /// Time allowed to send request headers, from `RIZ_REQUEST_TIMEOUT_SECS`
+    pub request_timeout_secs: u64,
+
+    /// Swagger UI path if enabled, from `RIZ_ENABLE_SWAGGER` & `RIZ_SWAGGER_PATH`
+    pub swagger_path: Option<String>,
+
+    /// Whether status is fetched for all lights on startup, from `RIZ_WARM_STATUS`
+    pub warm_status: bool,
+
+    /// Default bulb reply timeout, from `RIZ_BULB_TIMEOUT_MS`
+    pub bulb_timeout_ms: u64,
+
+    /// Default bulb resends after a timeout, from `RIZ_BULB_RETRIES`
+    pub bulb_retries: u8,
+
+    /// Most status reads in flight for a room, from `RIZ_STATUS_CONCURRENCY`
+    pub status_concurrency: usize,
+
+    /// Whether lights may have public IPs, from `RIZ_ALLOW_PUBLIC_IPS`
+    pub allow_public_ips: bool,
+}
+
+/// Show the configuration the API resolved from env at startup
+///
+/// # Path
+///   `GET /v1/config`
+///
+/// # Responses
+///   - `200`: [EffectiveConfig]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = EffectiveConfig),
+    ),
+)]
+#[get("/v1/config")]
+async fn read(config: Data<EffectiveConfig>) -> Result<impl Responder> {
+    Ok(HttpResponse::Ok().json(config.get_ref()))
+}
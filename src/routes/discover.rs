@@ -0,0 +1,75 @@
+//! Riz API route for discovering bulbs on the local network
+
+use std::time::Duration;
+
+use actix_web::{
+    error::{ErrorBadRequest, ErrorServiceUnavailable},
+    get,
+    web::{self, Query},
+    HttpResponse, Responder, Result,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::models::Light;
+
+/// Default time to wait for discovery replies
+const DEFAULT_DISCOVER_TIMEOUT_MS: u64 = 3000;
+
+/// Longest time allowed to wait for discovery replies
+const MAX_DISCOVER_TIMEOUT_MS: u64 = 30000;
+
+/// Optional query parameters for discovering bulbs
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DiscoverQuery {
+    /// Milliseconds to wait for replies (default 3000)
+    timeout: Option<u64>,
+
+    /// Return as soon as this many bulbs have replied
+    expected: Option<usize>,
+}
+
+/// Discover bulbs on the local network
+///
+/// NB: the broadcast only reaches bulbs on the same network as the API,
+///     which isn't the case with default docker networking
+///
+/// # Path
+///   `GET /v1/discover?timeout=<u64>&expected=<usize>`
+///
+/// # Responses
+///   - `200`: [Vec] of [DiscoveredLight]
+///   - `400`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = Vec<DiscoveredLight>),
+        (status = 400, description = "Bad Request", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(DiscoverQuery),
+)]
+#[get("/v1/discover")]
+async fn discover(query: Query<DiscoverQuery>) -> Result<impl Responder> {
+    let timeout = query.timeout.unwrap_or(DEFAULT_DISCOVER_TIMEOUT_MS);
+    if timeout > MAX_DISCOVER_TIMEOUT_MS {
+        return Err(ErrorBadRequest(format!(
+            "timeout must be at most {}",
+            MAX_DISCOVER_TIMEOUT_MS
+        )));
+    }
+
+    let expected = query.expected;
+    let found = web::block(move || Light::discover(Duration::from_millis(timeout), expected))
+        .await
+        .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+    match found {
+        Ok(lights) => Ok(HttpResponse::Ok().json(lights)),
+        Err(e) => Err(ErrorServiceUnavailable(format!(
+            "Failed to discover lights: {}",
+            e
+        ))),
+    }
+}
@@ -1,6 +1,10 @@
-//! Riz API health route
+//! Riz API health routes
 
-use actix_web::{get, HttpResponse, Responder, Result};
+use std::sync::Mutex;
+
+use actix_web::{get, web::Data, HttpResponse, Responder, Result};
+
+use crate::{sync::lock, Storage};
 
 /// Simple ping route
 ///
@@ -20,3 +24,31 @@ pub async fn ping() -> Result<impl Responder> {
     // could check if we are having any issues opening sockets...
     Ok(HttpResponse::Ok().json("ok"))
 }
+
+/// Readiness route, unhealthy while `rooms.json` writes keep failing
+///
+/// See [Storage::write_health] for when storage is considered unhealthy.
+/// The reply's `dirty` is true while changes are only held in memory.
+///
+/// # Path
+///   `GET /v1/health/ready`
+///
+/// # Responses
+///   - `200`: [WriteHealth]
+///   - `503`: [WriteHealth]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = WriteHealth),
+        (status = 503, description = "Unavailable", body = WriteHealth),
+    ),
+)]
+#[get("/v1/health/ready")]
+pub async fn ready(storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
+    let health = lock(&storage).write_health();
+    if health.healthy {
+        Ok(HttpResponse::Ok().json(health))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(health))
+    }
+}
@@ -1,23 +1,33 @@
 //! Riz API routes for light control
 
-use std::sync::Mutex;
+use std::{net::Ipv4Addr, sync::Mutex};
 
 use actix_web::{
     delete,
-    error::{ErrorConflict, ErrorNotFound, ErrorServiceUnavailable},
+    error::{
+        ErrorBadRequest, ErrorConflict, ErrorInternalServerError, ErrorNotFound,
+        ErrorServiceUnavailable,
+    },
     get, patch, post, put,
-    web::{Data, Json, Path},
+    web::{self, Data, Json, Path, Query},
     HttpResponse, Responder, Result,
 };
 use log::error;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 use crate::{
-    models::{Light, LightRequest, LightingResponse},
+    models::{Color, Light, LightFilter, LightRef, LightRequest, LightingResponse, RandomMode},
     storage::Storage,
-    worker::Worker,
+    sync::lock,
+    worker::{Origin, Worker},
+    Error, RequestId,
 };
 
+/// Times a request is sent with `ensure=true` before giving up
+const ENSURE_ATTEMPTS: u8 = 3;
+
 /// Create a light
 ///
 /// # Path
@@ -28,13 +38,17 @@ use crate::{
 ///
 /// # Responses
 ///   - `200`: [Uuid]
+///   - `404`: [String]
 ///   - `409`: [String]
+///   - `500`: [String]
 ///
 #[utoipa::path(
     request_body = Light,
     responses(
         (status = 200, description = "OK", body = Uuid),
+        (status = 404, description = "Not Found", body = String),
         (status = 409, description = "Conflict", body = String),
+        (status = 500, description = "Internal Server Error", body = String),
     ),
     params(
         ("id", description = "Room ID")
@@ -48,108 +62,377 @@ async fn create(
 ) -> Result<impl Responder> {
     let id = id.into_inner();
     let light = req.into_inner();
-    let mut data = storage.lock().unwrap();
-    if let Ok(id) = data.new_light(&id, light) {
-        Ok(HttpResponse::Ok().json(id))
-    } else {
-        Err(ErrorConflict("Failed to create new light"))
+    let mut data = lock(&storage);
+    match data.new_light(&id, light) {
+        Ok(id) => Ok(HttpResponse::Ok().json(id)),
+        Err(e @ Error::RoomNotFound(_)) => Err(ErrorNotFound(e.to_string())),
+        Err(e @ Error::Persist { .. }) => Err(ErrorInternalServerError(e.to_string())),
+        Err(e) => Err(ErrorConflict(format!("Failed to create new light: {}", e))),
     }
 }
 
+/// Optional query parameters for updating all bulbs in a room
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct UpdateRoomQuery {
+    /// Send a scene to all bulbs as close together as possible
+    sync_scene: Option<bool>,
+
+    /// Read each bulb back and resend until it matches the request
+    ensure: Option<bool>,
+
+    /// Read each bulb back once sent, and store what it actually applied
+    verify_state: Option<bool>,
+}
+
 /// Update lighting settings for all bulbs in a room
 ///
+/// Progress for each bulb can be read from the returned operation ID
+/// with `GET /v1/operations/{id}`
+///
+/// With `sync_scene=true` the request must include a scene, which is
+/// sent to all bulbs back to back so their animations start in phase.
+/// This is best effort, UDP and wifi jitter can still leave the bulbs
+/// some tens of milliseconds apart.
+///
+/// With `ensure=true` each bulb's status is read back, and the request
+/// resent until it matches. Bulbs which never match are errors in the
+/// operation. This can't be combined with `sync_scene`.
+///
+/// With `verify_state=true` each bulb's status is read back once the
+/// request is sent, and stored instead of the request, as bulbs can drop
+/// some settings sent together. Ensured requests always do this.
+///
 /// # Path
-///   `PUT /v1/room/{id}/lights`
+///   `PUT /v1/room/{id}/lights?sync_scene=<bool>&ensure=<bool>&verify_state=<bool>`
 ///
 /// # Body
 ///   [LightRequest]
 ///
 /// # Responses
-///   - `204`: [None]
+///   - `200`: [Uuid]
+///   - `400`: [String]
 ///   - `404`: [String]
 ///   - `503`: [String]
 ///
 #[utoipa::path(
     request_body = LightRequest,
     responses(
-        (status = 204, description = "OK"),
+        (status = 200, description = "OK", body = Uuid),
+        (status = 400, description = "Bad Request", body = String),
         (status = 404, description = "Not Found", body = String),
         (status = 503, description = "Unavailable", body = String),
     ),
     params(
         ("id", description = "Room ID"),
+        UpdateRoomQuery,
     ),
 )]
 #[put("/v1/room/{id}/lights")]
 async fn update_room(
     id: Path<Uuid>,
+    query: Query<UpdateRoomQuery>,
     req: Json<LightRequest>,
     storage: Data<Mutex<Storage>>,
     worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
 ) -> Result<impl Responder> {
     let id = id.into_inner();
     let req = req.into_inner();
+    if let Err(e) = req.validate() {
+        return Err(ErrorBadRequest(e.to_string()));
+    }
+
+    if req.partial_color().is_some() {
+        return Err(ErrorBadRequest(
+            "partial_color is only supported for single lights",
+        ));
+    }
+
+    let sync_scene = query.sync_scene.unwrap_or(false);
+    if sync_scene && req.scene().is_none() && req.scene_id().is_none() {
+        return Err(ErrorBadRequest("sync_scene requires a scene"));
+    }
+
+    let ensure = query.ensure.unwrap_or(false);
+    if ensure && sync_scene {
+        return Err(ErrorBadRequest(
+            "ensure can not be combined with sync_scene",
+        ));
+    }
 
     let room = {
-        let data = storage.lock().unwrap();
+        let data = lock(&storage);
         match data.read(&id) {
             Some(room) => room,
             None => return Err(ErrorNotFound(format!("No such room: {}", id))),
         }
     };
 
-    if let Some(lights) = room.list() {
-        let mut worker = worker.lock().unwrap();
-        for light_id in lights {
-            if let Some(light) = room.read(light_id) {
-                if worker.create_task(light.ip(), req.clone()).is_err() {
-                    return Err(ErrorServiceUnavailable("No available workers".to_string()));
-                }
-            }
+    if room.list().is_some() {
+        let lights: Vec<_> = room
+            .enabled_lights()
+            .map(|(light_id, light)| (*light_id, light.ip()))
+            .collect();
+
+        let origin = Origin::api(&request_id).verifying(query.verify_state.unwrap_or(false));
+        let mut worker = lock(&worker);
+        let op = if sync_scene {
+            worker.create_sync_operation(&id, &lights, req, &origin)
+        } else if ensure {
+            worker.create_ensured_operation(&id, &lights, req, ENSURE_ATTEMPTS, &origin)
+        } else {
+            worker.create_operation(&id, &lights, req, &origin)
+        };
+
+        match op {
+            Ok(op) => Ok(HttpResponse::Ok().json(op)),
+            Err(_) => Err(ErrorServiceUnavailable("No available workers".to_string())),
         }
-        Ok(HttpResponse::Ok())
     } else {
         Err(ErrorNotFound(format!("No lights in room: {}", id)))
     }
 }
 
+/// Lighting settings to apply to all lights matching a filter
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ApplyRequest {
+    /// Which lights to update, from all rooms
+    #[serde(default)]
+    filter: LightFilter,
+
+    /// Settings to send to each matching light
+    request: LightRequest,
+}
+
+/// Update lighting settings for all lights matching a filter, in any room
+///
+/// Lights are matched on their last known status, each match is sent
+/// the request through the worker like a single light update
+///
+/// # Path
+///   `POST /v1/lights/apply`
+///
+/// # Body
+///   [ApplyRequest]
+///
+/// # Responses
+///   - `200`: [Vec] of [LightRef] of the matched lights
+///   - `400`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    request_body = ApplyRequest,
+    responses(
+        (status = 200, description = "OK", body = Vec<LightRef>),
+        (status = 400, description = "Bad Request", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+)]
+#[post("/v1/lights/apply")]
+async fn apply(
+    req: Json<ApplyRequest>,
+    storage: Data<Mutex<Storage>>,
+    worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
+) -> Result<impl Responder> {
+    let ApplyRequest { filter, request } = req.into_inner();
+    if let Err(e) = request.validate() {
+        return Err(ErrorBadRequest(e.to_string()));
+    }
+
+    if request.partial_color().is_some() {
+        return Err(ErrorBadRequest(
+            "partial_color is only supported for single lights",
+        ));
+    }
+
+    let matched: Vec<_> = {
+        let data = lock(&storage);
+        data.iter_lights()
+            .filter(|(room_id, _, light)| filter.matches(room_id, light))
+            .map(|(room_id, light_id, light)| (LightRef::new(room_id, light_id), light.ip()))
+            .collect()
+    };
+
+    let origin = Origin::api(&request_id);
+    let mut worker = lock(&worker);
+    for (light, ip) in &matched {
+        let task = worker.create_light_task(
+            light.room_id(),
+            light.light_id(),
+            *ip,
+            request.clone(),
+            &origin,
+        );
+        if task.is_err() {
+            return Err(ErrorServiceUnavailable("No available workers".to_string()));
+        }
+    }
+
+    let matched: Vec<_> = matched.into_iter().map(|(light, _)| light).collect();
+    Ok(HttpResponse::Ok().json(matched))
+}
+
+/// Optional query parameters for updating a single bulb
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct UpdateLightQuery {
+    /// Read the bulb back and resend until it matches the request
+    ensure: Option<bool>,
+
+    /// Read the bulb back once sent, and store what it actually applied
+    verify_state: Option<bool>,
+}
+
 /// Update lighting settings for a single bulb
 ///
+/// A `partial_color` in the request is merged onto the request's color,
+/// or otherwise the light's last known color, before being sent
+///
+/// With `ensure=true` the request is sent right away rather than queued,
+/// then the bulb's status is read back and the request resent until it
+/// matches. The confirmed status is returned, or a `409` if the bulb
+/// never matched, see [Light::ensure].
+///
+/// With `verify_state=true` the bulb's status is read back once the
+/// request is sent, and stored instead of the request, see
+/// [crate::Origin::verifying]
+///
 /// # Path
-///   `PUT /v1/room/{id}/light/{light_id}`
+///   `PUT /v1/room/{id}/light/{light_id}?ensure=<bool>&verify_state=<bool>`
 ///
 /// # Body
 ///   [LightRequest]
 ///
 /// # Responses
-///   - `204`: [None]
+///   - `200`: [None], or [crate::models::LightStatus] with `ensure=true`
+///   - `400`: [String]
 ///   - `404`: [String]
+///   - `409`: [String]
 ///   - `503`: [String]
 ///
 #[utoipa::path(
     request_body = LightRequest,
     responses(
-        (status = 204, description = "OK"),
+        (status = 200, description = "OK, with the confirmed status if ensured", body = LightStatus),
+        (status = 400, description = "Bad Request", body = String),
         (status = 404, description = "Not Found", body = String),
+        (status = 409, description = "Conflict", body = String),
         (status = 503, description = "Unavailable", body = String),
     ),
     params(
         ("id", description = "Room ID"),
         ("light_id", description = "Light ID"),
+        UpdateLightQuery,
     )
 )]
 #[put("/v1/room/{id}/light/{light_id}")]
 async fn update(
     ids: Path<(Uuid, Uuid)>,
+    query: Query<UpdateLightQuery>,
     req: Json<LightRequest>,
     storage: Data<Mutex<Storage>>,
     worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
+) -> Result<impl Responder> {
+    let (room_id, light_id) = ids.into_inner();
+    let mut req = req.into_inner();
+    if let Err(e) = req.validate() {
+        return Err(ErrorBadRequest(e.to_string()));
+    }
+
+    let room = {
+        let data = lock(&storage);
+        match data.read(&room_id) {
+            Some(room) => room,
+            None => return Err(ErrorNotFound(format!("No such room: {}", room_id))),
+        }
+    };
+
+    if let Some(light) = room.read(&light_id) {
+        let known = light.status().and_then(|known| known.color());
+        if let Err(e) = req.merge_color(known) {
+            return Err(ErrorConflict(e.to_string()));
+        }
+
+        if query.ensure.unwrap_or(false) {
+            let ip = light.ip();
+            let light = light.clone();
+            let origin = Origin::api(&request_id);
+            if let Err(e) =
+                lock(&worker).record_light_command(&room_id, &light_id, ip, &req, &origin)
+            {
+                error!("Failed to queue audit entry: {}", e);
+            }
+            let ensured = web::block(move || light.ensure(&req, ENSURE_ATTEMPTS))
+                .await
+                .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+            return match ensured {
+                Ok(confirmed) => {
+                    let resp = LightingResponse::status(ip, confirmed.clone());
+                    if let Err(e) = lock(&worker).queue_update(resp, &request_id) {
+                        error!("Failed to queue write: {}", e);
+                    }
+                    Ok(HttpResponse::Ok().json(confirmed))
+                }
+                Err(e @ Error::UnconfirmableReboot) => Err(ErrorBadRequest(e.to_string())),
+                Err(e @ Error::Unconfirmed { .. }) => Err(ErrorConflict(e.to_string())),
+                Err(e) => Err(ErrorServiceUnavailable(e.to_string())),
+            };
+        }
+
+        let origin = Origin::api(&request_id).verifying(query.verify_state.unwrap_or(false));
+        let mut worker = lock(&worker);
+        match worker.create_light_task(&room_id, &light_id, light.ip(), req, &origin) {
+            Ok(_) => Ok(HttpResponse::Ok().finish()),
+            Err(_) => Err(ErrorServiceUnavailable("No available workers".to_string())),
+        }
+    } else {
+        Err(ErrorNotFound(format!("No such light: {}", light_id)))
+    }
+}
+
+/// Optional query parameters for a random lighting setting
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RandomQuery {
+    /// Pick a `Color`, a `Scene`, or `Any` (default)
+    mode: Option<RandomMode>,
+}
+
+/// Set a random color or scene on a single bulb
+///
+/// # Path
+///   `PUT /v1/room/{id}/light/{light_id}/random?mode=<RandomMode>`
+///
+/// # Responses
+///   - `200`: [LightRequest] of the random pick
+///   - `404`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = LightRequest),
+        (status = 404, description = "Not Found", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(
+        ("id", description = "Room ID"),
+        ("light_id", description = "Light ID"),
+        RandomQuery,
+    )
+)]
+#[put("/v1/room/{id}/light/{light_id}/random")]
+async fn random(
+    ids: Path<(Uuid, Uuid)>,
+    query: Query<RandomQuery>,
+    storage: Data<Mutex<Storage>>,
+    worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
 ) -> Result<impl Responder> {
     let (room_id, light_id) = ids.into_inner();
-    let req = req.into_inner();
 
     let room = {
-        let data = storage.lock().unwrap();
+        let data = lock(&storage);
         match data.read(&room_id) {
             Some(room) => room,
             None => return Err(ErrorNotFound(format!("No such room: {}", room_id))),
@@ -157,9 +440,13 @@ async fn update(
     };
 
     if let Some(light) = room.read(&light_id) {
-        let mut worker = worker.lock().unwrap();
-        match worker.create_task(light.ip(), req) {
-            Ok(_) => Ok(HttpResponse::Ok()),
+        let mode = query.mode.unwrap_or_default();
+        let req = LightRequest::random(mode, &mut rand::thread_rng());
+
+        let origin = Origin::api(&request_id);
+        let mut worker = lock(&worker);
+        match worker.create_light_task(&room_id, &light_id, light.ip(), req.clone(), &origin) {
+            Ok(_) => Ok(HttpResponse::Ok().json(req)),
             Err(_) => Err(ErrorServiceUnavailable("No available workers".to_string())),
         }
     } else {
@@ -167,10 +454,295 @@ async fn update(
     }
 }
 
+/// Query parameters for nudging a bulb's brightness
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct NudgeQuery {
+    /// Signed change in brightness percent, the result is clamped to 10-100
+    delta: i8,
+}
+
+/// Change a single bulb's brightness relative to its current brightness
+///
+/// The light's last known brightness is used, or read from the bulb if
+/// unknown. Bulbs which don't report a brightness are treated as 100.
+/// The new brightness is then queued to the bulb through the worker.
+///
+/// # Path
+///   `PUT /v1/room/{id}/light/{light_id}/brightness/nudge?delta=<i8>`
+///
+/// # Responses
+///   - `200`: [None]
+///   - `404`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK"),
+        (status = 404, description = "Not Found", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(
+        ("id", description = "Room ID"),
+        ("light_id", description = "Light ID"),
+        NudgeQuery,
+    )
+)]
+#[put("/v1/room/{id}/light/{light_id}/brightness/nudge")]
+async fn nudge_brightness(
+    ids: Path<(Uuid, Uuid)>,
+    query: Query<NudgeQuery>,
+    storage: Data<Mutex<Storage>>,
+    worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
+) -> Result<impl Responder> {
+    let (room_id, light_id) = ids.into_inner();
+
+    let room = {
+        let data = lock(&storage);
+        match data.read(&room_id) {
+            Some(room) => room,
+            None => return Err(ErrorNotFound(format!("No such room: {}", room_id))),
+        }
+    };
+
+    let light = match room.read(&light_id) {
+        Some(light) => light.clone(),
+        None => return Err(ErrorNotFound(format!("No such light: {}", light_id))),
+    };
+
+    let ip = light.ip();
+    let delta = query.delta;
+    let nudged = web::block(move || light.nudged_brightness(delta))
+        .await
+        .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+    let req = match nudged {
+        Ok(brightness) => LightRequest::from(&brightness),
+        Err(e) => {
+            return Err(ErrorServiceUnavailable(format!(
+                "Failed to nudge brightness: {}",
+                e
+            )))
+        }
+    };
+
+    let origin = Origin::api(&request_id);
+    let mut worker = lock(&worker);
+    match worker.create_light_task(&room_id, &light_id, ip, req, &origin) {
+        Ok(_) => Ok(HttpResponse::Ok()),
+        Err(_) => Err(ErrorServiceUnavailable("No available workers".to_string())),
+    }
+}
+
+/// A color to flash on a bulb, see [Light::flash]
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FlashRequest {
+    /// Color to flash, shown at full brightness
+    color: Color,
+
+    /// Number of flashes, from 1 to 10
+    #[schema(minimum = 1, maximum = 10)]
+    count: u8,
+
+    /// Time the color is shown for each flash, up to 2000 milliseconds
+    #[schema(maximum = 2000)]
+    on_ms: u64,
+
+    /// Time the bulb is off after each flash, up to 2000 milliseconds
+    #[schema(maximum = 2000)]
+    off_ms: u64,
+}
+
+/// Flash a color on a single bulb, then restore its previous settings
+///
+/// Useful for notifications. The response is sent once the bulb has
+/// been restored.
+///
+/// # Path
+///   `POST /v1/room/{id}/light/{light_id}/flash`
+///
+/// # Body
+///   [FlashRequest]
+///
+/// # Responses
+///   - `200`: [None]
+///   - `400`: [String]
+///   - `404`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    request_body = FlashRequest,
+    responses(
+        (status = 200, description = "OK"),
+        (status = 400, description = "Bad Request", body = String),
+        (status = 404, description = "Not Found", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(
+        ("id", description = "Room ID"),
+        ("light_id", description = "Light ID"),
+    )
+)]
+#[post("/v1/room/{id}/light/{light_id}/flash")]
+async fn flash(
+    ids: Path<(Uuid, Uuid)>,
+    req: Json<FlashRequest>,
+    storage: Data<Mutex<Storage>>,
+) -> Result<impl Responder> {
+    let (room_id, light_id) = ids.into_inner();
+
+    let light = {
+        let data = lock(&storage);
+        match data.read(&room_id) {
+            Some(room) => match room.read(&light_id) {
+                Some(light) => light.clone(),
+                None => return Err(ErrorNotFound(format!("No such light: {}", light_id))),
+            },
+            None => return Err(ErrorNotFound(format!("No such room: {}", room_id))),
+        }
+    };
+
+    let req = req.into_inner();
+    let flashed = web::block(move || light.flash(&req.color, req.count, req.on_ms, req.off_ms))
+        .await
+        .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+    match flashed {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(e @ Error::InvalidFlash(_)) => Err(ErrorBadRequest(e.to_string())),
+        Err(e) => Err(ErrorServiceUnavailable(format!("Failed to flash: {}", e))),
+    }
+}
+
+/// Set a bulb to match another bulb in the same room
+///
+/// The source's last known status is used, or read from the bulb if
+/// unknown. The settings sent are returned, see [LightRequest::from].
+///
+/// # Path
+///   `POST /v1/room/{id}/light/{light_id}/sync/{source_id}`
+///
+/// # Responses
+///   - `200`: [LightRequest]
+///   - `404`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = LightRequest),
+        (status = 404, description = "Not Found", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(
+        ("id", description = "Room ID"),
+        ("light_id", description = "Light ID to set"),
+        ("source_id", description = "Light ID to match"),
+    )
+)]
+#[post("/v1/room/{id}/light/{light_id}/sync/{source_id}")]
+async fn sync(
+    ids: Path<(Uuid, Uuid, Uuid)>,
+    storage: Data<Mutex<Storage>>,
+    worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
+) -> Result<impl Responder> {
+    let (room_id, light_id, source_id) = ids.into_inner();
+
+    let (light, source) = {
+        let data = lock(&storage);
+        let room = match data.read(&room_id) {
+            Some(room) => room,
+            None => return Err(ErrorNotFound(format!("No such room: {}", room_id))),
+        };
+        match (room.read(&light_id), room.read(&source_id)) {
+            (Some(light), Some(source)) => (light.clone(), source.clone()),
+            (None, _) => return Err(ErrorNotFound(format!("No such light: {}", light_id))),
+            (_, None) => return Err(ErrorNotFound(format!("No such light: {}", source_id))),
+        }
+    };
+
+    let synced = web::block(move || {
+        let source_status = match source.status() {
+            Some(known) => known.clone(),
+            None => source.get_status()?,
+        };
+        let resp = light.sync_from(&source_status)?;
+        Ok::<_, Error>((LightRequest::from(&source_status), resp))
+    })
+    .await
+    .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+    match synced {
+        Ok((req, resp)) => {
+            if let Err(e) = lock(&worker).queue_update(resp, &request_id) {
+                error!("Failed to queue write: {}", e);
+            }
+            Ok(HttpResponse::Ok().json(req))
+        }
+        Err(e) => Err(ErrorServiceUnavailable(format!("Failed to sync: {}", e))),
+    }
+}
+
+/// Read the last message sent to a single bulb and its reply
+///
+/// Only kept with `RIZ_DEBUG_EXCHANGES=true` (env var), otherwise this
+/// is always a `404`
+///
+/// # Path
+///   `GET /v1/room/{id}/light/{light_id}/debug`
+///
+/// # Responses
+///   - `200`: [UdpExchange]
+///   - `404`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = UdpExchange),
+        (status = 404, description = "Not Found", body = String),
+    ),
+    params(
+        ("id", description = "Room ID"),
+        ("light_id", description = "Light ID"),
+    )
+)]
+#[get("/v1/room/{id}/light/{light_id}/debug")]
+async fn debug(ids: Path<(Uuid, Uuid)>, storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
+    let (room_id, light_id) = ids.into_inner();
+
+    let light = {
+        let data = lock(&storage);
+        match data.read(&room_id) {
+            Some(room) => match room.read(&light_id) {
+                Some(light) => light.clone(),
+                None => return Err(ErrorNotFound(format!("No such light: {}", light_id))),
+            },
+            None => return Err(ErrorNotFound(format!("No such room: {}", room_id))),
+        }
+    };
+
+    match light.last_exchange() {
+        Some(exchange) => Ok(HttpResponse::Ok().json(exchange)),
+        None => Err(ErrorNotFound(format!(
+            "No exchange kept for {}, see RIZ_DEBUG_EXCHANGES",
+            light.ip()
+        ))),
+    }
+}
+
+/// Optional query parameters for reading a single bulb's status
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct StatusQuery {
+    /// Write the fresh status to the stored light (default true)
+    persist: Option<bool>,
+}
+
 /// Update lighting status for a single bulb
 ///
+/// With `persist=false` the live status is only returned, and the
+/// stored light is left as it was
+///
 /// # Path
-///   `GET /v1/room/{id}/light/{light_id}/status`
+///   `GET /v1/room/{id}/light/{light_id}/status?persist=<bool>`
 ///
 /// # Responses
 ///   - `200`: [crate::models::LightStatus]
@@ -186,42 +758,105 @@ async fn update(
     params(
         ("id", description = "Room ID"),
         ("light_id", description = "Light ID"),
+        StatusQuery,
     )
 )]
 #[get("/v1/room/{id}/light/{light_id}/status")]
 async fn status(
     ids: Path<(Uuid, Uuid)>,
+    query: Query<StatusQuery>,
     data: Data<Mutex<Storage>>,
     worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
 ) -> Result<impl Responder> {
     let (room_id, light_id) = ids.into_inner();
 
     let room = {
-        let data = data.lock().unwrap();
+        let data = lock(&data);
         match data.read(&room_id) {
             Some(room) => room,
             None => return Err(ErrorNotFound(format!("No such room: {}", room_id))),
         }
     };
 
-    if let Some(light) = room.read(&light_id) {
-        match light.get_status() {
-            Ok(status) => {
-                let mut worker = worker.lock().unwrap();
-                if let Err(e) =
-                    worker.queue_update(LightingResponse::status(light.ip(), status.clone()))
-                {
-                    error!("Failed to queue write: {}", e);
-                }
-                Ok(HttpResponse::Ok().json(status))
-            }
-            Err(e) => Err(ErrorServiceUnavailable(format!(
-                "Failed to fetch status: {}",
-                e
-            ))),
+    let light = match room.read(&light_id) {
+        Some(light) => light,
+        None => return Err(ErrorNotFound(format!("No such light: {}", light_id))),
+    };
+
+    // reads are queued ahead of setting changes, and stored by the worker
+    let rx = {
+        let mut worker = lock(&worker);
+        match query.persist {
+            Some(false) => worker.peek_status(light.ip(), &request_id),
+            _ => worker.read_status(light.ip(), &request_id),
         }
-    } else {
-        Err(ErrorNotFound(format!("No such light: {}", light_id)))
+    }
+    .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+    let status = web::block(move || rx.recv().unwrap_or(Err(Error::NoReply)))
+        .await
+        .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+    match status {
+        Ok(status) => Ok(HttpResponse::Ok().json(status)),
+        Err(e) => Err(ErrorServiceUnavailable(format!(
+            "Failed to fetch status: {}",
+            e
+        ))),
+    }
+}
+
+/// Read a single bulb's raw `getPilot` reply
+///
+/// Everything the bulb replied with is returned as is, including the
+/// values [crate::models::LightStatus] leaves out (eg: `rssi`, `mac` and
+/// `env`). The stored light isn't updated.
+///
+/// # Path
+///   `GET /v1/room/{id}/light/{light_id}/pilot`
+///
+/// # Responses
+///   - `200`: [serde_json::Value]
+///   - `404`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = Object),
+        (status = 404, description = "Not Found", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(
+        ("id", description = "Room ID"),
+        ("light_id", description = "Light ID"),
+    )
+)]
+#[get("/v1/room/{id}/light/{light_id}/pilot")]
+async fn pilot(ids: Path<(Uuid, Uuid)>, storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
+    let (room_id, light_id) = ids.into_inner();
+
+    let light = {
+        let data = lock(&storage);
+        let room = match data.read(&room_id) {
+            Some(room) => room,
+            None => return Err(ErrorNotFound(format!("No such room: {}", room_id))),
+        };
+        match room.read(&light_id) {
+            Some(light) => light.clone(),
+            None => return Err(ErrorNotFound(format!("No such light: {}", light_id))),
+        }
+    };
+
+    let reply = web::block(move || light.get_pilot())
+        .await
+        .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+    match reply {
+        Ok(reply) => Ok(HttpResponse::Ok().json(reply)),
+        Err(e) => Err(ErrorServiceUnavailable(format!(
+            "Failed to fetch pilot: {}",
+            e
+        ))),
     }
 }
 
@@ -234,14 +869,18 @@ async fn status(
 ///   [Light]
 ///
 /// # Responses
-///   - `204`: [None]
+///   - `200`: [None]
+///   - `204`: [None] (when nothing changed)
 ///   - `404`: [String]
+///   - `500`: [String]
 ///
 #[utoipa::path(
     request_body = Light,
     responses(
-        (status = 204, description = "OK"),
+        (status = 200, description = "OK"),
+        (status = 204, description = "No Change"),
         (status = 404, description = "Not Found", body = String),
+        (status = 500, description = "Internal Server Error", body = String),
     ),
     params(
         ("id", description = "Room ID"),
@@ -257,11 +896,12 @@ async fn update_light(
     let (room_id, light_id) = ids.into_inner();
     let light = light.into_inner();
 
-    let mut data = storage.lock().unwrap();
-    if data.update_light(&room_id, &light_id, &light).is_ok() {
-        Ok(HttpResponse::Ok())
-    } else {
-        Err(ErrorNotFound(format!("Not found: {}", room_id)))
+    let mut data = lock(&storage);
+    match data.update_light(&room_id, &light_id, &light) {
+        Ok(_) => Ok(HttpResponse::Ok()),
+        Err(Error::NoChangeLight { .. }) => Ok(HttpResponse::NoContent()),
+        Err(e @ Error::Persist { .. }) => Err(ErrorInternalServerError(e.to_string())),
+        Err(e) => Err(ErrorNotFound(e.to_string())),
     }
 }
 
@@ -273,11 +913,13 @@ async fn update_light(
 /// # Responses
 ///   - `204`: [None]
 ///   - `404`: [String]
+///   - `500`: [String]
 ///
 #[utoipa::path(
     responses(
         (status = 204, description = "OK"),
         (status = 404, description = "Not Found", body = String),
+        (status = 500, description = "Internal Server Error", body = String),
     ),
     params(
         ("id", description = "Room ID"),
@@ -287,13 +929,65 @@ async fn update_light(
 #[delete("/v1/room/{id}/light/{light_id}")]
 async fn destroy(ids: Path<(Uuid, Uuid)>, storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
     let (room_id, light_id) = ids.into_inner();
-    let mut data = storage.lock().unwrap();
-    if data.delete_light(&room_id, &light_id).is_ok() {
-        Ok(HttpResponse::Ok())
-    } else {
-        Err(ErrorNotFound(format!(
+    let mut data = lock(&storage);
+    match data.delete_light(&room_id, &light_id) {
+        Ok(_) => Ok(HttpResponse::Ok()),
+        Err(e @ Error::Persist { .. }) => Err(ErrorInternalServerError(e.to_string())),
+        Err(_) => Err(ErrorNotFound(format!(
             "Not found: {} in room {}",
             light_id, room_id
-        )))
+        ))),
     }
 }
+
+/// Remove a light by its IP, from whichever room it's in
+///
+/// # Path
+///   `DELETE /v1/lights/by-ip/{ip}`
+///
+/// # Responses
+///   - `200`: [None]
+///   - `404`: [String]
+///   - `500`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK"),
+        (status = 404, description = "Not Found", body = String),
+        (status = 500, description = "Internal Server Error", body = String),
+    ),
+    params(
+        ("ip", description = "Light IPv4 address")
+    )
+)]
+#[delete("/v1/lights/by-ip/{ip}")]
+async fn destroy_by_ip(
+    ip: Path<Ipv4Addr>,
+    storage: Data<Mutex<Storage>>,
+) -> Result<impl Responder> {
+    let mut data = lock(&storage);
+    match data.delete_light_by_ip(&ip.into_inner()) {
+        Ok(_) => Ok(HttpResponse::Ok()),
+        Err(e @ Error::Persist { .. }) => Err(ErrorInternalServerError(e.to_string())),
+        Err(e) => Err(ErrorNotFound(e.to_string())),
+    }
+}
+
+/// List the IPs of all known lights, in all rooms, sorted
+///
+/// # Path
+///   `GET /v1/ips`
+///
+/// # Responses
+///   - `200`: [Vec] of [std::net::Ipv4Addr]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = Vec<String>),
+    ),
+)]
+#[get("/v1/ips")]
+async fn ips(storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
+    let data = lock(&storage);
+    Ok(HttpResponse::Ok().json(data.all_ips()))
+}
@@ -0,0 +1,126 @@
+//! Riz API routes for tidying up stored lights
+
+use std::sync::Mutex;
+
+use actix_web::{
+    error::{ErrorInternalServerError, ErrorServiceUnavailable},
+    post,
+    web::{self, Data, Query},
+    HttpResponse, Responder, Result,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    models::{LightRef, PruneAction},
+    storage::Storage,
+    sync::lock,
+};
+
+/// Default number of times each light is probed before it's unreachable
+const DEFAULT_PRUNE_ATTEMPTS: u8 = 3;
+
+/// Most times each light can be probed, see [DEFAULT_PRUNE_ATTEMPTS]
+const MAX_PRUNE_ATTEMPTS: u8 = 10;
+
+/// Optional query parameters for pruning unreachable lights
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PruneQuery {
+    /// What to do with unreachable lights, nothing changes without it
+    action: Option<PruneAction>,
+
+    /// Times each light is probed before it's unreachable (1-10, default 3)
+    attempts: Option<u8>,
+
+    /// Only report what would change
+    dry_run: Option<bool>,
+}
+
+/// Lights which didn't reply to any probe, and what was done about it
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PruneReport {
+    /// Action applied to the unreachable lights, or that would have been
+    pub action: Option<PruneAction>,
+
+    /// If the action was applied
+    pub applied: bool,
+
+    /// Lights which didn't reply
+    pub unreachable: Vec<LightRef>,
+}
+
+/// Probe every light, and disable or clear the status of unreachable ones
+///
+/// Each light is probed up to `attempts` times, lights which never reply
+/// are unreachable. With an `action`, and without `dry_run=true`, the
+/// action is applied to them. Lights are never deleted.
+///
+/// # Path
+///   `POST /v1/maintenance/prune?action=<disable|clear_status>&attempts=<u8>&dry_run=<bool>`
+///
+/// # Responses
+///   - `200`: [PruneReport]
+///   - `500`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = PruneReport),
+        (status = 500, description = "Internal Server Error", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(PruneQuery),
+)]
+#[post("/v1/maintenance/prune")]
+async fn prune(query: Query<PruneQuery>, storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
+    let attempts = query
+        .attempts
+        .unwrap_or(DEFAULT_PRUNE_ATTEMPTS)
+        .clamp(1, MAX_PRUNE_ATTEMPTS);
+
+    let rooms: Vec<_> = {
+        let data = lock(&storage);
+        data.list_sorted()
+            .into_iter()
+            .filter_map(|(id, _)| data.read(&id).map(|room| (id, room)))
+            .collect()
+    };
+
+    let unreachable = web::block(move || {
+        let mut unreachable = Vec::new();
+        for (room_id, room) in rooms {
+            for (light_id, reachable, _) in room.reachability() {
+                let light = match room.read(&light_id) {
+                    Some(light) if !reachable => light,
+                    _ => continue,
+                };
+                // the first probe was the reachability check
+                if (1..attempts).all(|_| light.ping().is_err()) {
+                    unreachable.push(LightRef::new(&room_id, &light_id));
+                }
+            }
+        }
+        unreachable
+    })
+    .await
+    .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+    let applied = match query.action {
+        Some(action) if !query.dry_run.unwrap_or(false) => {
+            if !unreachable.is_empty() {
+                if let Err(e) = lock(&storage).prune_lights(&unreachable, action) {
+                    return Err(ErrorInternalServerError(e.to_string()));
+                }
+            }
+            true
+        }
+        _ => false,
+    };
+
+    Ok(HttpResponse::Ok().json(PruneReport {
+        action: query.action,
+        applied,
+        unreachable,
+    }))
+}
@@ -0,0 +1,43 @@
+//! Riz API routes for tracking operations
+
+use std::sync::Mutex;
+
+use actix_web::{
+    error::ErrorNotFound,
+    get,
+    web::{Data, Path},
+    HttpResponse, Responder, Result,
+};
+use uuid::Uuid;
+
+use crate::{sync::lock, worker::Worker};
+
+/// Read the per-light outcomes of an operation
+///
+/// # Path
+///   `GET /v1/operations/{id}`
+///
+/// # Responses
+///   - `200`: [crate::models::Operation]
+///   - `404`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = Operation),
+        (status = 404, description = "Not Found", body = String),
+    ),
+    params(
+        ("id", description = "Operation ID")
+    )
+)]
+#[get("/v1/operations/{id}")]
+async fn read(id: Path<Uuid>, worker: Data<Mutex<Worker>>) -> Result<impl Responder> {
+    let id = id.into_inner();
+    let worker = lock(&worker);
+
+    if let Some(op) = worker.operation(&id) {
+        Ok(HttpResponse::Ok().json(op))
+    } else {
+        Err(ErrorNotFound(format!("No such operation: {}", id)))
+    }
+}
@@ -0,0 +1,140 @@
+//! Riz API routes for simulating occupancy while away
+
+use std::sync::Mutex;
+
+use actix_web::{
+    delete,
+    error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound},
+    get, put,
+    web::{Data, Json, Path},
+    HttpResponse, Responder, Result,
+};
+use uuid::Uuid;
+
+use crate::{
+    presence_schedule::{Presence, PresenceSchedule},
+    storage::Storage,
+    sync::lock,
+    Error,
+};
+
+/// Read the presence schedule
+///
+/// # Path
+///   `GET /v1/presence`
+///
+/// # Responses
+///   - `200`: [PresenceSchedule]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = PresenceSchedule),
+    ),
+)]
+#[get("/v1/presence")]
+async fn read(presence: Data<Mutex<Presence>>) -> Result<impl Responder> {
+    let presence = lock(&presence);
+    Ok(HttpResponse::Ok().json(presence.schedule()))
+}
+
+/// Replace the presence schedule, including its enabled rooms
+///
+/// # Path
+///   `PUT /v1/presence`
+///
+/// # Body
+///   [PresenceSchedule]
+///
+/// # Responses
+///   - `200`: [PresenceSchedule]
+///   - `400`: [String]
+///   - `500`: [String]
+///
+#[utoipa::path(
+    request_body = PresenceSchedule,
+    responses(
+        (status = 200, description = "OK", body = PresenceSchedule),
+        (status = 400, description = "Bad Request", body = String),
+        (status = 500, description = "Internal Server Error", body = String),
+    ),
+)]
+#[put("/v1/presence")]
+async fn update(
+    schedule: Json<PresenceSchedule>,
+    presence: Data<Mutex<Presence>>,
+) -> Result<impl Responder> {
+    let mut presence = lock(&presence);
+    match presence.set_schedule(schedule.into_inner()) {
+        Ok(_) => Ok(HttpResponse::Ok().json(presence.schedule())),
+        Err(e @ Error::InvalidSchedule(_)) => Err(ErrorBadRequest(e.to_string())),
+        Err(e) => Err(ErrorInternalServerError(e.to_string())),
+    }
+}
+
+/// Enable presence for a room
+///
+/// The room's lights are turned on and off at random during the
+/// schedule's active hours, see [PresenceSchedule]
+///
+/// # Path
+///   `PUT /v1/room/{id}/presence`
+///
+/// # Responses
+///   - `200`: [PresenceSchedule]
+///   - `404`: [String]
+///   - `500`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = PresenceSchedule),
+        (status = 404, description = "Not Found", body = String),
+        (status = 500, description = "Internal Server Error", body = String),
+    ),
+    params(
+        ("id", description = "Room ID")
+    )
+)]
+#[put("/v1/room/{id}/presence")]
+async fn enable(
+    id: Path<Uuid>,
+    storage: Data<Mutex<Storage>>,
+    presence: Data<Mutex<Presence>>,
+) -> Result<impl Responder> {
+    let id = id.into_inner();
+    if lock(&storage).read(&id).is_none() {
+        return Err(ErrorNotFound(format!("No such room: {}", id)));
+    }
+
+    let mut presence = lock(&presence);
+    match presence.enable(&id) {
+        Ok(_) => Ok(HttpResponse::Ok().json(presence.schedule())),
+        Err(e) => Err(ErrorInternalServerError(e.to_string())),
+    }
+}
+
+/// Disable presence for a room, any lights it turned on are turned off
+///
+/// # Path
+///   `DELETE /v1/room/{id}/presence`
+///
+/// # Responses
+///   - `200`: [PresenceSchedule]
+///   - `500`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = PresenceSchedule),
+        (status = 500, description = "Internal Server Error", body = String),
+    ),
+    params(
+        ("id", description = "Room ID")
+    )
+)]
+#[delete("/v1/room/{id}/presence")]
+async fn disable(id: Path<Uuid>, presence: Data<Mutex<Presence>>) -> Result<impl Responder> {
+    let mut presence = lock(&presence);
+    match presence.disable(&id.into_inner()) {
+        Ok(_) => Ok(HttpResponse::Ok().json(presence.schedule())),
+        Err(e) => Err(ErrorInternalServerError(e.to_string())),
+    }
+}
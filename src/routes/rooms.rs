@@ -1,20 +1,54 @@
 //! Riz API routes for room control
 
-use std::sync::Mutex;
+use std::{collections::HashMap, sync::Mutex, time::Duration};
 
 use actix_web::{
     delete,
-    error::{ErrorConflict, ErrorNotFound, ErrorServiceUnavailable},
-    get, patch, post,
-    web::{Data, Json, Path},
+    error::{
+        ErrorBadRequest, ErrorConflict, ErrorInternalServerError, ErrorNotFound,
+        ErrorServiceUnavailable,
+    },
+    get,
+    http::header,
+    patch, post,
+    web::{self, Data, Json, Path, Query},
     HttpResponse, Responder, Result,
 };
 use log::error;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-use crate::{models::Room, storage::Storage, worker::Worker};
+use crate::{
+    models::{LightRef, LightRequest, LightingResponse, PowerMode, Room, RoomPreset},
+    storage::Storage,
+    sync::lock,
+    worker::{Origin, Worker},
+    Error, RequestId,
+};
+
+/// Default delay between each bulb's reboot in a room
+const DEFAULT_REBOOT_DELAY_MS: u64 = 2000;
+
+/// Longest delay allowed between each bulb's reboot in a room
+const MAX_REBOOT_DELAY_MS: u64 = 60000;
+
+/// A newly created room, see [create]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreatedRoom {
+    /// The new room's ID
+    pub id: Uuid,
 
-/// Create a room
+    /// The room, with newly assigned light IDs
+    pub room: Room,
+}
+
+/// Create a room, optionally with initial lights
+///
+/// Any lights in the request are each given a new ID, client supplied
+/// light IDs are ignored. The room is only created if all lights are
+/// valid. The new room's ID based path is also returned in the
+/// `Content-Location` header
 ///
 /// # Path
 ///   `POST /v1/rooms`
@@ -23,24 +57,31 @@ use crate::{models::Room, storage::Storage, worker::Worker};
 ///   [Room]
 ///
 /// # Responses
-///   - `200`: [Uuid]
+///   - `200`: [CreatedRoom]
 ///   - `409`: [String]
+///   - `500`: [String]
 ///
 #[utoipa::path(
     request_body = Room,
     responses(
-        (status = 200, description = "OK", body = Uuid),
+        (status = 200, description = "OK", body = CreatedRoom),
         (status = 409, description = "Conflict", body = String),
+        (status = 500, description = "Internal Server Error", body = String),
     ),
 )]
 #[post("/v1/rooms")]
 async fn create(req: Json<Room>, storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
     let room = req.into_inner();
-    let mut data = storage.lock().unwrap();
-    if let Ok(id) = data.new_room(room) {
-        Ok(HttpResponse::Ok().json(id))
-    } else {
-        Err(ErrorConflict("Failed to create new room"))
+    let mut data = lock(&storage);
+    match data.new_room(room) {
+        Ok(id) => match data.read(&id) {
+            Some(room) => Ok(HttpResponse::Ok()
+                .insert_header((header::CONTENT_LOCATION, format!("/v1/room/{}", id)))
+                .json(CreatedRoom { id, room })),
+            None => Err(ErrorNotFound(format!("No such room: {}", id))),
+        },
+        Err(e @ Error::Persist { .. }) => Err(ErrorInternalServerError(e.to_string())),
+        Err(e) => Err(ErrorConflict(format!("Failed to create new room: {}", e))),
     }
 }
 
@@ -52,11 +93,13 @@ async fn create(req: Json<Room>, storage: Data<Mutex<Storage>>) -> Result<impl R
 /// # Responses
 ///   - `204`: [None]
 ///   - `404`: [String]
+///   - `500`: [String]
 ///
 #[utoipa::path(
     responses(
         (status = 204, description = "OK"),
         (status = 404, description = "Not Found", body = String),
+        (status = 500, description = "Internal Server Error", body = String),
     ),
     params(
         ("id", description = "Room ID")
@@ -65,18 +108,36 @@ async fn create(req: Json<Room>, storage: Data<Mutex<Storage>>) -> Result<impl R
 #[delete("/v1/room/{id}")]
 async fn destroy(id: Path<Uuid>, storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
     let id = id.into_inner();
-    let mut data = storage.lock().unwrap();
-    if data.delete_room(&id).is_ok() {
-        Ok(HttpResponse::Ok())
-    } else {
-        Err(ErrorNotFound(format!("Not found: {}", id)))
+    let mut data = lock(&storage);
+    match data.delete_room(&id) {
+        Ok(_) => Ok(HttpResponse::Ok()),
+        Err(e @ Error::Persist { .. }) => Err(ErrorInternalServerError(e.to_string())),
+        Err(_) => Err(ErrorNotFound(format!("Not found: {}", id))),
     }
 }
 
+/// Orders to list rooms in
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomSort {
+    /// By room name, case-insensitive
+    Name,
+}
+
+/// Optional query parameters for listing rooms
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ListQuery {
+    /// Order of the listed IDs, unordered by default
+    sort: Option<RoomSort>,
+}
+
 /// List all room IDs
 ///
+/// With `sort=name` the IDs are ordered by room name, otherwise the
+/// order may change between calls
+///
 /// # Path
-///   `GET /v1/rooms`
+///   `GET /v1/rooms?sort=<RoomSort>`
 ///
 /// # Responses
 ///   - `200`: [Vec] of [Uuid]
@@ -87,10 +148,16 @@ async fn destroy(id: Path<Uuid>, storage: Data<Mutex<Storage>>) -> Result<impl R
         (status = 200, description = "OK", body = Vec<Uuid>),
         (status = 404, description = "Not Found", body = String),
     ),
+    params(ListQuery),
 )]
 #[get("/v1/rooms")]
-async fn list(storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
-    let data = storage.lock().unwrap();
+async fn list(query: Query<ListQuery>, storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
+    let data = lock(&storage);
+    if let Some(RoomSort::Name) = query.sort {
+        let ids: Vec<_> = data.list_sorted().into_iter().map(|(id, _)| id).collect();
+        return Ok(HttpResponse::Ok().json(ids));
+    }
+
     if let Ok(ids) = data.list() {
         Ok(HttpResponse::Ok().json(ids))
     } else {
@@ -119,7 +186,7 @@ async fn list(storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
 #[get("/v1/room/{id}")]
 async fn read(id: Path<Uuid>, storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
     let id = id.into_inner();
-    let data = storage.lock().unwrap();
+    let data = lock(&storage);
 
     if let Some(room) = data.read(&id) {
         Ok(HttpResponse::Ok().json(room))
@@ -128,6 +195,42 @@ async fn read(id: Path<Uuid>, storage: Data<Mutex<Storage>>) -> Result<impl Resp
     }
 }
 
+/// Read room details by name, case-insensitively
+///
+/// The room's ID based path is returned in the `Content-Location` header
+///
+/// # Path
+///   `GET /v1/rooms/by-name/{name}`
+///
+/// # Responses
+///   - `200`: [Room]
+///   - `404`: [String]
+///   - `409`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = Room),
+        (status = 404, description = "Not Found", body = String),
+        (status = 409, description = "Conflict", body = String),
+    ),
+    params(
+        ("name", description = "Room name")
+    )
+)]
+#[get("/v1/rooms/by-name/{name}")]
+async fn read_by_name(name: Path<String>, storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
+    let name = name.into_inner();
+    let data = lock(&storage);
+
+    match data.read_by_name(&name) {
+        Ok((id, room)) => Ok(HttpResponse::Ok()
+            .insert_header((header::CONTENT_LOCATION, format!("/v1/room/{}", id)))
+            .json(room)),
+        Err(e @ Error::AmbiguousRoomName(_)) => Err(ErrorConflict(e.to_string())),
+        Err(e) => Err(ErrorNotFound(e.to_string())),
+    }
+}
+
 /// Update room details
 ///
 /// # Path
@@ -137,14 +240,18 @@ async fn read(id: Path<Uuid>, storage: Data<Mutex<Storage>>) -> Result<impl Resp
 ///   [Room]
 ///
 /// # Responses
-///   - `204`: [None]
+///   - `200`: [None]
+///   - `204`: [None] (when nothing changed)
 ///   - `404`: [String]
+///   - `500`: [String]
 ///
 #[utoipa::path(
     request_body = Room,
     responses(
-        (status = 204, description = "OK"),
+        (status = 200, description = "OK"),
+        (status = 204, description = "No Change"),
         (status = 404, description = "Not Found", body = String),
+        (status = 500, description = "Internal Server Error", body = String),
     ),
     params(
         ("id", description = "Room ID")
@@ -159,16 +266,20 @@ async fn update(
     let id = id.into_inner();
     let room = req.into_inner();
 
-    let mut data = storage.lock().unwrap();
-    if data.update_room(&id, &room).is_ok() {
-        Ok(HttpResponse::Ok())
-    } else {
-        Err(ErrorNotFound(format!("Not found: {}", id)))
+    let mut data = lock(&storage);
+    match data.update_room(&id, &room) {
+        Ok(_) => Ok(HttpResponse::Ok()),
+        Err(Error::NoChangeRoom(_)) => Ok(HttpResponse::NoContent()),
+        Err(e @ Error::Persist { .. }) => Err(ErrorInternalServerError(e.to_string())),
+        Err(_) => Err(ErrorNotFound(format!("Not found: {}", id))),
     }
 }
 
 /// Update lighting status for all bulbs in a room
 ///
+/// Lights which don't reply keep their last known status, flagged as
+/// `stale`, so the room is still returned when its bulbs are unreachable
+///
 /// # Path
 ///   `GET /v1/room/{id}/status`
 ///
@@ -192,32 +303,539 @@ async fn status(
     id: Path<Uuid>,
     data: Data<Mutex<Storage>>,
     worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
 ) -> Result<impl Responder> {
     let id = id.into_inner();
 
-    let mut room = {
-        let data = data.lock().unwrap();
+    let room = {
+        let data = lock(&data);
         match data.read(&id) {
             Some(room) => room,
             None => return Err(ErrorNotFound(format!("Not found: {}", id))),
         }
     };
 
-    match room.get_status() {
-        Ok(responses) => {
-            let mut worker = worker.lock().unwrap();
+    let (mut room, statuses) = web::block(move || {
+        let statuses = room.read_statuses();
+        (room, statuses)
+    })
+    .await
+    .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+    let mut responses = Vec::new();
+    for (light_id, fetched) in statuses {
+        match fetched {
+            Ok(live) => {
+                let ip = room.read(&light_id).map(|light| light.ip());
+                if let Some(ip) = ip {
+                    responses.push(LightingResponse::status(ip, live));
+                }
+            }
+            Err(e) => {
+                error!("[{}] No status from {}: {}", request_id, light_id, e);
+                room.mark_stale(&light_id);
+            }
+        }
+    }
+
+    let mut worker = lock(&worker);
+    for resp in responses {
+        room.process_reply(&resp);
+        if let Err(e) = worker.queue_update(resp, &request_id) {
+            error!("Failed to queue write: {}", e);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(room))
+}
+
+/// Whether a light answered a ping, see [reachability]
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LightReachability {
+    /// Light ID
+    pub light_id: Uuid,
+
+    /// If the bulb replied
+    pub reachable: bool,
+
+    /// Round trip time in milliseconds, if the bulb replied
+    pub latency_ms: Option<u64>,
+}
+
+/// Check which lights in the room answer, regardless of their settings
+///
+/// All lights are probed concurrently, including disabled ones, and
+/// nothing is stored. See [Room::reachability]
+///
+/// # Path
+///   `GET /v1/room/{id}/reachability`
+///
+/// # Responses
+///   - `200`: [Vec] of [LightReachability]
+///   - `404`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = Vec<LightReachability>),
+        (status = 404, description = "Not Found", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(
+        ("id", description = "Room ID")
+    )
+)]
+#[get("/v1/room/{id}/reachability")]
+async fn reachability(id: Path<Uuid>, storage: Data<Mutex<Storage>>) -> Result<impl Responder> {
+    let id = id.into_inner();
+    let room = match lock(&storage).read(&id) {
+        Some(room) => room,
+        None => return Err(ErrorNotFound(format!("Not found: {}", id))),
+    };
+
+    let probes = web::block(move || room.reachability())
+        .await
+        .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+    let probes: Vec<_> = probes
+        .into_iter()
+        .map(|(light_id, reachable, latency)| LightReachability {
+            light_id,
+            reachable,
+            latency_ms: latency.map(|d| d.as_millis() as u64),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(probes))
+}
+
+/// Rooms to refresh the status of, see [batch_status]
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RoomsStatusRequest {
+    /// Room IDs, duplicates are only read once
+    ids: Vec<Uuid>,
+}
+
+/// A room with refreshed status, or why its status couldn't be read
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoomStatus {
+    /// The room, with the status of all of its lights refreshed
+    Room(Room),
+
+    /// The room is unknown, or any of its lights failed to reply
+    Error(String),
+}
+
+/// Update lighting status for all bulbs in many rooms
+///
+/// All bulbs are read concurrently through the worker, ahead of any
+/// queued setting changes. Unknown rooms, and rooms with any light which
+/// failed to reply, are listed as errors.
+///
+/// # Path
+///   `POST /v1/rooms/status`
+///
+/// # Body
+///   [RoomsStatusRequest]
+///
+/// # Responses
+///   - `200`: [HashMap] of room [Uuid] to [RoomStatus]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    request_body = RoomsStatusRequest,
+    responses(
+        (status = 200, description = "OK", body = HashMap<String, RoomStatus>),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+)]
+#[post("/v1/rooms/status")]
+async fn batch_status(
+    req: Json<RoomsStatusRequest>,
+    storage: Data<Mutex<Storage>>,
+    worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
+) -> Result<impl Responder> {
+    let mut ids = req.into_inner().ids;
+    ids.sort();
+    ids.dedup();
+
+    let rooms: Vec<_> = {
+        let data = lock(&storage);
+        ids.into_iter().map(|id| (id, data.read(&id))).collect()
+    };
 
-            for resp in responses {
-                if let Err(e) = worker.queue_update(resp) {
-                    error!("Failed to queue write: {}", e);
+    let mut results = HashMap::new();
+    let mut pending = Vec::new();
+    {
+        let mut worker = lock(&worker);
+        for (id, room) in rooms {
+            let room = match room {
+                Some(room) => room,
+                None => {
+                    results.insert(id, RoomStatus::Error(format!("No such room: {}", id)));
+                    continue;
                 }
+            };
+
+            let reads: std::result::Result<Vec<_>, _> = room
+                .enabled_lights()
+                .map(|(_, light)| {
+                    worker
+                        .read_status(light.ip(), &request_id)
+                        .map(|rx| (light.ip(), rx))
+                })
+                .collect();
+            match reads {
+                Ok(reads) => pending.push((id, room, reads)),
+                Err(_) => return Err(ErrorServiceUnavailable("No available workers".to_string())),
             }
+        }
+    }
+
+    let refreshed = web::block(move || {
+        pending
+            .into_iter()
+            .map(|(id, mut room, reads)| {
+                let res = reads.into_iter().try_for_each(|(ip, rx)| {
+                    let fetched = rx.recv().unwrap_or(Err(Error::NoReply))?;
+                    room.process_reply(&LightingResponse::status(ip, fetched));
+                    Ok::<_, Error>(())
+                });
+                match res {
+                    Ok(_) => (id, RoomStatus::Room(room)),
+                    Err(e) => (
+                        id,
+                        RoomStatus::Error(format!("Failed to fetch status: {}", e)),
+                    ),
+                }
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+    results.extend(refreshed);
+    Ok(HttpResponse::Ok().json(results))
+}
+
+/// Export a room's lighting as a preset, to apply to another room
+///
+/// Each light's last known settings are listed by position, lights are
+/// ordered by IP. No IPs or IDs are included.
+///
+/// # Path
+///   `GET /v1/room/{id}/export-preset`
+///
+/// # Responses
+///   - `200`: [RoomPreset]
+///   - `404`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = RoomPreset),
+        (status = 404, description = "Not Found", body = String),
+    ),
+    params(
+        ("id", description = "Room ID")
+    )
+)]
+#[get("/v1/room/{id}/export-preset")]
+async fn export_preset(id: Path<Uuid>, data: Data<Mutex<Storage>>) -> Result<impl Responder> {
+    let id = id.into_inner();
+    let data = lock(&data);
+    match data.read(&id) {
+        Some(room) => Ok(HttpResponse::Ok().json(room.preset())),
+        None => Err(ErrorNotFound(format!("Not found: {}", id))),
+    }
+}
+
+/// Apply a preset's lighting to a room's lights, by position
+///
+/// Lights are ordered by IP, and each is sent the preset's settings for
+/// its position. Lights past the end of the preset, in a position
+/// without settings, or disabled, are left as they are.
+///
+/// # Path
+///   `POST /v1/room/{id}/import-preset`
+///
+/// # Body
+///   [RoomPreset]
+///
+/// # Responses
+///   - `200`: [Vec] of [LightRef] of the updated lights
+///   - `400`: [String]
+///   - `404`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    request_body = RoomPreset,
+    responses(
+        (status = 200, description = "OK", body = Vec<LightRef>),
+        (status = 400, description = "Bad Request", body = String),
+        (status = 404, description = "Not Found", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(
+        ("id", description = "Room ID")
+    )
+)]
+#[post("/v1/room/{id}/import-preset")]
+async fn import_preset(
+    id: Path<Uuid>,
+    preset: Json<RoomPreset>,
+    storage: Data<Mutex<Storage>>,
+    worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
+) -> Result<impl Responder> {
+    let id = id.into_inner();
+
+    for req in preset.lights().iter().flatten() {
+        if let Err(e) = req.validate() {
+            return Err(ErrorBadRequest(e.to_string()));
+        }
+    }
+
+    let requests: Vec<_> = {
+        let data = lock(&storage);
+        match data.read(&id) {
+            Some(room) => preset
+                .requests(&room)
+                .into_iter()
+                .filter(|(_, light, _)| light.enabled())
+                .map(|(light_id, light, req)| (*light_id, light.ip(), req.clone()))
+                .collect(),
+            None => return Err(ErrorNotFound(format!("No such room: {}", id))),
+        }
+    };
+
+    let origin = Origin::api(&request_id);
+    let mut worker = lock(&worker);
+    let mut updated = Vec::with_capacity(requests.len());
+    for (light_id, ip, req) in requests {
+        if worker
+            .create_light_task(&id, &light_id, ip, req, &origin)
+            .is_err()
+        {
+            return Err(ErrorServiceUnavailable("No available workers".to_string()));
+        }
+        updated.push(LightRef::new(&id, &light_id));
+    }
+
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+/// Optional query parameters for rebooting all bulbs in a room
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RebootQuery {
+    /// Milliseconds to wait between each bulb's reboot (default 2000)
+    delay_ms: Option<u64>,
+}
+
+/// Reboot all bulbs in a room, one at a time
+///
+/// Each bulb is sent a reboot in turn, waiting `delay_ms` between them,
+/// so they don't all drop off the network at once. The returned
+/// [Operation] lists the bulbs commanded, their progress can be read
+/// from the operation's path in the `Content-Location` header
+///
+/// # Path
+///   `POST /v1/room/{id}/reboot?delay_ms=<u64>`
+///
+/// # Responses
+///   - `200`: [Operation]
+///   - `400`: [String]
+///   - `404`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = Operation),
+        (status = 400, description = "Bad Request", body = String),
+        (status = 404, description = "Not Found", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(
+        ("id", description = "Room ID"),
+        RebootQuery,
+    ),
+)]
+#[post("/v1/room/{id}/reboot")]
+async fn reboot(
+    id: Path<Uuid>,
+    query: Query<RebootQuery>,
+    storage: Data<Mutex<Storage>>,
+    worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
+) -> Result<impl Responder> {
+    let id = id.into_inner();
+
+    let delay = query.delay_ms.unwrap_or(DEFAULT_REBOOT_DELAY_MS);
+    if delay > MAX_REBOOT_DELAY_MS {
+        return Err(ErrorBadRequest(format!(
+            "delay_ms must be at most {}",
+            MAX_REBOOT_DELAY_MS
+        )));
+    }
+
+    let lights: Vec<_> = {
+        let data = lock(&storage);
+        match data.read(&id) {
+            Some(room) => room
+                .enabled_lights()
+                .map(|(light_id, light)| (*light_id, light.ip()))
+                .collect(),
+            None => return Err(ErrorNotFound(format!("No such room: {}", id))),
+        }
+    };
+
+    if lights.is_empty() {
+        return Err(ErrorNotFound(format!("No lights in room: {}", id)));
+    }
+
+    let mut worker = lock(&worker);
+    let op = match worker.create_staggered_operation(
+        &id,
+        &lights,
+        LightRequest::from_power(PowerMode::Reboot),
+        Duration::from_millis(delay),
+        &Origin::api(&request_id),
+    ) {
+        Ok(op) => op,
+        Err(_) => return Err(ErrorServiceUnavailable("No available workers".to_string())),
+    };
+
+    match worker.operation(&op) {
+        Some(operation) => Ok(HttpResponse::Ok()
+            .insert_header((header::CONTENT_LOCATION, format!("/v1/operations/{}", op)))
+            .json(operation)),
+        None => Err(ErrorNotFound(format!("No such operation: {}", op))),
+    }
+}
+
+/// Reset all bulbs in a room to a safe default
+///
+/// A recovery action for when a scene or color leaves the room unusable.
+/// The default is warm white at full brightness, turned on, or set with
+/// `RIZ_RESET_REQUEST` (env var), see [LightRequest::reset]. A request
+/// body replaces the default for this reset only. Progress for each
+/// bulb can be read from the returned operation ID with
+/// `GET /v1/operations/{id}`
+///
+/// # Path
+///   `POST /v1/room/{id}/reset`
+///
+/// # Body
+///   [LightRequest] (optional)
+///
+/// # Responses
+///   - `200`: [Uuid]
+///   - `400`: [String]
+///   - `404`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    request_body(content = LightRequest, description = "Optional, replaces the safe default"),
+    responses(
+        (status = 200, description = "OK", body = Uuid),
+        (status = 400, description = "Bad Request", body = String),
+        (status = 404, description = "Not Found", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(
+        ("id", description = "Room ID")
+    )
+)]
+#[post("/v1/room/{id}/reset")]
+async fn reset(
+    id: Path<Uuid>,
+    body: web::Bytes,
+    storage: Data<Mutex<Storage>>,
+    worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
+) -> Result<impl Responder> {
+    let id = id.into_inner();
+
+    let req = if body.is_empty() {
+        LightRequest::reset()
+    } else {
+        match serde_json::from_slice::<LightRequest>(&body) {
+            Ok(req) => req,
+            Err(e) => return Err(ErrorBadRequest(format!("Invalid request: {}", e))),
+        }
+    };
+    if let Err(e) = req.validate() {
+        return Err(ErrorBadRequest(e.to_string()));
+    }
+    if req.partial_color().is_some() {
+        return Err(ErrorBadRequest(
+            "partial_color is only supported for single lights",
+        ));
+    }
 
-            Ok(HttpResponse::Ok().json(room))
+    let lights: Vec<_> = {
+        let data = lock(&storage);
+        match data.read(&id) {
+            Some(room) => room
+                .enabled_lights()
+                .map(|(light_id, light)| (*light_id, light.ip()))
+                .collect(),
+            None => return Err(ErrorNotFound(format!("No such room: {}", id))),
         }
-        Err(e) => Err(ErrorServiceUnavailable(format!(
-            "Failed to fetch status: {}",
-            e
-        ))),
+    };
+
+    if lights.is_empty() {
+        return Err(ErrorNotFound(format!("No lights in room: {}", id)));
+    }
+
+    match lock(&worker).create_operation(&id, &lights, req, &Origin::api(&request_id)) {
+        Ok(op) => Ok(HttpResponse::Ok().json(op)),
+        Err(_) => Err(ErrorServiceUnavailable("No available workers".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, net::Ipv4Addr};
+
+    use actix_web::{test, App};
+
+    use super::*;
+    use crate::models::Light;
+
+    #[actix_web::test]
+    async fn create_returns_room_id() {
+        let mut base = env::temp_dir();
+        base.push(Uuid::new_v4().to_string());
+        fs::create_dir_all(&base).unwrap();
+        let file_path = base.join("rooms.json").to_string_lossy().to_string();
+
+        let storage = Data::new(Mutex::new(Storage::with_path(file_path)));
+        let app = test::init_service(App::new().app_data(storage.clone()).service(create)).await;
+
+        let mut room = Room::new("test");
+        room.new_light(Light::new(Ipv4Addr::new(10, 1, 2, 3), None))
+            .unwrap();
+        let req = test::TestRequest::post()
+            .uri("/v1/rooms")
+            .set_json(&room)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let location = resp.headers().get(header::CONTENT_LOCATION).cloned();
+        let created: CreatedRoom = test::read_body_json(resp).await;
+        assert_eq!(
+            location.unwrap().to_str().unwrap(),
+            format!("/v1/room/{}", created.id)
+        );
+
+        let stored = lock(&storage).read(&created.id).unwrap();
+        assert_eq!(stored.name(), "test");
+        let (light_id, _) = created.room.iter_lights().next().unwrap();
+        assert!(stored.read(light_id).is_some());
+
+        fs::remove_dir_all(base).unwrap();
     }
 }
@@ -0,0 +1,151 @@
+//! Riz API routes for scene details
+
+use std::sync::Mutex;
+
+use actix_web::{
+    error::{ErrorNotFound, ErrorServiceUnavailable},
+    get,
+    web::{self, Data, Query},
+    HttpResponse, Responder, Result,
+};
+use log::error;
+use serde::Deserialize;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::{
+    models::{LightingResponse, SceneName, SceneNames},
+    storage::Storage,
+    sync::lock,
+    worker::Worker,
+    RequestId,
+};
+
+/// Optional filters for listing scenes
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ScenesQuery {
+    /// Only include scenes this light ID supports
+    light: Option<Uuid>,
+
+    /// Group scenes by their category
+    group: Option<bool>,
+}
+
+/// List all scenes with their display names
+///
+/// With `group=true` the scenes are returned as an object of
+/// [crate::models::SceneCategory] to the scenes in that category
+///
+/// With `light=<uuid>` the light's model is read from its cached
+/// capabilities, probing (and caching) them on first use
+///
+/// # Path
+///   `GET /v1/scenes?light=<uuid>&group=<bool>`
+///
+/// # Responses
+///   - `200`: [Vec] of [crate::models::SceneName]
+///   - `404`: [String]
+///   - `503`: [String]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = Vec<SceneName>),
+        (status = 404, description = "Not Found", body = String),
+        (status = 503, description = "Unavailable", body = String),
+    ),
+    params(ScenesQuery),
+)]
+#[get("/v1/scenes")]
+async fn list(
+    query: Query<ScenesQuery>,
+    names: Data<SceneNames>,
+    storage: Data<Mutex<Storage>>,
+    worker: Data<Mutex<Worker>>,
+    request_id: RequestId,
+) -> Result<impl Responder> {
+    let scenes = match query.light {
+        Some(light_id) => {
+            supported_scenes(&light_id, &names, &storage, &worker, &request_id).await?
+        }
+        None => names.list(),
+    };
+
+    if query.group.unwrap_or(false) {
+        Ok(HttpResponse::Ok().json(SceneName::group(scenes)))
+    } else {
+        Ok(HttpResponse::Ok().json(scenes))
+    }
+}
+
+/// List only the favorite scenes, set with `RIZ_FAVORITE_SCENES` (env var)
+///
+/// # Path
+///   `GET /v1/scenes/favorites`
+///
+/// # Responses
+///   - `200`: [Vec] of [crate::models::SceneName]
+///
+#[utoipa::path(
+    responses(
+        (status = 200, description = "OK", body = Vec<SceneName>),
+    ),
+)]
+#[get("/v1/scenes/favorites")]
+async fn favorites(names: Data<SceneNames>) -> Result<impl Responder> {
+    Ok(HttpResponse::Ok().json(names.list_favorites()))
+}
+
+/// List the scenes the light can play, by its model
+///
+/// The light's cached capabilities are used when known, otherwise the
+/// light is asked for its system config, which is then cached
+async fn supported_scenes(
+    light_id: &Uuid,
+    names: &SceneNames,
+    storage: &Mutex<Storage>,
+    worker: &Mutex<Worker>,
+    request_id: &RequestId,
+) -> Result<Vec<SceneName>> {
+    let light = {
+        let storage = lock(storage);
+        let found = storage
+            .iter_lights()
+            .find(|(_, id, _)| *id == light_id)
+            .map(|(_, _, light)| light.clone());
+        found
+    };
+    let light = match light {
+        Some(light) => light,
+        None => return Err(ErrorNotFound(format!("No such light: {}", light_id))),
+    };
+
+    if let Some(capabilities) = light.capabilities() {
+        return Ok(match capabilities.module_name() {
+            Some(module_name) => names.list_supported(module_name),
+            None => names.list(),
+        });
+    }
+
+    let ip = light.ip();
+    let config = web::block(move || light.get_system_config())
+        .await
+        .map_err(|e| ErrorServiceUnavailable(e.to_string()))?;
+
+    match config {
+        Ok(config) => {
+            let scenes = match config.module_name() {
+                Some(module_name) => names.list_supported(module_name),
+                None => names.list(),
+            };
+            let resp = LightingResponse::config(ip, config);
+            if let Err(e) = lock(worker).queue_update(resp, request_id) {
+                error!("Failed to queue write: {}", e);
+            }
+            Ok(scenes)
+        }
+        Err(e) => Err(ErrorServiceUnavailable(format!(
+            "Failed to fetch system config: {}",
+            e
+        ))),
+    }
+}
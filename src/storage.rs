@@ -1,15 +1,30 @@
-use std::{collections::HashMap, env, fs, net::Ipv4Addr, path::Path};
+use std::{
+    collections::{HashMap, VecDeque},
+    env, fmt, fs,
+    net::Ipv4Addr,
+    path::Path,
+    time::Instant,
+};
 
 use ipnet::Ipv4Net;
-use log::{error, warn};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{
-    models::{Light, LightingResponse, Room},
+    models::{Light, LightRef, LightingResponse, PruneAction, Room},
     Error, Result,
 };
 
-const STORAGE_ENV_KEY: &str = "RIZ_STORAGE_PATH";
+pub(crate) const STORAGE_ENV_KEY: &str = "RIZ_STORAGE_PATH";
+const ALLOW_PUBLIC_IPS_ENV_KEY: &str = "RIZ_ALLOW_PUBLIC_IPS";
+
+/// Number of recent `rooms.json` writes to count failures over
+const WRITE_WINDOW: usize = 20;
+
+/// Failed writes within the window at which storage is reported unhealthy
+const WRITE_FAILURE_LIMIT: usize = 5;
 
 /// Reads and syncs with `rooms.json` in `RIZ_STORAGE_PATH` (env var)
 ///
@@ -32,19 +47,138 @@ const STORAGE_ENV_KEY: &str = "RIZ_STORAGE_PATH";
 pub struct Storage {
     rooms: HashMap<Uuid, Room>,
     file_path: String,
+    writes: WriteStats,
+    observer: Option<Observer>,
+    dirty: bool,
+    allow_public_ips: bool,
+}
+
+/// Callback for each reply which updated a light, see [Storage::set_observer]
+struct Observer(Box<dyn Fn(&LightingResponse) + Send>);
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+/// Recent `rooms.json` write outcomes, see [Storage::write_health]
+#[derive(Default, Debug)]
+struct WriteStats {
+    last_ms: Option<u64>,
+    recent: VecDeque<bool>,
+    total_failures: u64,
+}
+
+impl WriteStats {
+    fn record(&mut self, elapsed_ms: u64, ok: bool) {
+        self.last_ms = Some(elapsed_ms);
+        if self.recent.len() >= WRITE_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(ok);
+        if !ok {
+            self.total_failures += 1;
+        }
+    }
+}
+
+/// Latency and failures of recent `rooms.json` writes
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WriteHealth {
+    /// Time taken by the last write, in milliseconds, if any
+    pub last_write_ms: Option<u64>,
+
+    /// Failed writes of the last `window` writes
+    pub recent_failures: usize,
+
+    /// Number of recent writes counted
+    pub window: usize,
+
+    /// Failed writes since startup
+    pub total_failures: u64,
+
+    /// False once recent failures reach the limit
+    pub healthy: bool,
+
+    /// True while there are changes not yet written, see [Storage::is_dirty]
+    pub dirty: bool,
 }
 
 impl Storage {
     /// Create a new Stoage object (should only do this once)
     pub fn new() -> Self {
-        let file_path = Self::get_storage_path();
+        Self::with_path(Self::get_storage_path())
+    }
+
+    /// Create a Storage object kept at `file_path`, ignoring `RIZ_STORAGE_PATH`
+    pub(crate) fn with_path(file_path: String) -> Self {
         let mut rooms = Self::read_json(&file_path);
 
         for (id, room) in rooms.iter_mut() {
             room.link(id);
         }
 
-        Storage { rooms, file_path }
+        Storage {
+            rooms,
+            file_path,
+            writes: WriteStats::default(),
+            observer: None,
+            dirty: false,
+            allow_public_ips: env::var(ALLOW_PUBLIC_IPS_ENV_KEY).map_or(false, |v| v == "true"),
+        }
+    }
+
+    /// Check if lights may have public (non-private) IPs
+    ///
+    /// Off unless `RIZ_ALLOW_PUBLIC_IPS=true` (env var), for bulbs reached
+    /// over routed networks (eg: a VPN) with public addressing. Loopback,
+    /// link local, unspecified, broadcast and multicast IPs are still
+    /// refused.
+    ///
+    /// NB: with this on, anyone who can use the API can make Riz send UDP
+    ///     to any address on the internet, and read back the replies.
+    ///     Only enable it when the API is not reachable by untrusted
+    ///     clients.
+    ///
+    pub fn allow_public_ips(&self) -> bool {
+        self.allow_public_ips
+    }
+
+    /// Call the observer with each reply which updates a light
+    ///
+    /// Useful to react to every state change when embedding Riz, it's
+    /// called whether or not `rooms.json` is then written. Replaces any
+    /// previous observer. Unlike the other `&mut` methods, this doesn't
+    /// write `rooms.json`.
+    ///
+    /// NB: the observer runs while the storage is borrowed (and usually
+    ///     locked), so it mustn't lock the storage itself
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use riz::Storage;
+    ///
+    /// let mut storage = Storage::new();
+    /// storage.set_observer(|resp| println!("{} updated", resp.ip()));
+    /// ```
+    ///
+    pub fn set_observer<F>(&mut self, observer: F)
+    where
+        F: Fn(&LightingResponse) + Send + 'static,
+    {
+        self.observer = Some(Observer(Box::new(observer)));
+    }
+
+    /// Stop calling the observer, see [Self::set_observer]
+    pub fn clear_observer(&mut self) {
+        self.observer = None;
+    }
+
+    /// Accessor for the path to `rooms.json`
+    pub fn file_path(&self) -> &str {
+        &self.file_path
     }
 
     fn read_json(file_path: &str) -> HashMap<Uuid, Room> {
@@ -73,20 +207,94 @@ impl Storage {
     }
 
     /// Write the contents of self.rooms to rooms.json
-    fn write(&self) {
-        if let Ok(contents) = serde_json::to_string(&self.rooms) {
-            if let Err(e) = fs::write(&self.file_path, contents) {
-                error!("Failed to write JSON: {:?}", e);
-            }
-        } else {
-            error!("Failed to dump JSON");
+    ///
+    /// # Errors
+    ///   [Error::JsonDump] if the rooms could not be serialized
+    ///   [Error::Persist] if `rooms.json` could not be written
+    ///
+    fn write(&mut self) -> Result<()> {
+        // any changes are in memory only until a write succeeds
+        self.dirty = true;
+        let contents = serde_json::to_string(&self.rooms).map_err(Error::JsonDump)?;
+
+        let start = Instant::now();
+        let res = fs::write(&self.file_path, contents);
+        let elapsed = start.elapsed().as_millis() as u64;
+        self.writes.record(elapsed, res.is_ok());
+
+        self.dirty = res.is_err();
+        res.map_err(|e| Error::persist(&self.file_path, e))
+    }
+
+    /// Write a change to `rooms.json`, or undo it if the write fails
+    ///
+    /// The rooms are put back to `previous`, so a change the caller is
+    /// told failed isn't kept in memory (or written later by [Self::flush])
+    ///
+    fn write_or_restore(&mut self, previous: HashMap<Uuid, Room>) -> Result<()> {
+        let dirty = self.dirty;
+        self.write().map_err(|e| {
+            self.rooms = previous;
+            self.dirty = dirty;
+            e
+        })
+    }
+
+    /// Check if there are bulb replies which failed to be written to `rooms.json`
+    ///
+    /// These would be lost on restart, see [Self::flush]. Other changes
+    /// are undone when they can't be written, so they're never pending.
+    ///
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Retry writing `rooms.json` if the last write failed
+    ///
+    /// # Returns
+    ///   [bool] of if a write was needed (and succeeded)
+    ///
+    /// # Errors
+    ///   [Error::Persist] if `rooms.json` still could not be written
+    ///
+    pub fn flush(&mut self) -> Result<bool> {
+        if !self.dirty {
+            return Ok(false);
+        }
+        self.write().map(|_| true)
+    }
+
+    /// Report the latency and failures of recent `rooms.json` writes
+    ///
+    /// Storage is unhealthy once the last 20 writes include 5 failures,
+    /// it recovers as successful writes push the failures out
+    ///
+    pub fn write_health(&self) -> WriteHealth {
+        let recent_failures = self.writes.recent.iter().filter(|ok| !**ok).count();
+        WriteHealth {
+            last_write_ms: self.writes.last_ms,
+            recent_failures,
+            window: self.writes.recent.len(),
+            total_failures: self.writes.total_failures,
+            healthy: recent_failures < WRITE_FAILURE_LIMIT,
+            dirty: self.dirty,
         }
     }
 
-    /// Create a new room
+    /// Create a new room, optionally with initial lights
+    ///
+    /// Every initial light is given a newly generated ID, any IDs in the
+    /// given room's lights are ignored. The room is only stored if all
+    /// of its lights are valid.
+    ///
+    /// NB: for all mutating methods, a change which can't be written to
+    ///     `rooms.json` is undone and the error is returned, only bulb
+    ///     replies are kept to be written later, see [Self::process_replies]
     ///
     /// # Errors
-    ///   [Error::InvalidIP] if any light in the new room has an invalid IP address
+    ///   [Error::InvalidIP] if any light in the new room has an invalid or duplicate IP address
+    ///   [Error::TooManyLights] if the new room has more than [Room::max_lights]
+    ///   [Error::Persist] if `rooms.json` could not be written
     ///
     pub fn new_room(&mut self, room: Room) -> Result<Uuid> {
         let mut id = Uuid::new_v4();
@@ -94,23 +302,37 @@ impl Storage {
             id = Uuid::new_v4();
         }
 
-        // ensure any lights ips in the new room are valid (should be empty...)
+        // ensure any lights ips in the new room are valid
         self.validate_room(&room)?;
 
         let mut room = room;
         room.link(&id);
+        room.reassign_light_ids()?;
 
+        let previous = self.rooms.clone();
         self.rooms.insert(id, room);
-        self.write();
+        self.write_or_restore(previous)?;
         Ok(id)
     }
 
     /// Create a new light in the room
+    ///
+    /// # Errors
+    ///   [Error::RoomNotFound] if the room doesn't exist
+    ///   [Error::InvalidIP] if the light has an invalid or duplicate IP address
+    ///   [Error::Persist] if `rooms.json` could not be written
+    ///
     pub fn new_light(&mut self, room: &Uuid, light: Light) -> Result<Uuid> {
-        self.validate_light(&light)?;
+        let subnet = match self.rooms.get(room) {
+            Some(entry) => entry.subnet().copied(),
+            None => return Err(Error::RoomNotFound(*room)),
+        };
+
+        self.validate_light(&light, subnet.as_ref())?;
+        let previous = self.rooms.clone();
         if let Some(entry) = self.rooms.get_mut(room) {
             let id = entry.new_light(light)?;
-            self.write();
+            self.write_or_restore(previous)?;
             Ok(id)
         } else {
             Err(Error::RoomNotFound(*room))
@@ -122,12 +344,32 @@ impl Storage {
         self.rooms.get(room).cloned()
     }
 
-    /// Updates non-light attributes (currently just name)
+    /// Read a room by name, case-insensitively (returns clone)
+    ///
+    /// # Errors
+    ///   [Error::RoomNameNotFound] if no room has the name
+    ///   [Error::AmbiguousRoomName] if more than one room has the name
+    ///
+    pub fn read_by_name(&self, name: &str) -> Result<(Uuid, Room)> {
+        let name = name.to_lowercase();
+        let mut matches = self
+            .rooms
+            .iter()
+            .filter(|(_, room)| room.name().to_lowercase() == name);
+
+        match (matches.next(), matches.next()) {
+            (Some((id, room)), None) => Ok((*id, room.clone())),
+            (Some(_), Some(_)) => Err(Error::AmbiguousRoomName(name)),
+            (None, _) => Err(Error::RoomNameNotFound(name)),
+        }
+    }
+
+    /// Updates non-light attributes (name, subnet)
     pub fn update_room(&mut self, id: &Uuid, room: &Room) -> Result<()> {
+        let previous = self.rooms.clone();
         if let Some(entry) = self.rooms.get_mut(id) {
             if entry.update(room) {
-                self.write();
-                Ok(())
+                self.write_or_restore(previous)
             } else {
                 Err(Error::NoChangeRoom(*id))
             }
@@ -136,12 +378,12 @@ impl Storage {
         }
     }
 
-    /// Update non-lighting attributes of the light in the room (name, ip)
+    /// Update non-lighting attributes of the light in the room (name, nickname, ip)
     pub fn update_light(&mut self, id: &Uuid, light_id: &Uuid, light: &Light) -> Result<()> {
+        let previous = self.rooms.clone();
         if let Some(room) = self.rooms.get_mut(id) {
             room.update_light(light_id, light)?;
-            self.write();
-            Ok(())
+            self.write_or_restore(previous)
         } else {
             Err(Error::light_not_found(id, light_id))
         }
@@ -149,64 +391,194 @@ impl Storage {
 
     /// Remove a room
     pub fn delete_room(&mut self, room: &Uuid) -> Result<()> {
+        let previous = self.rooms.clone();
         match self.rooms.remove(room) {
-            Some(_) => {
-                self.write();
-                Ok(())
-            }
+            Some(_) => self.write_or_restore(previous),
             None => Err(Error::RoomNotFound(*room)),
         }
     }
 
     /// Remove a light in a room
     pub fn delete_light(&mut self, room: &Uuid, light: &Uuid) -> Result<()> {
+        let previous = self.rooms.clone();
         match self.rooms.get_mut(room) {
             Some(rm) => {
                 rm.delete_light(light)?;
-                self.write();
-                Ok(())
+                self.write_or_restore(previous)
             }
             None => Err(Error::RoomNotFound(*room)),
         }
     }
 
+    /// Remove a light by its IP, from whichever room it's in
+    ///
+    /// IPs (including aliases) are unique across all rooms, so at most
+    /// one light matches
+    ///
+    /// # Errors
+    ///   [Error::LightIpNotFound] if no light has the IP
+    ///
+    pub fn delete_light_by_ip(&mut self, ip: &Ipv4Addr) -> Result<()> {
+        let (room_id, light_id) = match self.iter_lights().find(|(_, _, light)| light.has_ip(*ip)) {
+            Some((room_id, light_id, _)) => (*room_id, *light_id),
+            None => return Err(Error::LightIpNotFound(*ip)),
+        };
+        self.delete_light(&room_id, &light_id)
+    }
+
+    /// Apply the prune action to each of the lights, see [Light::prune]
+    ///
+    /// Unknown lights are skipped, all changes are written at once
+    ///
+    pub fn prune_lights(&mut self, lights: &[LightRef], action: PruneAction) -> Result<()> {
+        let previous = self.rooms.clone();
+        for (room_id, light_id, light) in self.iter_lights_mut() {
+            if lights.contains(&LightRef::new(room_id, light_id)) {
+                light.prune(action);
+            }
+        }
+        self.write_or_restore(previous)
+    }
+
+    /// Iterate over all lights in all rooms
+    ///
+    /// # Returns
+    ///   [Iterator] of (room ID, light ID, [Light])
+    ///
+    pub fn iter_lights(&self) -> impl Iterator<Item = (&Uuid, &Uuid, &Light)> {
+        self.rooms.iter().flat_map(|(room_id, room)| {
+            room.iter_lights()
+                .map(move |(light_id, light)| (room_id, light_id, light))
+        })
+    }
+
+    /// Iterate over all lights in all rooms mutably
+    ///
+    /// NB: changes made through this iterator are not written to
+    ///     `rooms.json` until the next write from another method
+    ///
+    /// # Returns
+    ///   [Iterator] of (room ID, light ID, &mut [Light])
+    ///
+    pub fn iter_lights_mut(&mut self) -> impl Iterator<Item = (&Uuid, &Uuid, &mut Light)> {
+        self.rooms.iter_mut().flat_map(|(room_id, room)| {
+            room.iter_lights_mut()
+                .map(move |(light_id, light)| (room_id, light_id, light))
+        })
+    }
+
+    /// Find a light in any room by its IP, or one of its aliases
+    pub fn light_by_ip(&self, ip: Ipv4Addr) -> Option<&Light> {
+        self.iter_lights()
+            .find(|(_, _, light)| light.has_ip(ip))
+            .map(|(_, _, light)| light)
+    }
+
+    /// List the IPs (including aliases) of all lights in all rooms, sorted
+    pub fn all_ips(&self) -> Vec<Ipv4Addr> {
+        let mut ips: Vec<_> = self
+            .iter_lights()
+            .flat_map(|(_, _, light)| light.ips())
+            .collect();
+        ips.sort();
+        ips
+    }
+
     /// List room IDs
     pub fn list(&self) -> Result<Vec<&Uuid>> {
         Ok(self.rooms.keys().collect())
     }
 
+    /// List room IDs with their names, sorted by name (case-insensitive)
+    ///
+    /// Rooms with the same name are ordered by ID, so the order is stable
+    ///
+    pub fn list_sorted(&self) -> Vec<(Uuid, String)> {
+        let mut rooms: Vec<_> = self
+            .rooms
+            .iter()
+            .map(|(id, room)| (*id, room.name().to_string()))
+            .collect();
+        rooms.sort_by_cached_key(|(id, name)| (name.to_lowercase(), *id));
+        rooms
+    }
+
     /// Process the response of a lighting request
-    pub fn process_reply(&mut self, resp: &LightingResponse) {
+    ///
+    /// # Returns
+    ///   [bool] of if any light was updated (and `rooms.json` written)
+    ///
+    /// # Errors
+    ///   [Error::Persist] if `rooms.json` could not be written
+    ///
+    pub fn process_reply(&mut self, resp: &LightingResponse) -> Result<bool> {
+        self.process_replies(std::slice::from_ref(resp))
+    }
+
+    /// Process a batch of lighting responses, writing at most once
+    ///
+    /// Unlike other changes, replies are kept in memory when `rooms.json`
+    /// can't be written, as they're what the bulbs are actually showing.
+    /// They're written later by [Self::flush], see [Self::is_dirty].
+    ///
+    /// # Returns
+    ///   [bool] of if any light was updated (and `rooms.json` written)
+    ///
+    /// # Errors
+    ///   [Error::Persist] if `rooms.json` could not be written
+    ///
+    pub fn process_replies(&mut self, resps: &[LightingResponse]) -> Result<bool> {
         let mut any_update = false;
-        for room in self.rooms.values_mut() {
-            let room_update = room.process_reply(resp);
-            any_update = any_update || room_update;
+        for resp in resps {
+            let update = self.apply_reply(resp);
+            if update {
+                if let Some(Observer(observer)) = &self.observer {
+                    observer(resp);
+                }
+            }
+            any_update = any_update || update;
         }
 
         if any_update {
-            self.write();
+            self.write()?;
         }
+        Ok(any_update)
+    }
+
+    /// Update the in-memory state of all rooms from the response
+    fn apply_reply(&mut self, resp: &LightingResponse) -> bool {
+        let mut any_update = false;
+        for room in self.rooms.values_mut() {
+            let room_update = room.process_reply(resp);
+            any_update = any_update || room_update;
+        }
+        any_update
     }
 
     /// Check if all lights in the room are valid and unique
     fn validate_room(&self, room: &Room) -> Result<()> {
-        if let Some(lights) = room.list() {
-            for light_id in lights {
-                if let Some(light) = room.read(light_id) {
-                    self.validate_light(light)?;
-                }
-            }
+        let max = Room::max_lights();
+        if room.iter_lights().count() > max {
+            return Err(Error::too_many_lights(&room.id(), max));
+        }
+
+        for (_, light) in room.iter_lights() {
+            self.validate_light(light, room.subnet())?;
         }
         Ok(())
     }
 
-    /// Check if the light's ip is valid and unqiue
-    fn validate_light(&self, light: &Light) -> Result<()> {
-        self.validate_ip(&light.ip())
+    /// Check if the light's ips (including aliases) are valid and unqiue
+    fn validate_light(&self, light: &Light, subnet: Option<&Ipv4Net>) -> Result<()> {
+        light.ips().try_for_each(|ip| self.validate_ip(&ip, subnet))
     }
 
     /// Check if the IP is valid and unique
-    fn validate_ip(&self, ip: &Ipv4Addr) -> Result<()> {
+    ///
+    /// The network and broadcast address checks use the subnet when it
+    /// contains the IP, otherwise the IP's classful network
+    ///
+    fn validate_ip(&self, ip: &Ipv4Addr, subnet: Option<&Ipv4Net>) -> Result<()> {
         // || ip.is_benchmarking() can be added once stable
         if ip.is_documentation() {
             return self.unique_ip(ip);
@@ -233,20 +605,26 @@ impl Storage {
         //     return Err(Error::invalid_ip(ip, "a reserved ip"));
         // }
 
-        if !ip.is_private() {
+        if !ip.is_private() && !self.allow_public_ips {
             return Err(Error::invalid_ip(ip, "a public ip"));
         }
 
         // check if this IP is a subnet broadcast or network address
-        if let Some(net) = classful_network(ip) {
+        let net = match subnet {
+            Some(net) if net.contains(ip) => Some(*net),
+            _ => classful_network(ip),
+        };
+
+        if let Some(net) = net {
             // NB: because we are probably behind docker, we can't
             //     really tell what our local network is, without
             //     probing around... which we probably shouldn't do.
             //     otherwise, it would be possible to limit the IPs
             //     to the actual connected networks. but as we've
-            //     already limited them to private IPs this is fine.
+            //     already limited them to private IPs (unless public
+            //     IPs are allowed) this is fine.
             //     it won't correctly pick up classless setups though,
-            //     again because docker. ¯\_(ツ)_/¯ oh well
+            //     again because docker, unless the room has a subnet.
 
             if *ip == net.network() {
                 return Err(Error::invalid_ip(ip, "the subnet's network address"));
@@ -265,16 +643,8 @@ impl Storage {
 
     /// Check if the IP is unique
     fn unique_ip(&self, ip: &Ipv4Addr) -> Result<()> {
-        for room in self.rooms.values() {
-            if let Some(lights) = room.list() {
-                for light_id in lights {
-                    if let Some(light) = room.read(light_id) {
-                        if *ip == light.ip() {
-                            return Err(Error::invalid_ip(ip, "already known"));
-                        }
-                    }
-                }
-            }
+        if self.iter_lights().any(|(_, _, light)| light.has_ip(*ip)) {
+            return Err(Error::invalid_ip(ip, "already known"));
         }
         Ok(())
     }
@@ -292,14 +662,17 @@ fn classful_network(ip: &Ipv4Addr) -> Option<Ipv4Net> {
 #[cfg(test)]
 mod tests {
     use rand::{distributions::Alphanumeric, Rng};
-    use std::{env, panic, str::FromStr, vec};
+    use std::{env, panic, str::FromStr, sync::mpsc, time::Duration, vec};
+
+    use log::error;
 
     use super::*;
+    use crate::models::PowerMode;
 
     /// Run the closure test with a new temp test storage, and clean up after
-    fn test_storage<T>(test: T) -> ()
+    fn test_storage<T>(test: T)
     where
-        T: FnOnce() -> () + panic::UnwindSafe,
+        T: FnOnce() + panic::UnwindSafe,
     {
         let s: String = rand::thread_rng()
             .sample_iter(&Alphanumeric)
@@ -309,9 +682,10 @@ mod tests {
 
         let mut base = env::temp_dir();
         base.push(s);
+        fs::create_dir_all(&base).unwrap();
         env::set_var(STORAGE_ENV_KEY, base.clone());
 
-        let res = panic::catch_unwind(|| test());
+        let res = panic::catch_unwind(test);
 
         fs::remove_dir_all(base).unwrap_or_else(|_| error!("failed to clean up tmp storage"));
 
@@ -330,6 +704,50 @@ mod tests {
         assert_eq!(res, Err(Error::invalid_ip(&ip, "already known")));
     }
 
+    #[test]
+    fn max_lights_per_room() {
+        let mut room = Room::new("test");
+        for i in 1..=100 {
+            let ip = Ipv4Addr::new(10, 0, 0, i);
+            assert!(room.new_light(Light::new(ip, None)).is_ok());
+        }
+
+        let res = room.new_light(Light::new(Ipv4Addr::new(10, 0, 0, 101), None));
+        assert_eq!(res, Err(Error::too_many_lights(&room.id(), 100)));
+    }
+
+    #[test]
+    fn alias_ips_unique() {
+        test_storage(|| {
+            let ip = Ipv4Addr::new(192, 0, 2, 3);
+            let alias = Ipv4Addr::new(192, 0, 2, 4);
+
+            let mut room = Room::new("test");
+            room.new_light(Light::new(ip, None).with_aliases(&[alias]))
+                .unwrap();
+
+            let mut storage = Storage::new();
+            let room_id = storage.new_room(room).unwrap();
+            assert!(storage.light_by_ip(alias).is_some());
+            assert_eq!(storage.all_ips(), vec![ip, alias]);
+
+            let res = storage.new_light(&room_id, Light::new(alias, None));
+            assert_eq!(res, Err(Error::invalid_ip(&alias, "already known")));
+
+            let other = Ipv4Addr::new(192, 0, 2, 5);
+            let light = Light::new(other, None).with_aliases(&[ip]);
+            let res = storage.new_light(&room_id, light);
+            assert_eq!(res, Err(Error::invalid_ip(&ip, "already known")));
+
+            let light = Light::new(other, None).with_aliases(&[other]);
+            let res = storage.new_light(&room_id, light);
+            assert_eq!(
+                res,
+                Err(Error::invalid_ip(&other, "repeated in the light's aliases"))
+            );
+        })
+    }
+
     #[test]
     fn unique_ips_different_rooms() {
         test_storage(|| {
@@ -350,6 +768,76 @@ mod tests {
         })
     }
 
+    #[test]
+    fn rooms_sorted_by_name() {
+        test_storage(|| {
+            let mut storage = Storage::new();
+            for name in ["kitchen", "Bedroom", "attic", "bedroom"] {
+                storage.new_room(Room::new(name)).unwrap();
+            }
+
+            let names: Vec<_> = storage
+                .list_sorted()
+                .into_iter()
+                .map(|(_, name)| name.to_lowercase())
+                .collect();
+            assert_eq!(names, vec!["attic", "bedroom", "bedroom", "kitchen"]);
+        })
+    }
+
+    #[test]
+    fn new_room_with_lights() {
+        test_storage(|| {
+            let client_id = Uuid::new_v4();
+            let ip1 = Ipv4Addr::from_str("10.1.2.3").unwrap();
+            let ip2 = Ipv4Addr::from_str("10.1.2.4").unwrap();
+
+            let room: Room = serde_json::from_value(serde_json::json!({
+                "name": "furnished",
+                "lights": {
+                    client_id.to_string(): {"ip": ip1, "name": "lamp"},
+                    Uuid::new_v4().to_string(): {"ip": ip2},
+                },
+            }))
+            .unwrap();
+
+            let mut storage = Storage::new();
+            let room_id = storage.new_room(room).unwrap();
+
+            let room = storage.read(&room_id).unwrap();
+            assert_eq!(room.iter_lights().count(), 2);
+            assert!(room.read(&client_id).is_none());
+
+            let (_, lamp) = room
+                .iter_lights()
+                .find(|(_, light)| light.ip() == ip1)
+                .unwrap();
+            assert_eq!(lamp.name(), Some("lamp"));
+        })
+    }
+
+    #[test]
+    fn new_room_duplicate_ips() {
+        test_storage(|| {
+            let ip = Ipv4Addr::from_str("10.1.2.3").unwrap();
+
+            let room: Room = serde_json::from_value(serde_json::json!({
+                "name": "dupes",
+                "lights": {
+                    Uuid::new_v4().to_string(): {"ip": ip},
+                    Uuid::new_v4().to_string(): {"ip": ip},
+                },
+            }))
+            .unwrap();
+
+            let mut storage = Storage::new();
+            let res = storage.new_room(room);
+
+            assert_eq!(res, Err(Error::invalid_ip(&ip, "already known")));
+            assert_eq!(storage.list().unwrap().len(), 0);
+        })
+    }
+
     #[test]
     fn new_light_unique_ip() {
         test_storage(|| {
@@ -367,6 +855,316 @@ mod tests {
         })
     }
 
+    #[test]
+    fn new_light_missing_room() {
+        test_storage(|| {
+            // the missing room is reported before the invalid ip
+            let light = Light::new(Ipv4Addr::new(8, 8, 8, 8), None);
+            let room_id = Uuid::new_v4();
+
+            let mut storage = Storage::new();
+            let res = storage.new_light(&room_id, light);
+            assert_eq!(res, Err(Error::RoomNotFound(room_id)));
+        })
+    }
+
+    #[test]
+    fn process_replies_batched() {
+        test_storage(|| {
+            let ip1 = Ipv4Addr::from_str("10.1.2.3").unwrap();
+            let ip2 = Ipv4Addr::from_str("10.1.2.4").unwrap();
+
+            let mut storage = Storage::new();
+            let room_id = storage.new_room(Room::new("test")).unwrap();
+            let id1 = storage.new_light(&room_id, Light::new(ip1, None)).unwrap();
+            let id2 = storage.new_light(&room_id, Light::new(ip2, None)).unwrap();
+
+            let resps = vec![
+                LightingResponse::power(ip1, PowerMode::Off),
+                LightingResponse::power(ip2, PowerMode::On),
+            ];
+            assert!(storage.process_replies(&resps).unwrap());
+
+            let room = storage.read(&room_id).unwrap();
+            assert!(!room.read(&id1).unwrap().status().unwrap().emitting());
+            assert!(room.read(&id2).unwrap().status().unwrap().emitting());
+
+            let unknown = Ipv4Addr::from_str("10.1.2.5").unwrap();
+            let resps = vec![LightingResponse::power(unknown, PowerMode::On)];
+            assert!(!storage.process_replies(&resps).unwrap());
+        })
+    }
+
+    /// Block writes to `rooms.json` by putting a directory in its place
+    fn block_writes(storage: &Storage) {
+        let _ = fs::remove_file(&storage.file_path);
+        fs::create_dir(&storage.file_path).unwrap();
+    }
+
+    #[test]
+    fn write_errors_returned() {
+        test_storage(|| {
+            let ip = Ipv4Addr::from_str("10.1.2.3").unwrap();
+
+            let mut storage = Storage::new();
+            let room_id = storage.new_room(Room::new("test")).unwrap();
+            let light_id = storage.new_light(&room_id, Light::new(ip, None)).unwrap();
+
+            block_writes(&storage);
+
+            let res = storage.new_room(Room::new("test2"));
+            assert!(matches!(res, Err(Error::Persist { .. })));
+            assert_eq!(storage.list().unwrap().len(), 1);
+
+            let res = storage.update_room(&room_id, &Room::new("renamed"));
+            assert!(matches!(res, Err(Error::Persist { .. })));
+            assert_eq!(storage.read(&room_id).unwrap().name(), "test");
+
+            let res = storage.delete_light(&room_id, &light_id);
+            assert!(matches!(res, Err(Error::Persist { .. })));
+            assert!(storage.read(&room_id).unwrap().read(&light_id).is_some());
+            assert!(!storage.is_dirty());
+
+            let res = storage.process_reply(&LightingResponse::power(ip, PowerMode::Off));
+            assert!(matches!(res, Err(Error::Persist { .. })));
+            assert!(storage.is_dirty());
+        })
+    }
+
+    #[test]
+    fn write_health_tracked() {
+        test_storage(|| {
+            let mut storage = Storage::new();
+            let room_id = storage.new_room(Room::new("test")).unwrap();
+
+            let health = storage.write_health();
+            assert!(health.healthy && health.last_write_ms.is_some());
+            assert_eq!((health.window, health.recent_failures), (1, 0));
+
+            block_writes(&storage);
+            for i in 0..WRITE_FAILURE_LIMIT {
+                assert!(storage.write_health().healthy);
+                let _ = storage.update_room(&room_id, &Room::new(&format!("test{}", i)));
+            }
+
+            let health = storage.write_health();
+            assert!(!health.healthy);
+            assert_eq!(health.recent_failures, WRITE_FAILURE_LIMIT);
+            assert_eq!(health.total_failures, WRITE_FAILURE_LIMIT as u64);
+        })
+    }
+
+    #[test]
+    fn dirty_until_flushed() {
+        test_storage(|| {
+            let ip = Ipv4Addr::from_str("10.1.2.3").unwrap();
+
+            let mut storage = Storage::new();
+            let room_id = storage.new_room(Room::new("test")).unwrap();
+            let light_id = storage.new_light(&room_id, Light::new(ip, None)).unwrap();
+            assert!(!storage.is_dirty());
+            assert!(matches!(storage.flush(), Ok(false)));
+
+            block_writes(&storage);
+            assert!(storage
+                .process_reply(&LightingResponse::power(ip, PowerMode::Off))
+                .is_err());
+            assert!(storage.is_dirty() && storage.write_health().dirty);
+            assert!(storage.flush().is_err());
+
+            fs::remove_dir_all(storage.file_path()).unwrap();
+            assert!(matches!(storage.flush(), Ok(true)));
+            assert!(!storage.is_dirty());
+            let room = Storage::new().read(&room_id).unwrap();
+            assert!(!room.read(&light_id).unwrap().status().unwrap().emitting());
+        })
+    }
+
+    #[test]
+    fn observer_called_on_update() {
+        test_storage(|| {
+            let ip = Ipv4Addr::from_str("10.1.2.3").unwrap();
+            let unknown = Ipv4Addr::from_str("10.1.2.5").unwrap();
+
+            let mut storage = Storage::new();
+            let room_id = storage.new_room(Room::new("test")).unwrap();
+            storage.new_light(&room_id, Light::new(ip, None)).unwrap();
+
+            let (tx, rx) = mpsc::channel();
+            storage.set_observer(move |resp| tx.send(resp.ip()).unwrap());
+
+            storage
+                .process_replies(&[
+                    LightingResponse::power(ip, PowerMode::Off),
+                    LightingResponse::power(unknown, PowerMode::Off),
+                ])
+                .unwrap();
+            assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![ip]);
+
+            storage.clear_observer();
+            storage
+                .process_reply(&LightingResponse::power(ip, PowerMode::On))
+                .unwrap();
+            assert!(rx.try_recv().is_err());
+        })
+    }
+
+    #[test]
+    fn no_write_without_update() {
+        test_storage(|| {
+            let mut storage = Storage::new();
+            block_writes(&storage);
+
+            let unknown = Ipv4Addr::from_str("10.1.2.5").unwrap();
+            let res = storage.process_reply(&LightingResponse::power(unknown, PowerMode::On));
+            assert!(matches!(res, Ok(false)));
+        })
+    }
+
+    #[test]
+    fn iter_lights_all_rooms() {
+        test_storage(|| {
+            let mut storage = Storage::new();
+            let room_id = storage.new_room(Room::new("test")).unwrap();
+            let light_id = storage
+                .new_light(
+                    &room_id,
+                    Light::new(Ipv4Addr::from_str("10.1.2.3").unwrap(), None),
+                )
+                .unwrap();
+
+            let room2_id = storage.new_room(Room::new("test2")).unwrap();
+            storage
+                .new_light(
+                    &room2_id,
+                    Light::new(Ipv4Addr::from_str("10.1.2.4").unwrap(), None),
+                )
+                .unwrap();
+            storage.new_room(Room::new("empty")).unwrap();
+
+            assert_eq!(storage.iter_lights().count(), 2);
+
+            let (found_room, _, light) = storage
+                .iter_lights()
+                .find(|(_, id, _)| **id == light_id)
+                .unwrap();
+            assert_eq!(found_room, &room_id);
+            assert_eq!(light.ip(), Ipv4Addr::from_str("10.1.2.3").unwrap());
+        })
+    }
+
+    #[test]
+    fn all_ips_sorted() {
+        test_storage(|| {
+            let mut storage = Storage::new();
+
+            let mut room = Room::new("one");
+            room.new_light(Light::new(Ipv4Addr::new(10, 1, 2, 30), None))
+                .unwrap();
+            room.new_light(Light::new(Ipv4Addr::new(10, 1, 2, 4), None))
+                .unwrap();
+            storage.new_room(room).unwrap();
+
+            let mut room = Room::new("two");
+            room.new_light(Light::new(Ipv4Addr::new(10, 1, 2, 10), None))
+                .unwrap();
+            storage.new_room(room).unwrap();
+
+            assert_eq!(
+                storage.all_ips(),
+                vec![
+                    Ipv4Addr::new(10, 1, 2, 4),
+                    Ipv4Addr::new(10, 1, 2, 10),
+                    Ipv4Addr::new(10, 1, 2, 30),
+                ]
+            );
+        })
+    }
+
+    #[test]
+    fn light_found_by_ip() {
+        test_storage(|| {
+            let mut storage = Storage::new();
+
+            let ip = Ipv4Addr::new(10, 1, 2, 3);
+            let light = Light::new(ip, Some("far")).with_timeouts(Duration::from_secs(3), 2);
+            let mut room = Room::new("one");
+            room.new_light(light).unwrap();
+            storage.new_room(room).unwrap();
+
+            let found = storage.light_by_ip(ip).unwrap();
+            assert_eq!(found.name(), Some("far"));
+            assert_eq!(found.retries(), 2);
+            assert!(storage.light_by_ip(Ipv4Addr::new(10, 1, 2, 4)).is_none());
+        })
+    }
+
+    #[test]
+    fn light_deleted_by_ip() {
+        test_storage(|| {
+            let mut storage = Storage::new();
+
+            let ip = Ipv4Addr::new(10, 1, 2, 3);
+            let mut room = Room::new("one");
+            room.new_light(Light::new(ip, None)).unwrap();
+            room.new_light(Light::new(Ipv4Addr::new(10, 1, 2, 4), None))
+                .unwrap();
+            storage.new_room(room).unwrap();
+
+            storage.delete_light_by_ip(&ip).unwrap();
+            assert!(storage.light_by_ip(ip).is_none());
+            assert_eq!(storage.all_ips(), vec![Ipv4Addr::new(10, 1, 2, 4)]);
+
+            let res = storage.delete_light_by_ip(&ip);
+            assert!(matches!(res, Err(Error::LightIpNotFound(_))));
+        })
+    }
+
+    #[test]
+    fn lights_pruned() {
+        test_storage(|| {
+            let mut storage = Storage::new();
+
+            let room_id = storage.new_room(Room::new("one")).unwrap();
+            let gone = storage
+                .new_light(&room_id, Light::new(Ipv4Addr::new(10, 1, 2, 3), None))
+                .unwrap();
+            let kept = storage
+                .new_light(&room_id, Light::new(Ipv4Addr::new(10, 1, 2, 4), None))
+                .unwrap();
+
+            let pruned = [LightRef::new(&room_id, &gone)];
+            storage.prune_lights(&pruned, PruneAction::Disable).unwrap();
+
+            let room = storage.read(&room_id).unwrap();
+            assert!(!room.read(&gone).unwrap().enabled());
+            assert!(room.read(&kept).unwrap().enabled());
+        })
+    }
+
+    #[test]
+    fn read_room_by_name() {
+        test_storage(|| {
+            let mut storage = Storage::new();
+            let id = storage.new_room(Room::new("Kitchen")).unwrap();
+            storage.new_room(Room::new("den")).unwrap();
+            storage.new_room(Room::new("Den")).unwrap();
+
+            let (found, room) = storage.read_by_name("kitchen").unwrap();
+            assert_eq!(found, id);
+            assert_eq!(room.name(), "Kitchen");
+
+            let res = storage.read_by_name("DEN");
+            assert_eq!(res.err(), Some(Error::AmbiguousRoomName("den".to_string())));
+
+            let res = storage.read_by_name("attic");
+            assert_eq!(
+                res.err(),
+                Some(Error::RoomNameNotFound("attic".to_string()))
+            );
+        })
+    }
+
     #[test]
     fn invalid_ips_denied() {
         test_storage(|| {
@@ -415,4 +1213,63 @@ mod tests {
             }
         })
     }
+
+    #[test]
+    fn public_ips_allowed() {
+        test_storage(|| {
+            let tests = vec![
+                ("8.8.8.8", None),
+                ("100.64.1.2", None),
+                ("127.0.0.1", Some("a local ip")),
+                ("255.255.255.255", Some("a broadcast address")),
+                ("224.224.224.224", Some("a multicast address")),
+                ("8.255.255.255", Some("the subnet's broadcast address")),
+            ];
+
+            for (ip, reason) in tests {
+                let ip = Ipv4Addr::from_str(ip).unwrap();
+
+                let mut room = Room::new("test");
+                room.new_light(Light::new(ip, None)).unwrap();
+
+                let mut storage = Storage::new();
+                storage.allow_public_ips = true;
+                let res = storage.new_room(room);
+
+                match reason {
+                    Some(reason) => assert_eq!(res, Err(Error::invalid_ip(&ip, reason))),
+                    None => assert!(res.is_ok()),
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn room_subnet_overrides_classful() {
+        test_storage(|| {
+            let subnet = Ipv4Net::from_str("192.168.0.0/22").unwrap();
+            let mut storage = Storage::new();
+
+            // classfully the network address, but a host in the /22
+            let ip = Ipv4Addr::new(192, 168, 1, 0);
+            let mut room = Room::new("test").with_subnet(subnet);
+            room.new_light(Light::new(ip, None)).unwrap();
+            let room_id = storage.new_room(room).unwrap();
+
+            let ip = Ipv4Addr::new(192, 168, 3, 255);
+            let res = storage.new_light(&room_id, Light::new(ip, None));
+            assert_eq!(
+                res,
+                Err(Error::invalid_ip(&ip, "the subnet's broadcast address"))
+            );
+
+            // IPs outside the subnet fall back to their classful network
+            let ip = Ipv4Addr::new(192, 168, 4, 0);
+            let res = storage.new_light(&room_id, Light::new(ip, None));
+            assert_eq!(
+                res,
+                Err(Error::invalid_ip(&ip, "the subnet's network address"))
+            );
+        })
+    }
 }
@@ -0,0 +1,27 @@
+use std::sync::{Mutex, MutexGuard};
+
+use log::error;
+
+/// Lock the [Mutex], recovering the inner value if it was poisoned
+///
+/// A panic while holding the lock would otherwise poison it and cause
+/// every following lock attempt to panic as well. The state behind our
+/// locks is always left usable, so it's safer to log and carry on.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Mutex;
+/// use riz::lock;
+///
+/// let mutex = Mutex::new(1);
+/// *lock(&mutex) += 1;
+/// assert_eq!(*lock(&mutex), 2);
+/// ```
+///
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| {
+        error!("Recovering from poisoned lock");
+        e.into_inner()
+    })
+}
@@ -1,25 +1,148 @@
-use std::net::Ipv4Addr;
+use std::collections::{HashMap, VecDeque};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
 use std::sync::{
-    mpsc::{self, Sender},
-    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, Sender},
+    Arc, Condvar, Mutex,
 };
 use std::thread;
+use std::time::{Duration, Instant};
 
 use actix_web::web::Data;
-use log::{error, info};
+use log::{debug, error, info};
+use uuid::Uuid;
 
 use crate::{
-    models::{Light, LightRequest, LightingResponse, Payload},
-    Error, Result, Storage,
+    models::{Light, LightRequest, LightStatus, LightingResponse, Operation, Payload},
+    sync::lock,
+    AuditEntry, AuditLog, Error, RequestId, Result, Storage,
 };
 
+/// How long the reply thread waits to coalesce replies into one write
+const REPLY_BATCH_WINDOW: Duration = Duration::from_millis(250);
+
+/// Maximum number of replies processed per `rooms.json` write
+const REPLY_BATCH_SIZE: usize = 100;
+
+/// Source recorded in the audit log for commands dispatched by API routes
+const API_AUDIT_SOURCE: &str = "api";
+
+/// Maximum number of operations to remember outcomes for
+const MAX_OPERATIONS: usize = 100;
+
+/// Number of threads sending commands to bulbs
+const WORKER_THREADS: usize = 4;
+
+/// Priority jobs (status reads) taken in a row before a queued command
+const MAX_PRIORITY_STREAK: usize = 8;
+
+/// Port registered bulbs push their status to, see [Light::register]
+const PUSH_PORT: u16 = 38900;
+
+/// How often the push listener checks if the worker is shutting down
+const PUSH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Identical status pushed by a bulb within this long is only stored once
+const PUSH_REPEAT_WINDOW: Duration = Duration::from_secs(2);
+
 pub enum DispatchMessage {
-    Job((Ipv4Addr, LightRequest, Sender<ReplyMessage>)),
+    Job(
+        (
+            Ipv4Addr,
+            LightRequest,
+            Sender<ReplyMessage>,
+            Option<OperationTag>,
+            bool,
+            RequestId,
+        ),
+    ),
+    SyncJob(
+        (
+            Vec<SyncTarget>,
+            LightRequest,
+            Sender<ReplyMessage>,
+            bool,
+            RequestId,
+        ),
+    ),
+    StaggerJob(
+        (
+            Vec<SyncTarget>,
+            LightRequest,
+            Duration,
+            Sender<ReplyMessage>,
+            RequestId,
+        ),
+    ),
+    EnsureJob(
+        (
+            Ipv4Addr,
+            LightRequest,
+            u8,
+            Sender<ReplyMessage>,
+            Option<OperationTag>,
+            RequestId,
+        ),
+    ),
+    StatusJob(
+        (
+            Ipv4Addr,
+            Sender<Result<LightStatus>>,
+            Option<Sender<ReplyMessage>>,
+            RequestId,
+        ),
+    ),
+    Audit(Vec<AuditEntry>),
     Shutdown,
 }
 
+/// A light's IP and operation tag in a synchronized job
+type SyncTarget = (Ipv4Addr, Option<OperationTag>);
+
+/// A stored light and operation tag, resolved from a [SyncTarget]
+type LightTarget = (Light, Option<OperationTag>);
+
+/// Identifies the [Operation] and light a dispatched job belongs to
+pub struct OperationTag {
+    operations: Arc<Mutex<Operations>>,
+    id: Uuid,
+    light_id: Uuid,
+}
+
+/// Recently created operations, oldest are forgotten first
+#[derive(Default)]
+struct Operations {
+    ops: HashMap<Uuid, Operation>,
+    order: VecDeque<Uuid>,
+}
+
+impl Operations {
+    fn insert(&mut self, id: Uuid, op: Operation) {
+        while self.order.len() >= MAX_OPERATIONS {
+            if let Some(old) = self.order.pop_front() {
+                self.ops.remove(&old);
+            }
+        }
+        self.order.push_back(id);
+        self.ops.insert(id, op);
+    }
+
+    fn complete(&mut self, id: &Uuid, light_id: &Uuid, error: Option<String>) {
+        if let Some(op) = self.ops.get_mut(id) {
+            op.complete(light_id, error);
+        }
+    }
+
+    fn skip(&mut self, id: &Uuid, light_id: &Uuid) {
+        if let Some(op) = self.ops.get_mut(id) {
+            op.skip(light_id);
+        }
+    }
+}
+
 pub enum ReplyMessage {
-    Reply(LightingResponse),
+    Reply(LightingResponse, RequestId),
     Shutdown,
 }
 
@@ -29,29 +152,401 @@ pub struct Worker {
     reply_tx: Sender<ReplyMessage>,
     thread: Option<thread::JoinHandle<()>>,
     reply_thread: Option<thread::JoinHandle<()>>,
+    push_thread: Option<thread::JoinHandle<()>>,
+    push_stop: Arc<AtomicBool>,
+    stored: Data<Mutex<Storage>>,
+    operations: Arc<Mutex<Operations>>,
+    audit: AuditLog,
+}
+
+/// Where a queued command came from, and how it's sent
+///
+/// Passed to each of the [Worker]'s `create_*` methods. The source is
+/// recorded in the audit log, and the request ID is included in the
+/// worker's log lines for the command and the bulb's reply.
+///
+#[derive(Debug, Clone)]
+pub struct Origin {
+    source: &'static str,
+    request_id: RequestId,
+    verify: bool,
+}
+
+impl Origin {
+    /// A command sent by an API route, traced to the route's request
+    pub fn api(request_id: &RequestId) -> Self {
+        Origin {
+            source: API_AUDIT_SOURCE,
+            request_id: request_id.clone(),
+            verify: false,
+        }
+    }
+
+    /// A command sent by a background task, eg: `presence`
+    ///
+    /// There's no request to trace, so the command is given a new ID
+    ///
+    pub fn background(source: &'static str) -> Self {
+        Origin {
+            source,
+            request_id: RequestId::new(),
+            verify: false,
+        }
+    }
+
+    /// Record each bulb's actual state after a command, not the request
+    ///
+    /// Bulbs can drop part of a request (a scene sent with a color, for
+    /// example), so with `verify` the status is read back once the
+    /// command is sent and stored instead. Applies to single light,
+    /// operation and synchronized tasks, ensured tasks always do this.
+    ///
+    pub fn verifying(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Audit log entry for sending the request to a light
+    fn entry(
+        &self,
+        room_id: Option<Uuid>,
+        light_id: Option<Uuid>,
+        ip: Ipv4Addr,
+        req: &LightRequest,
+    ) -> AuditEntry {
+        AuditEntry::new(room_id, light_id, ip, req, self.source)
+    }
 }
 
-fn send_reply(resp: Result<LightingResponse>, tx: Sender<ReplyMessage>) {
+fn send_reply(
+    resp: Result<LightingResponse>,
+    tx: Sender<ReplyMessage>,
+    id: &RequestId,
+) -> Result<()> {
     match resp {
         Ok(resp) => {
-            if let Err(e) = tx.send(ReplyMessage::Reply(resp)) {
-                error!("Failed to sync response: {:?}", e);
+            if let Err(e) = tx.send(ReplyMessage::Reply(resp, id.clone())) {
+                error!("[{}] Failed to sync response: {:?}", id, e);
             }
+            Ok(())
         }
         Err(e) => {
-            error!("Lighting error: {}", e);
+            error!("[{}] Lighting error: {}", id, e);
+            Err(e)
         }
-    };
+    }
+}
+
+/// Find the stored light by IP, so commands use its own settings
+///
+/// Falls back to a bare light for IPs which aren't stored
+///
+fn stored_light(data: &Storage, ip: Ipv4Addr) -> Light {
+    data.light_by_ip(ip)
+        .cloned()
+        .unwrap_or_else(|| Light::new(ip, None))
+}
+
+/// Resolve each target's stored light, see [stored_light]
+fn stored_lights(data: &Data<Mutex<Storage>>, targets: Vec<SyncTarget>) -> Vec<LightTarget> {
+    let data = lock(data);
+    targets
+        .into_iter()
+        .map(|(ip, tag)| (stored_light(&data, ip), tag))
+        .collect()
 }
 
-fn handle_request(ip: Ipv4Addr, request: LightRequest, tx: Sender<ReplyMessage>) {
-    let light = Light::new(ip, None);
+/// Reply with the response, or only check it when `verify` is set
+///
+/// Verified requests reply once with the bulb's status after everything
+/// is sent instead, see [verify_state]
+///
+fn reply_unverified(
+    resp: Result<LightingResponse>,
+    tx: &Sender<ReplyMessage>,
+    verify: bool,
+    id: &RequestId,
+) -> Result<()> {
+    if !verify {
+        return send_reply(resp, tx.clone(), id);
+    }
+    resp.map(|_| ()).map_err(|e| {
+        error!("[{}] Lighting error: {}", id, e);
+        e
+    })
+}
+
+/// Read the bulb back and reply with its actual state
+fn verify_state(light: &Light, tx: Sender<ReplyMessage>, id: &RequestId) -> Result<()> {
+    light.settle();
+    let status = light.get_status()?;
+    send_reply(Ok(LightingResponse::status(light.ip(), status)), tx, id)
+}
+
+fn handle_request(
+    light: Light,
+    request: LightRequest,
+    tx: Sender<ReplyMessage>,
+    tag: Option<OperationTag>,
+    verify: bool,
+    id: RequestId,
+) {
+    let ip = light.ip();
+    debug!("[{}] Sending request to {}", id, ip);
     let payload = Payload::from(&request);
-    if payload.is_valid() {
-        send_reply(light.set(&payload), tx.clone());
+    let mut res = request.validate();
+    if let Err(e) = &res {
+        error!("[{}] Refusing request for {}: {}", id, ip, e);
+    } else if request.only_if_on() {
+        match is_on(&light, &tx, &id) {
+            Ok(true) => light.settle(),
+            Ok(false) => return skip(tag),
+            Err(e) => return complete(tag, Some(e.to_string())),
+        }
     }
-    if let Some(power) = request.power() {
-        send_reply(light.set_power(power), tx);
+
+    if res.is_ok() {
+        if payload.is_valid() {
+            res = reply_unverified(light.set(&payload), &tx, verify, &id);
+        }
+        if let Some(power) = request.power() {
+            if payload.is_valid() {
+                light.settle();
+            }
+            res = res.and(reply_unverified(light.set_power(power), &tx, verify, &id));
+        }
+        if verify && res.is_ok() {
+            res = verify_state(&light, tx, &id);
+        }
+    }
+
+    complete(tag, res.err().map(|e| e.to_string()));
+}
+
+/// Send the request and confirm the bulb applied it, see [Light::ensure]
+fn handle_ensured_request(
+    light: Light,
+    request: LightRequest,
+    attempts: u8,
+    tx: Sender<ReplyMessage>,
+    tag: Option<OperationTag>,
+    id: RequestId,
+) {
+    debug!("[{}] Sending ensured request to {}", id, light.ip());
+    if request.only_if_on() {
+        match is_on(&light, &tx, &id) {
+            Ok(true) => light.settle(),
+            Ok(false) => return skip(tag),
+            Err(e) => return complete(tag, Some(e.to_string())),
+        }
+    }
+
+    let ip = light.ip();
+    let res = light
+        .ensure(&request, attempts)
+        .and_then(|status| send_reply(Ok(LightingResponse::status(ip, status)), tx, &id));
+    if let Err(e) = &res {
+        error!("[{}] Failed to ensure request for {}: {}", id, ip, e);
+    }
+    complete(tag, res.err().map(|e| e.to_string()));
+}
+
+fn handle_sync_request(
+    lights: Vec<LightTarget>,
+    request: LightRequest,
+    tx: Sender<ReplyMessage>,
+    verify: bool,
+    id: RequestId,
+) {
+    debug!("[{}] Sending request to {} lights", id, lights.len());
+    let lights: Vec<LightTarget> = if request.only_if_on() {
+        lights
+            .into_iter()
+            .filter_map(|(light, tag)| match is_on(&light, &tx, &id) {
+                Ok(true) => Some((light, tag)),
+                Ok(false) => {
+                    skip(tag);
+                    None
+                }
+                Err(e) => {
+                    complete(tag, Some(e.to_string()));
+                    None
+                }
+            })
+            .collect()
+    } else {
+        lights
+    };
+
+    let (bulbs, tags): (Vec<Light>, Vec<_>) = lights.into_iter().unzip();
+    let payload = Payload::from(&request);
+
+    let results = match request
+        .validate()
+        .and_then(|_| Light::set_synchronized(&bulbs, &payload))
+    {
+        Ok(results) => results,
+        Err(e) => {
+            error!("[{}] Refusing synchronized request: {}", id, e);
+            for tag in tags {
+                complete(tag, Some(e.to_string()));
+            }
+            return;
+        }
+    };
+
+    for ((light, tag), resp) in bulbs.iter().zip(tags).zip(results) {
+        let mut res = reply_unverified(resp, &tx, verify, &id);
+        if let Some(power) = request.power() {
+            light.settle();
+            res = res.and(reply_unverified(light.set_power(power), &tx, verify, &id));
+        }
+        if verify && res.is_ok() {
+            res = verify_state(light, tx.clone(), &id);
+        }
+        complete(tag, res.err().map(|e| e.to_string()));
+    }
+}
+
+fn handle_staggered_request(
+    lights: Vec<LightTarget>,
+    request: LightRequest,
+    delay: Duration,
+    tx: Sender<ReplyMessage>,
+    id: RequestId,
+) {
+    for (i, (light, tag)) in lights.into_iter().enumerate() {
+        if i > 0 {
+            thread::sleep(delay);
+        }
+        handle_request(light, request.clone(), tx.clone(), tag, false, id.clone());
+    }
+}
+
+/// Read the light's status for a waiting caller
+///
+/// The fresh status is also sent as a reply, to update the stored status
+///
+fn handle_status_request(
+    light: Light,
+    caller: Sender<Result<LightStatus>>,
+    tx: Option<Sender<ReplyMessage>>,
+    id: RequestId,
+) {
+    let res = light.get_status();
+    if let (Ok(status), Some(tx)) = (&res, tx) {
+        let resp = LightingResponse::status(light.ip(), status.clone());
+        let _ = send_reply(Ok(resp), tx, &id);
+    }
+    if caller.send(res).is_err() {
+        info!(
+            "[{}] Dropping status for {}, nobody is waiting",
+            id,
+            light.ip()
+        );
+    }
+}
+
+/// Read the light's status to check if it's emitting
+///
+/// The fresh status is also sent as a reply, to update the stored status
+///
+fn is_on(light: &Light, tx: &Sender<ReplyMessage>, id: &RequestId) -> Result<bool> {
+    let status = light.get_status()?;
+    let on = status.emitting();
+    if !on {
+        info!("[{}] Skipping {}, the bulb is off", id, light.ip());
+    }
+    send_reply(
+        Ok(LightingResponse::status(light.ip(), status)),
+        tx.clone(),
+        id,
+    )?;
+    Ok(on)
+}
+
+/// Last status pushed by each bulb, to drop repeats
+///
+/// Bulbs send each `syncPilot` a few times in quick succession, and
+/// storing each one would write `rooms.json` for nothing
+///
+#[derive(Default)]
+struct PushedStatus {
+    last: HashMap<Ipv4Addr, (LightStatus, Instant)>,
+}
+
+impl PushedStatus {
+    /// Check if the status repeats the bulb's last push, remembering it
+    fn is_repeat(&mut self, ip: Ipv4Addr, status: &LightStatus, at: Instant) -> bool {
+        if let Some((last, last_at)) = self.last.get(&ip) {
+            if last == status && at.duration_since(*last_at) < PUSH_REPEAT_WINDOW {
+                return true;
+            }
+        }
+        self.last.insert(ip, (status.clone(), at));
+        false
+    }
+}
+
+/// Receive status pushed by registered bulbs until `stop` is set
+///
+/// Each `syncPilot` from a stored light is sent as a reply, unless it
+/// repeats the light's last push. Anything else is ignored.
+///
+fn receive_pushes(
+    socket: UdpSocket,
+    tx: Sender<ReplyMessage>,
+    stop: Arc<AtomicBool>,
+    data: Data<Mutex<Storage>>,
+) {
+    let mut pushed = PushedStatus::default();
+    let mut buffer = [0; 4096];
+    while !stop.load(Ordering::Relaxed) {
+        let (len, ip) = match socket.recv_from(&mut buffer) {
+            Ok((len, addr)) => match addr.ip() {
+                IpAddr::V4(ip) => (len, ip),
+                IpAddr::V6(_) => continue,
+            },
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => {
+                error!("Failed to receive pushed status: {}", e);
+                continue;
+            }
+        };
+
+        let status = match LightStatus::from_sync_pilot(&buffer[..len]) {
+            Some(status) => status,
+            None => continue,
+        };
+        if lock(&data).light_by_ip(ip).is_none() {
+            debug!("Ignoring status pushed from unknown {}", ip);
+            continue;
+        }
+
+        if !pushed.is_repeat(ip, &status, Instant::now()) {
+            let id = RequestId::new();
+            debug!("[{}] Status pushed from {}", id, ip);
+            let resp = LightingResponse::status(ip, status);
+            if tx.send(ReplyMessage::Reply(resp, id)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Record that a job was skipped in its operation, if it has one
+fn skip(tag: Option<OperationTag>) {
+    if let Some(tag) = tag {
+        let mut operations = lock(&tag.operations);
+        operations.skip(&tag.id, &tag.light_id);
+    }
+}
+
+/// Record the outcome of a job in its operation, if it has one
+fn complete(tag: Option<OperationTag>, error: Option<String>) {
+    if let Some(tag) = tag {
+        let mut operations = lock(&tag.operations);
+        operations.complete(&tag.id, &tag.light_id, error);
     }
 }
 
@@ -63,16 +558,50 @@ impl Worker {
     pub fn new(data: Data<Mutex<Storage>>) -> Self {
         let (tx, rx) = mpsc::channel::<DispatchMessage>();
         let (reply_tx, reply_rx) = mpsc::channel::<ReplyMessage>();
-        let pool = ThreadPool::new(4);
+        let pool = ThreadPool::new(WORKER_THREADS);
+        let stored = Data::clone(&data);
+        let pushes = Data::clone(&data);
+        let audit = AuditLog::new();
+        let audit_log = audit.clone();
 
         let handle = thread::spawn(move || {
             for msg in rx {
                 match msg {
                     DispatchMessage::Job(msg) => {
+                        let light = stored_light(&lock(&stored), msg.0);
+                        pool.execute(move || {
+                            handle_request(light, msg.1, msg.2, msg.3, msg.4, msg.5);
+                        });
+                    }
+                    DispatchMessage::SyncJob(msg) => {
+                        let lights = stored_lights(&stored, msg.0);
+                        pool.execute(move || {
+                            handle_sync_request(lights, msg.1, msg.2, msg.3, msg.4);
+                        });
+                    }
+                    DispatchMessage::EnsureJob(msg) => {
+                        let light = stored_light(&lock(&stored), msg.0);
                         pool.execute(move || {
-                            handle_request(msg.0, msg.1, msg.2);
+                            handle_ensured_request(light, msg.1, msg.2, msg.3, msg.4, msg.5);
                         });
                     }
+                    DispatchMessage::StaggerJob(msg) => {
+                        let lights = stored_lights(&stored, msg.0);
+                        pool.execute(move || {
+                            handle_staggered_request(lights, msg.1, msg.2, msg.3, msg.4);
+                        });
+                    }
+                    DispatchMessage::StatusJob(msg) => {
+                        let light = stored_light(&lock(&stored), msg.0);
+                        pool.execute_priority(move || {
+                            handle_status_request(light, msg.1, msg.2, msg.3);
+                        });
+                    }
+                    DispatchMessage::Audit(entries) => {
+                        for entry in &entries {
+                            audit_log.record(entry);
+                        }
+                    }
                     DispatchMessage::Shutdown => {
                         return;
                     }
@@ -81,16 +610,42 @@ impl Worker {
         });
 
         let reply_handle = thread::spawn(move || {
-            for msg in reply_rx {
+            while let Ok(msg) = reply_rx.recv() {
+                let mut batch = Vec::new();
+                let mut shutdown = false;
+
                 match msg {
-                    ReplyMessage::Reply(resp) => {
-                        let mut data = data.lock().unwrap();
-                        data.process_reply(&resp);
+                    ReplyMessage::Reply(resp, id) => {
+                        debug!("[{}] Reply from {}", id, resp.ip());
+                        batch.push(resp);
                     }
-                    ReplyMessage::Shutdown => {
-                        return;
+                    ReplyMessage::Shutdown => shutdown = true,
+                }
+
+                // coalesce any replies arriving shortly after the first
+                let deadline = Instant::now() + REPLY_BATCH_WINDOW;
+                while !shutdown && batch.len() < REPLY_BATCH_SIZE {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    match reply_rx.recv_timeout(timeout) {
+                        Ok(ReplyMessage::Reply(resp, id)) => {
+                            debug!("[{}] Reply from {}", id, resp.ip());
+                            batch.push(resp);
+                        }
+                        Ok(ReplyMessage::Shutdown) => shutdown = true,
+                        Err(_) => break,
+                    }
+                }
+
+                if !batch.is_empty() {
+                    let mut data = lock(&data);
+                    if let Err(e) = data.process_replies(&batch) {
+                        error!("Failed to persist replies: {}", e);
                     }
                 }
+
+                if shutdown {
+                    return;
+                }
             }
         });
 
@@ -99,34 +654,404 @@ impl Worker {
             reply_tx,
             thread: Some(handle),
             reply_thread: Some(reply_handle),
+            push_thread: None,
+            push_stop: Arc::new(AtomicBool::new(false)),
+            stored: pushes,
+            operations: Arc::new(Mutex::new(Operations::default())),
+            audit,
         }
     }
 
+    /// Number of threads sending commands to bulbs
+    pub fn threads(&self) -> usize {
+        WORKER_THREADS
+    }
+
     /// Queue a lighting setting change for the light by IP
     ///
     /// The work will be executed in the next available thread
     ///
-    pub fn create_task(&mut self, ip: Ipv4Addr, req: LightRequest) -> Result<()> {
-        match self
-            .tx
-            .send(DispatchMessage::Job((ip, req, self.reply_tx.clone())))
-        {
-            Ok(_) => {}
-            Err(e) => return Err(Error::Dispatch(e)),
+    pub fn create_task(&mut self, ip: Ipv4Addr, req: LightRequest, origin: &Origin) -> Result<()> {
+        self.record(vec![origin.entry(None, None, ip, &req)])?;
+        self.dispatch(ip, req, None, origin)
+    }
+
+    /// Queue a lighting setting change for a known light in a room
+    ///
+    /// Works like [Self::create_task], but the room and light IDs are
+    /// included in the audit log
+    ///
+    pub fn create_light_task(
+        &mut self,
+        room_id: &Uuid,
+        light_id: &Uuid,
+        ip: Ipv4Addr,
+        req: LightRequest,
+        origin: &Origin,
+    ) -> Result<()> {
+        self.record(vec![origin.entry(
+            Some(*room_id),
+            Some(*light_id),
+            ip,
+            &req,
+        )])?;
+        self.dispatch(ip, req, None, origin)
+    }
+
+    /// Record a command a route sent a light directly in the audit log
+    ///
+    /// For commands which bypass the queue (eg: `ensure=true`), the
+    /// queued ones are recorded when they're created
+    ///
+    pub fn record_light_command(
+        &self,
+        room_id: &Uuid,
+        light_id: &Uuid,
+        ip: Ipv4Addr,
+        req: &LightRequest,
+        origin: &Origin,
+    ) -> Result<()> {
+        self.record(vec![origin.entry(Some(*room_id), Some(*light_id), ip, req)])
+    }
+
+    /// Queue a lighting setting change for many lights, tracking outcomes
+    ///
+    /// Provide the light IDs and their IPs. The outcome for each light
+    /// can be read with [Self::operation] using the returned [Uuid]
+    ///
+    /// # Errors
+    ///   [Error::Dispatch] if any job could not be queued, lights which
+    ///   were not queued are marked as errors in the operation
+    ///
+    pub fn create_operation(
+        &mut self,
+        room_id: &Uuid,
+        lights: &[(Uuid, Ipv4Addr)],
+        req: LightRequest,
+        origin: &Origin,
+    ) -> Result<Uuid> {
+        let id = self.new_operation(lights);
+        let recorded = self.record_lights(room_id, lights, &req, origin);
+
+        let mut res = recorded.map(|_| id);
+        for (light_id, ip) in lights {
+            let tag = self.tag(&id, light_id);
+            if let Err(e) = self.dispatch(*ip, req.clone(), Some(tag), origin) {
+                let mut operations = lock(&self.operations);
+                operations.complete(&id, light_id, Some(e.to_string()));
+                res = Err(e);
+            }
+        }
+        res
+    }
+
+    /// Queue a lighting setting change for many lights, confirming each
+    ///
+    /// Works like [Self::create_operation], but each light's status is
+    /// read back and the request resent until it matches, up to `attempts`
+    /// times, see [Light::ensure]. Lights which never match are marked as
+    /// errors in the operation.
+    ///
+    /// # Errors
+    ///   [Error::Dispatch] if any job could not be queued, lights which
+    ///   were not queued are marked as errors in the operation
+    ///
+    pub fn create_ensured_operation(
+        &mut self,
+        room_id: &Uuid,
+        lights: &[(Uuid, Ipv4Addr)],
+        req: LightRequest,
+        attempts: u8,
+        origin: &Origin,
+    ) -> Result<Uuid> {
+        let id = self.new_operation(lights);
+        let recorded = self.record_lights(room_id, lights, &req, origin);
+
+        let mut res = recorded.map(|_| id);
+        for (light_id, ip) in lights {
+            let tag = self.tag(&id, light_id);
+            let job = (
+                *ip,
+                req.clone(),
+                attempts,
+                self.reply_tx.clone(),
+                Some(tag),
+                origin.request_id.clone(),
+            );
+            if let Err(e) = self.tx.send(DispatchMessage::EnsureJob(job)) {
+                let e = Error::Dispatch(Box::new(e));
+                let mut operations = lock(&self.operations);
+                operations.complete(&id, light_id, Some(e.to_string()));
+                res = Err(e);
+            }
+        }
+        res
+    }
+
+    /// Queue a lighting setting change for many lights to apply together
+    ///
+    /// Works like [Self::create_operation], but the settings are sent to
+    /// all lights back to back from a single thread, see
+    /// [Light::set_synchronized]. Useful to keep scenes in phase.
+    ///
+    /// # Errors
+    ///   [Error::Dispatch] if the job could not be queued, all lights are
+    ///   then marked as errors in the operation
+    ///
+    pub fn create_sync_operation(
+        &mut self,
+        room_id: &Uuid,
+        lights: &[(Uuid, Ipv4Addr)],
+        req: LightRequest,
+        origin: &Origin,
+    ) -> Result<Uuid> {
+        let id = self.new_operation(lights);
+        let jobs = lights
+            .iter()
+            .map(|(light_id, ip)| (*ip, Some(self.tag(&id, light_id))))
+            .collect();
+
+        let res = self
+            .record_lights(room_id, lights, &req, origin)
+            .and_then(|_| {
+                self.tx
+                    .send(DispatchMessage::SyncJob((
+                        jobs,
+                        req,
+                        self.reply_tx.clone(),
+                        origin.verify,
+                        origin.request_id.clone(),
+                    )))
+                    .map_err(|e| Error::Dispatch(Box::new(e)))
+            });
+        self.complete_unsent(&id, lights, res)
+    }
+
+    /// Queue a lighting setting change for many lights, one at a time
+    ///
+    /// Works like [Self::create_operation], but each light is sent the
+    /// request in turn from a single thread, waiting `delay` in between.
+    /// Useful for rebooting a room without every bulb dropping off the
+    /// network at once.
+    ///
+    /// # Errors
+    ///   [Error::Dispatch] if the job could not be queued, all lights are
+    ///   then marked as errors in the operation
+    ///
+    pub fn create_staggered_operation(
+        &mut self,
+        room_id: &Uuid,
+        lights: &[(Uuid, Ipv4Addr)],
+        req: LightRequest,
+        delay: Duration,
+        origin: &Origin,
+    ) -> Result<Uuid> {
+        let id = self.new_operation(lights);
+        let jobs = lights
+            .iter()
+            .map(|(light_id, ip)| (*ip, Some(self.tag(&id, light_id))))
+            .collect();
+
+        let res = self
+            .record_lights(room_id, lights, &req, origin)
+            .and_then(|_| {
+                self.tx
+                    .send(DispatchMessage::StaggerJob((
+                        jobs,
+                        req,
+                        delay,
+                        self.reply_tx.clone(),
+                        origin.request_id.clone(),
+                    )))
+                    .map_err(|e| Error::Dispatch(Box::new(e)))
+            });
+        self.complete_unsent(&id, lights, res)
+    }
+
+    /// Start tracking a new operation with all lights pending
+    fn new_operation(&self, lights: &[(Uuid, Ipv4Addr)]) -> Uuid {
+        let id = Uuid::new_v4();
+        let ids: Vec<Uuid> = lights.iter().map(|(light_id, _)| *light_id).collect();
+        lock(&self.operations).insert(id, Operation::new(&ids));
+        id
+    }
+
+    /// Mark all lights as errors in the operation if its job wasn't queued
+    fn complete_unsent(
+        &self,
+        id: &Uuid,
+        lights: &[(Uuid, Ipv4Addr)],
+        res: Result<()>,
+    ) -> Result<Uuid> {
+        match res {
+            Ok(_) => Ok(*id),
+            Err(e) => {
+                let mut operations = lock(&self.operations);
+                for (light_id, _) in lights {
+                    operations.complete(id, light_id, Some(e.to_string()));
+                }
+                Err(e)
+            }
         }
+    }
+
+    fn tag(&self, id: &Uuid, light_id: &Uuid) -> OperationTag {
+        OperationTag {
+            operations: Arc::clone(&self.operations),
+            id: *id,
+            light_id: *light_id,
+        }
+    }
+
+    /// Accessor for the log of all dispatched commands
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// Queue the entries to be written to the audit log
+    ///
+    /// They're written from the dispatch thread, ahead of the jobs they
+    /// describe, so callers never wait on the log file
+    ///
+    fn record(&self, entries: Vec<AuditEntry>) -> Result<()> {
+        self.tx
+            .send(DispatchMessage::Audit(entries))
+            .map_err(|e| Error::Dispatch(Box::new(e)))
+    }
+
+    /// Record the request to each of the lights in a room, see [Self::record]
+    fn record_lights(
+        &self,
+        room_id: &Uuid,
+        lights: &[(Uuid, Ipv4Addr)],
+        req: &LightRequest,
+        origin: &Origin,
+    ) -> Result<()> {
+        let entries = lights
+            .iter()
+            .map(|(light_id, ip)| origin.entry(Some(*room_id), Some(*light_id), *ip, req))
+            .collect();
+        self.record(entries)
+    }
+
+    /// Read the current outcomes of an operation by ID (returns clone)
+    pub fn operation(&self, id: &Uuid) -> Option<Operation> {
+        lock(&self.operations).ops.get(id).cloned()
+    }
+
+    fn dispatch(
+        &self,
+        ip: Ipv4Addr,
+        req: LightRequest,
+        tag: Option<OperationTag>,
+        origin: &Origin,
+    ) -> Result<()> {
+        match self.tx.send(DispatchMessage::Job((
+            ip,
+            req,
+            self.reply_tx.clone(),
+            tag,
+            origin.verify,
+            origin.request_id.clone(),
+        ))) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::Dispatch(Box::new(e))),
+        }
+    }
+
+    /// Queue a status read for the light by IP, ahead of setting changes
+    ///
+    /// Reads are taken by the next free thread before any queued setting
+    /// changes, so a busy room doesn't hold them up. The status is also
+    /// written back to the stored light.
+    ///
+    /// Blocks on the returned [Receiver] for the result, which will be
+    /// [Error::NoReply] if the worker shuts down first
+    ///
+    pub fn read_status(
+        &mut self,
+        ip: Ipv4Addr,
+        id: &RequestId,
+    ) -> Result<Receiver<Result<LightStatus>>> {
+        let reply_tx = self.reply_tx.clone();
+        self.queue_status(ip, Some(reply_tx), id)
+    }
+
+    /// Queue a status read for the light by IP, without storing it
+    ///
+    /// The same as [Self::read_status], but the stored light is left as
+    /// it was, for read-only observers
+    ///
+    pub fn peek_status(
+        &mut self,
+        ip: Ipv4Addr,
+        id: &RequestId,
+    ) -> Result<Receiver<Result<LightStatus>>> {
+        self.queue_status(ip, None, id)
+    }
+
+    /// Queue a status read, sending it as a reply when `reply_tx` is given
+    fn queue_status(
+        &mut self,
+        ip: Ipv4Addr,
+        reply_tx: Option<Sender<ReplyMessage>>,
+        id: &RequestId,
+    ) -> Result<Receiver<Result<LightStatus>>> {
+        let (caller, rx) = mpsc::channel();
+        match self.tx.send(DispatchMessage::StatusJob((
+            ip,
+            caller,
+            reply_tx,
+            id.clone(),
+        ))) {
+            Ok(_) => Ok(rx),
+            Err(e) => Err(Error::Dispatch(Box::new(e))),
+        }
+    }
+
+    /// Listen for status pushed by registered bulbs, see [Light::register]
+    ///
+    /// Pushed status is stored like a reply, see [Self::queue_update], so
+    /// changes made with the wall switch or the Wiz app are picked up
+    /// without polling. Pushes from unknown IPs are ignored, and repeats
+    /// of a bulb's last push are only stored once. Does nothing if
+    /// already listening.
+    ///
+    /// # Errors
+    ///   [Error::Socket] if UDP port 38900 can't be bound
+    ///
+    pub fn listen_for_pushes(&mut self) -> Result<()> {
+        if self.push_thread.is_some() {
+            return Ok(());
+        }
+
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, PUSH_PORT))
+            .map_err(|e| Error::socket("bind", e))?;
+        socket
+            .set_read_timeout(Some(PUSH_POLL_INTERVAL))
+            .map_err(|e| Error::socket("set_read_timeout", e))?;
+
+        let tx = self.reply_tx.clone();
+        let stop = Arc::clone(&self.push_stop);
+        let data = Data::clone(&self.stored);
+        self.push_thread = Some(thread::spawn(move || {
+            receive_pushes(socket, tx, stop, data)
+        }));
+        info!("Listening for pushed status on port {}", PUSH_PORT);
         Ok(())
     }
 
     /// Queue an update from a lighting setting change
     ///
-    /// This is the reply path from [Self::create_task]
+    /// This is the reply path from [Self::create_task]. Replies have
+    /// their own thread, so they're never queued behind bulb commands
     ///
     /// This will alert the dispatch they need to take the [Storage]
     /// [Data] [Mutex] to write the response to the affected
     /// [Light] and update `rooms.json`
     ///
-    pub fn queue_update(&mut self, resp: LightingResponse) -> Result<()> {
-        match self.reply_tx.send(ReplyMessage::Reply(resp)) {
+    pub fn queue_update(&mut self, resp: LightingResponse, id: &RequestId) -> Result<()> {
+        match self.reply_tx.send(ReplyMessage::Reply(resp, id.clone())) {
             Ok(_) => Ok(()),
             Err(e) => Err(Error::Reply(e)),
         }
@@ -146,6 +1071,13 @@ impl Drop for Worker {
             });
         }
 
+        self.push_stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.push_thread.take() {
+            thread.join().unwrap_or_else(|_| {
+                error!("failed to shutdown push listener");
+            });
+        }
+
         if let Err(e) = self.reply_tx.send(ReplyMessage::Shutdown) {
             error!("Failed to send response listener shutdown: {}", e);
         }
@@ -173,9 +1105,38 @@ enum Message {
     Shutdown,
 }
 
+/// Messages waiting for a runner, `high` is taken first
+///
+/// After [MAX_PRIORITY_STREAK] `high` messages in a row, one `normal`
+/// message is let through, so a flood of reads can't starve commands
+///
+#[derive(Default)]
+struct Queue {
+    high: VecDeque<Message>,
+    normal: VecDeque<Message>,
+    streak: usize,
+}
+
+impl Queue {
+    fn pop(&mut self) -> Option<Message> {
+        if self.streak >= MAX_PRIORITY_STREAK || self.high.is_empty() {
+            self.streak = 0;
+            if let Some(msg) = self.normal.pop_front() {
+                return Some(msg);
+            }
+        }
+        let msg = self.high.pop_front()?;
+        self.streak += 1;
+        Some(msg)
+    }
+}
+
+/// The shared [Queue], and a signal for runners when it's pushed to
+type SharedQueue = Arc<(Mutex<Queue>, Condvar)>;
+
 struct ThreadPool {
     runners: Vec<Runner>,
-    sender: Sender<Message>,
+    queue: SharedQueue,
 }
 
 impl ThreadPool {
@@ -189,32 +1150,49 @@ impl ThreadPool {
     pub fn new(size: usize) -> ThreadPool {
         assert!(size > 0); // return a Result type if this is recoverable
 
-        let (sender, receiver) = mpsc::channel();
-
-        let receiver = Arc::new(Mutex::new(receiver));
+        let queue = SharedQueue::default();
 
         let mut runners = Vec::with_capacity(size);
 
         for id in 0..size {
-            runners.push(Runner::new(id, Arc::clone(&receiver)));
+            runners.push(Runner::new(id, Arc::clone(&queue)));
         }
 
-        ThreadPool { runners, sender }
+        ThreadPool { runners, queue }
     }
 
     pub fn execute<F>(&self, f: F)
     where
         F: FnOnce() + Send + 'static,
     {
-        self.sender.send(Message::Job(Box::new(f))).unwrap();
+        self.push(Message::Job(Box::new(f)), false);
+    }
+
+    /// Execute the job ahead of most queued by [Self::execute], see [Queue]
+    pub fn execute_priority<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.push(Message::Job(Box::new(f)), true);
+    }
+
+    fn push(&self, msg: Message, high: bool) {
+        let (queue, ready) = &*self.queue;
+        let mut queue = lock(queue);
+        if high {
+            queue.high.push_back(msg);
+        } else {
+            queue.normal.push_back(msg);
+        }
+        ready.notify_one();
     }
 }
 
 impl Drop for ThreadPool {
     fn drop(&mut self) {
         info!("shutting down runners");
-        for _ in &mut self.runners {
-            self.sender.send(Message::Shutdown).unwrap();
+        for _ in 0..self.runners.len() {
+            self.push(Message::Shutdown, false);
         }
 
         for runner in &mut self.runners {
@@ -230,9 +1208,18 @@ struct Runner {
 }
 
 impl Runner {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Self {
+    fn new(id: usize, queue: SharedQueue) -> Self {
         let thread = thread::spawn(move || loop {
-            let job = receiver.lock().unwrap().recv().unwrap();
+            let job = {
+                let (queue, ready) = &*queue;
+                let mut queue = lock(queue);
+                loop {
+                    match queue.pop() {
+                        Some(msg) => break msg,
+                        None => queue = ready.wait(queue).unwrap_or_else(|e| e.into_inner()),
+                    }
+                }
+            };
             match job {
                 Message::Job(j) => {
                     j.call_box();
@@ -249,3 +1236,63 @@ impl Runner {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pushed_repeats_dropped() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let on = LightStatus::from(&crate::models::PowerMode::On);
+        let off = LightStatus::from(&crate::models::PowerMode::Off);
+        let start = Instant::now();
+
+        let mut pushed = PushedStatus::default();
+        assert!(!pushed.is_repeat(ip, &on, start));
+        assert!(pushed.is_repeat(ip, &on, start + Duration::from_millis(100)));
+        assert!(!pushed.is_repeat(Ipv4Addr::new(10, 1, 2, 4), &on, start));
+        assert!(!pushed.is_repeat(ip, &off, start + Duration::from_millis(200)));
+        assert!(!pushed.is_repeat(ip, &off, start + PUSH_REPEAT_WINDOW * 2));
+    }
+
+    #[test]
+    fn origin_recorded_as_source() {
+        let ip = Ipv4Addr::new(10, 1, 2, 3);
+        let req = LightRequest::from_power(crate::models::PowerMode::On);
+
+        let origin = Origin::api(&RequestId::new()).verifying(true);
+        assert!(origin.verify);
+        assert_eq!(
+            origin.entry(None, None, ip, &req).source(),
+            API_AUDIT_SOURCE
+        );
+
+        let origin = Origin::background("presence");
+        assert!(!origin.verify);
+        assert_eq!(origin.entry(None, None, ip, &req).source(), "presence");
+    }
+
+    #[test]
+    fn queue_priority_without_starving() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let job = |name: &'static str| {
+            let order = Arc::clone(&order);
+            Message::Job(Box::new(move || lock(&order).push(name)))
+        };
+
+        let mut queue = Queue::default();
+        queue.normal.push_back(job("normal"));
+        for _ in 0..MAX_PRIORITY_STREAK + 1 {
+            queue.high.push_back(job("high"));
+        }
+
+        while let Some(Message::Job(j)) = queue.pop() {
+            j.call_box();
+        }
+
+        let mut expected = vec!["high"; MAX_PRIORITY_STREAK];
+        expected.extend(["normal", "high"]);
+        assert_eq!(*lock(&order), expected);
+    }
+}